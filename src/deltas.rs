@@ -1,18 +1,15 @@
-#![allow(dead_code)]
-
+use std::io::IsTerminal;
 use std::path::PathBuf;
+use std::sync::Arc;
 
 use anyhow::{Context, Result};
 use clap::Parser;
 use log::info;
 
-mod models;
-mod steps;
-mod utils;
-
-use models::config::Config;
-use steps::generate::Generator;
-use utils::logging::init_logger;
+use bouf::models::args::LogFormat;
+use bouf::utils::logging::init_logger;
+use bouf::utils::progress::{set_progress_handler, ConsoleProgress};
+use bouf::{Config, Generator};
 
 #[derive(Parser, Debug)]
 #[command(about, long_about = None)]
@@ -25,9 +22,13 @@ fn main() -> Result<()> {
     let args: Args = Args::parse();
     info!("Loading config...");
     let mut conf = Config::from_file(&args.config)?;
-    init_logger(conf.general.log_level.as_str());
+    init_logger(conf.general.log_level.as_str(), LogFormat::Text);
+    if std::io::stdout().is_terminal() {
+        set_progress_handler(Arc::new(ConsoleProgress::default()));
+    }
 
     conf.validate(true, false)?;
+    conf.create_previous_dirs()?;
     let mut gen = Generator::init(&conf, false);
     info!("Running generator...");
     gen.create_patches().context("Creating delta patches failed!")?;
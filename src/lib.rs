@@ -0,0 +1,18 @@
+//! bouf - Building OBS Updates Fast(er)
+//!
+//! The pipeline can either be run as a whole (see [`steps::pipeline::execute`]) or step by step
+//! using [`Preparator`], [`Generator`], and [`Packaging`] with a validated [`Config`].
+//! Progress of long-running operations is reported to the handler set with [`set_progress_handler`].
+
+pub mod models;
+pub mod steps;
+pub mod utils;
+
+pub use models::config::Config;
+pub use models::journal::{Journal, Stage};
+pub use models::manifest::Manifest;
+pub use steps::generate::Generator;
+pub use steps::package::Packaging;
+pub use steps::prepare::Preparator;
+pub use utils::progress::{set_progress_handler, ProgressHandler};
+pub use utils::sign::Signer;
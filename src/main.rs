@@ -1,30 +1,62 @@
+use std::io::IsTerminal;
+use std::sync::Arc;
+
 use anyhow::{Context, Result};
-use clap::Parser;
+use clap::error::ErrorKind;
+use clap::{CommandFactory, Parser};
 use log::info;
 
-mod models;
-mod steps;
-mod utils;
-
-use models::args::MainArgs;
-use models::config::Config;
-use models::manifest::Manifest;
-use steps::generate::Generator;
-use steps::package::Packaging;
-use steps::prepare::Preparator;
-use utils::logging::init_logger;
-use utils::sign::Signer;
+use bouf::models::args::{Command, LogFormat, MainArgs};
+use bouf::steps::pipeline::execute;
+use bouf::steps::verify::verify_repro;
+use bouf::utils::events::JsonEvents;
+use bouf::utils::logging::init_logger;
+use bouf::utils::progress::{emit, set_progress_handler, ConsoleProgress, Event};
+use bouf::Config;
 
 fn main() -> Result<()> {
     let args: MainArgs = MainArgs::parse();
-    let mut conf = Config::from_file(args.config.as_path())?;
+
+    // The plan always covers all stages
+    if args.dry_run && !matches!(args.command, None | Some(Command::Run)) {
+        MainArgs::command()
+            .error(
+                ErrorKind::ArgumentConflict,
+                "--dry-run can only be used with \"run\" (the default command)",
+            )
+            .exit()
+    }
+
+    // Comparing output directories does not require a config or version
+    if let Some(Command::VerifyRepro { a, b }) = &args.command {
+        init_logger(if args.verbose { "trace" } else { "info" }, args.log_format);
+        return verify_repro(a, b);
+    }
+
+    let (Some(config), Some(_)) = (&args.config, &args.version) else {
+        MainArgs::command()
+            .error(
+                ErrorKind::MissingRequiredArgument,
+                "--config and --version are required (except for \"verify-repro\")",
+            )
+            .exit()
+    };
+    let mut conf = Config::from_file(config)?;
 
     let level = if args.verbose {
         "trace"
     } else {
         conf.general.log_level.as_str()
     };
-    init_logger(level);
+    init_logger(level, args.log_format);
+
+    if let Some(events) = &args.events {
+        set_progress_handler(Arc::new(JsonEvents::to_file(events)?));
+    } else if args.log_format == LogFormat::Json {
+        set_progress_handler(Arc::new(JsonEvents::stderr()));
+    } else if std::io::stdout().is_terminal() {
+        set_progress_handler(Arc::new(ConsoleProgress::default()));
+    }
 
     // Only validate config
     if args.test_config {
@@ -41,66 +73,17 @@ fn main() -> Result<()> {
     }
 
     info!("Verifying config validity...");
-    conf.apply_args(&args).context("Config invalid")?;
-    info!("Config Ok!");
-
-    info!("bouf process started with the following locations set:");
-    info!(" - Input dir: {}", &conf.env.input_dir.display());
-    info!(" - Previous versions dir: {}", &conf.env.previous_dir.display());
-    info!(" - Output dir: {}", &conf.env.output_dir.display());
-
-    if !args.updater_data_only {
-        let prep = Preparator::init(&conf);
-        prep.run().context("Preparation failed")?;
-    } else {
-        info!("Skipped preparation, this will also disable installer/zip creation.")
-    }
-
-    let mut manifest: Option<Manifest> = None;
-    if !args.packaging_only {
-        // Create deltas and manifest
-        info!("Creating manifest and patches...");
-        let generator = Generator::init(&conf, !args.updater_data_only);
-        manifest = Some(generator.run(args.skip_patches).context("Error during generator run")?);
-    }
-
-    let packager = Packaging::init(&conf);
-    // Create NSIS/ZIP
-    if !conf.package.installer.skip && !args.updater_data_only {
-        info!("Creating Installer");
-        packager.run_nsis().context("NSIS creation/signing failed")?;
-    } else {
-        info!("Skipping installer creation...")
-    }
-
-    if !args.updater_data_only && !conf.package.zip.skip {
-        // Create PDB and install folder ZIPs
-        info!("Creating zip files...");
-        packager.create_zips().context("Creating zip files failed")?;
-        info!("ZIP files created successfully!")
-    } else if !conf.package.zip.skip {
-        info!(" Skipping ZIP creation as preparation was skipped...")
-    }
-
-    if let Some(mut mf) = manifest {
-        // Sign manifest if it was created
-        info!("Finalising manifest...");
-        let manifest_file = packager
-            .finalise_manifest(&mut mf)
-            .context("Finalising manifest failed")?;
-
-        if !conf.package.updater.skip_sign {
-            info!("Signing manifest...");
-            let mut signer = Signer::init(conf.package.updater.private_key.as_ref());
-            signer.sign_file(&manifest_file).context("Signing file failed")?;
-        }
-    }
+    let result = conf.apply_args(&args).context("Config invalid").and_then(|_| {
+        info!("Config Ok!");
+        execute(&conf, &args)
+    });
 
-    if !args.updater_data_only && conf.post.copy_to_old {
-        info!("Copying install dir and PDBs to backup directory...");
-        steps::post::copy_to_old(&conf).context("Copying files failed")?;
+    if let Err(err) = &result {
+        emit(Event::Error {
+            message: err.to_string(),
+            causes: err.chain().skip(1).map(|c| c.to_string()).collect(),
+        });
     }
 
-    info!("*** Finished! ***");
-    Ok(())
+    result
 }
@@ -1,17 +1,20 @@
 use std::path::PathBuf;
 
-use clap::Parser;
+use clap::{Parser, Subcommand, ValueEnum};
 
 #[derive(Parser, Debug)]
 #[command(about, long_about = "Building OBS Updates Fast(er)")]
 pub struct MainArgs {
-    // Required
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    // Required (except for "verify-repro")
     /// Configuration file
     #[arg(short, long, value_name = "config.toml")]
-    pub config: PathBuf,
+    pub config: Option<PathBuf>,
     /// OBS main version
     #[arg(short, long, value_name = "Major.Minor.Patch[-(rc|beta)Num]")]
-    pub version: String,
+    pub version: Option<String>,
 
     // Optional version suffix
     /// Beta number
@@ -37,12 +40,12 @@ pub struct MainArgs {
     /// File containing release notes
     #[arg(long, value_name = "file.rtf")]
     pub notes_file: Option<PathBuf>,
-    /// Falls back to "UPDATER_PRIVATE_KEY" env var
+    /// Falls back to "UPDATER_PRIVATE_KEY" env var (may be specified multiple times)
     #[arg(long, value_name = "file.pem")]
-    pub private_key: Option<PathBuf>,
+    pub private_key: Vec<PathBuf>,
 
     // Optional flags
-    /// Create only delta patches and manifest
+    /// Create only delta patches and manifest (from the input instead of the prepared build)
     #[arg(long, default_value_t = false)]
     pub updater_data_only: bool,
     /// Only sign files and create ZIPs + Installer packages
@@ -63,10 +66,60 @@ pub struct MainArgs {
     /// Clear existing output directory
     #[arg(long, default_value_t = false)]
     pub clear_output: bool,
+    /// Resume previous run, skipping stages that were completed and whose inputs are unchanged
+    #[arg(long, default_value_t = false)]
+    pub resume: bool,
     /// Verbose logging
     #[arg(long, short = 'd', default_value_t = false)]
     pub verbose: bool,
+    /// Log output format ("json" also includes progress events)
+    #[arg(long, value_enum, value_name = "format", default_value_t = LogFormat::Text)]
+    pub log_format: LogFormat,
+    /// Write progress events, results, warnings, and errors to file (JSON lines)
+    #[arg(long, value_name = "events.jsonl")]
+    pub events: Option<PathBuf>,
     /// Test Config File
     #[arg(long, short, default_value_t = false)]
     pub test_config: bool,
+    /// Show what would be done without writing any files
+    #[arg(long, default_value_t = false)]
+    pub dry_run: bool,
+    /// Write dry-run plan to JSON file
+    #[arg(long, value_name = "plan.json", requires = "dry_run")]
+    pub plan_file: Option<PathBuf>,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LogFormat {
+    Text,
+    /// One JSON object per line
+    Json,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Run all stages (default)
+    Run,
+    /// Copy, codesign, and strip the new build into the output directory
+    Prepare,
+    /// Create updater files, delta patches, and the manifest from the prepared build
+    Generate,
+    /// Create installer and ZIP files from the prepared build
+    Package,
+    /// Sign the manifest (and file index) created by "generate"
+    Sign,
+    /// Copy the prepared build and PDBs to the previous builds directory
+    Post,
+    /// Explain how a file is copied, reused, signed, packaged, or removed
+    Explain {
+        /// Path relative to the build directory (e.g. "bin/64bit/obs64.exe")
+        path: String,
+    },
+    /// Compare two output directories byte-by-byte (does not require config or version)
+    VerifyRepro {
+        /// Output directory of the first build
+        a: PathBuf,
+        /// Output directory of the second build
+        b: PathBuf,
+    },
 }
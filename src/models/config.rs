@@ -1,22 +1,28 @@
 use std::cmp::Ordering;
+use std::collections::BTreeMap;
 use std::fs;
 use std::path::{Path, PathBuf};
 use std::str::FromStr;
 
-use anyhow::{bail, Result};
+use anyhow::{bail, Context, Result};
+use hashbrown::HashSet;
 use log::warn;
 use serde::{Deserialize, Deserializer};
 use toml;
 
-use crate::models::args::MainArgs;
+use crate::models::args::{Command, MainArgs};
+use crate::utils::authenticode::DigestAlgorithm;
+use crate::utils::filter::PathFilter;
 use crate::utils::misc;
 use crate::utils::sign::Signer;
+use crate::utils::template;
+use crate::utils::winget;
 
 fn get_signed_exts() -> Vec<String> {
     vec!["exe".to_string(), "dll".to_string(), "pyd".to_string()]
 }
 
-#[derive(Deserialize, Default)]
+#[derive(Deserialize, Default, Debug)]
 #[serde(default)]
 pub struct Config {
     // Sections
@@ -41,14 +47,16 @@ pub struct ObsVersion {
     pub rc: u8,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Debug)]
 #[serde(default)]
 pub struct GeneralOptions {
     pub branch: String,
     pub log_level: String,
+    /// Architecture used in output names (e.g. "x64" or "arm64")
+    pub arch: String,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Debug)]
 #[serde(default)]
 pub struct EnvOptions {
     pub input_dir: PathBuf,
@@ -61,107 +69,262 @@ pub struct EnvOptions {
     pub pdbcopy_path: PathBuf,
 }
 
-#[derive(Deserialize, Default)]
+#[derive(Deserialize, Default, Debug)]
 #[serde(default)]
 pub struct PreparationOptions {
     pub empty_output_dir: bool,
+    pub layout: LayoutOptions,
     pub copy: CopyOptions,
     pub codesign: CodesignOptions,
     pub codesign_extra: Option<CodesignOptions>,
+    pub verify_signatures: VerifySignatureOptions,
     pub strip_pdbs: StripPDBOptions,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Debug)]
+#[serde(default)]
+pub struct LayoutOptions {
+    /// Top-level directories of the build that are included
+    pub roots: Vec<String>,
+    /// Whether files directly in the build directory are included
+    pub top_level_files: bool,
+}
+
+#[derive(Deserialize, Debug)]
 #[serde(default)]
 pub struct CopyOptions {
     pub excludes: Vec<String>,
-    pub never_copy: Vec<String>,
-    pub always_copy: Vec<String>,
+    pub never_copy: PathFilter,
+    pub always_copy: PathFilter,
     pub overrides: Vec<(String, String)>,
     pub overrides_sign: Vec<(String, String)>,
 }
 
-#[derive(Deserialize, Default)]
+#[derive(Deserialize, Debug)]
 #[serde(default)]
 pub struct CodesignOptions {
     pub skip_sign: bool,
+    pub sign_backend: CodesignBackend,
     pub sign_name: Option<String>,
     pub sign_digest: String,
     pub sign_ts_serv: String,
-    #[serde(default = "get_signed_exts")]
     pub sign_exts: Vec<String>,
     pub sign_kms_key_id: Option<String>,
     pub sign_cert_file: Option<String>,
+    pub sign_key_file: Option<String>,
+    /// Program and arguments that sign a digest (native backend, alternative to the key file)
+    pub sign_key_command: Vec<String>,
     pub sign_append: bool,
     pub sign_ts_algo: Option<String>,
-    pub sign_filter: Vec<String>,
+    pub sign_filter: PathFilter,
+    // External command options
+    pub sign_command: Vec<String>,
+    pub sign_batch_size: usize,
+    pub sign_retries: u32,
+    pub sign_retry_delay: u64,
+    pub sign_ignore_exit_codes: Vec<i64>,
+}
+
+#[derive(Deserialize, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum CodesignBackend {
+    /// Windows SDK signtool.exe
+    #[default]
+    Signtool,
+    /// Built-in Authenticode implementation
+    Native,
+    /// User-defined command (e.g. jsign, osslsigncode, AzureSignTool)
+    Command,
+}
+
+#[derive(Deserialize, Default, Debug)]
+#[serde(default)]
+pub struct VerifySignatureOptions {
+    /// SHA-1 or SHA-256 certificate thumbprints accepted as signer, verification is disabled if empty
+    pub thumbprints: Vec<String>,
+    #[serde(default = "get_signed_exts")]
+    pub exts: Vec<String>,
+    pub exclude: PathFilter,
 }
 
-#[derive(Deserialize, Default)]
+#[derive(Deserialize, Default, Debug)]
 #[serde(default)]
 pub struct StripPDBOptions {
-    pub exclude: Vec<String>,
+    pub exclude: PathFilter,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Debug)]
 #[serde(default)]
 pub struct GenerationOptions {
     #[serde(deserialize_with = "deserialize_patch_type")]
     pub patch_type: PatchType,
     pub compress_files: bool,
     pub removed_files: Vec<String>,
-    pub exclude_from_parallel: Vec<String>,
-    pub exclude_from_removal: Vec<String>,
+    pub exclude_from_parallel: PathFilter,
+    pub exclude_from_removal: PathFilter,
     pub packages: Vec<ManifestPackageOptions>,
 }
 
-#[derive(Deserialize, Default)]
+#[derive(Deserialize, Default, Debug)]
 #[serde(default)]
 pub struct ManifestPackageOptions {
     pub name: String,
-    pub include_files: Option<Vec<String>>,
+    pub include_files: Option<PathFilter>,
 }
 
-#[derive(Deserialize, Default)]
+#[derive(Deserialize, Default, Debug)]
 #[serde(default)]
 pub struct PackageOptions {
     pub installer: InstallerOptions,
     pub zip: ZipOptions,
+    pub archives: Vec<ArchiveOptions>,
     pub updater: UpdaterOptions,
+    pub checksums: ChecksumOptions,
+    pub winget: WingetOptions,
 }
 
-#[derive(Deserialize, Default)]
+#[derive(Deserialize, Debug)]
 #[serde(default)]
 pub struct InstallerOptions {
     pub nsis_script: PathBuf,
     pub name: String,
     pub skip_sign: bool,
     pub skip: bool,
+    /// Additional defines passed to makensis (values support templates)
+    pub defines: BTreeMap<String, String>,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Debug)]
 #[serde(default)]
 pub struct ZipOptions {
     pub skip: bool,
     pub name: String,
     pub pdb_name: String,
+    pub backend: ZipBackend,
+    pub compression: ZipCompression,
+    /// Compression level, uses the compression method's default if not set
+    pub level: Option<u32>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct ArchiveOptions {
+    pub skip: bool,
+    pub format: ArchiveFormat,
+    /// Files to include: "install", "pdbs", or the name of a package
+    pub source: String,
+    pub name: String,
+    /// Compression level, uses the format's default if not set
+    pub level: Option<u32>,
+    /// ZIP implementation (ZIP only)
+    pub backend: ZipBackend,
+    /// ZIP compression method (native ZIP backend only)
+    pub compression: ZipCompression,
+    /// Additional files (name in the archive -> file), replacing files of the same name
+    pub extra_files: BTreeMap<String, PathBuf>,
+    /// Packages whose files are left out ("install" source only)
+    pub exclude_packages: Vec<String>,
+}
+
+#[derive(Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ArchiveFormat {
+    #[default]
+    #[serde(rename = "zip")]
+    Zip,
+    #[serde(rename = "tar.zst")]
+    TarZst,
+    #[serde(rename = "tar.xz")]
+    TarXz,
+}
+
+#[derive(Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ZipBackend {
+    /// Built-in ZIP implementation
+    #[default]
+    Native,
+    /// 7-Zip CLI
+    #[serde(rename = "7zip")]
+    SevenZip,
+}
+
+#[derive(Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ZipCompression {
+    #[default]
+    Deflate,
+    /// Zstandard (not supported by all ZIP tools, e.g. Windows Explorer)
+    Zstd,
 }
 
-#[derive(Deserialize, Default)]
+#[derive(Deserialize, Default, Debug)]
 #[serde(default)]
 pub struct UpdaterOptions {
     pub skip_sign: bool,
     pub pretty_json: bool,
+    pub file_index: bool,
     pub notes_file: PathBuf,
+    pub notes_format: NotesFormat,
+    pub notes_converter: NotesConverter,
     pub updater_path: PathBuf,
     pub private_key: Option<PathBuf>,
+    pub private_keys: Vec<PathBuf>,
     pub vc_redist_path: PathBuf,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Default, Debug)]
+#[serde(default)]
+pub struct ChecksumOptions {
+    pub skip: bool,
+    /// Sign "SHA256SUMS" with the updater key(s)
+    pub sign: bool,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(default)]
+pub struct WingetOptions {
+    /// Create winget manifests for the installer in "winget/"
+    pub enabled: bool,
+    pub schema_version: String,
+    pub package_identifier: String,
+    pub package_name: String,
+    pub publisher: String,
+    pub publisher_url: String,
+    pub license: String,
+    pub short_description: String,
+    pub locale: String,
+    /// Download URL of the installer (template, "{installer}" is the installer's file name)
+    pub installer_url: String,
+    /// Release notes URL (template)
+    pub release_notes_url: String,
+}
+
+#[derive(Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum NotesFormat {
+    /// Markdown for ".md" and ".markdown" files, RST otherwise
+    #[default]
+    Auto,
+    Markdown,
+    Rst,
+}
+
+#[derive(Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum NotesConverter {
+    /// Built-in converter (CommonMark and an RST subset)
+    #[default]
+    Native,
+    /// Pandoc CLI
+    Pandoc,
+}
+
+#[derive(Deserialize, Debug)]
 #[serde(default)]
 pub struct PostOptions {
     pub copy_to_old: bool,
+    /// Write release report (Markdown, HTML, and JSON) to the output directory
+    pub report: bool,
 }
 
 impl From<&ObsVersion> for u32 {
@@ -191,6 +354,19 @@ impl Default for GeneralOptions {
         Self {
             branch: "stable".to_string(),
             log_level: "info".to_string(),
+            arch: "x64".to_string(),
+        }
+    }
+}
+
+impl Default for InstallerOptions {
+    fn default() -> Self {
+        Self {
+            nsis_script: PathBuf::new(),
+            name: "OBS-Studio-{short_version}-Full-Installer-{arch}.exe".to_string(),
+            skip_sign: false,
+            skip: false,
+            defines: BTreeMap::new(),
         }
     }
 }
@@ -209,11 +385,72 @@ impl Default for EnvOptions {
     }
 }
 
+impl Default for CodesignOptions {
+    fn default() -> Self {
+        Self {
+            skip_sign: false,
+            sign_backend: CodesignBackend::default(),
+            sign_name: None,
+            sign_digest: String::new(),
+            sign_ts_serv: String::new(),
+            sign_exts: get_signed_exts(),
+            sign_kms_key_id: None,
+            sign_cert_file: None,
+            sign_key_file: None,
+            sign_key_command: Vec::new(),
+            sign_append: false,
+            sign_ts_algo: None,
+            sign_filter: PathFilter::default(),
+            sign_command: Vec::new(),
+            sign_batch_size: 5,
+            sign_retries: 0,
+            sign_retry_delay: 5,
+            // signtool sometimes returns STATUS_HEAP_CORRUPTION after successfully signing
+            sign_ignore_exit_codes: vec![0xc0000374],
+        }
+    }
+}
+
+impl Default for LayoutOptions {
+    fn default() -> Self {
+        Self {
+            roots: vec!["bin".to_string(), "data".to_string(), "obs-plugins".to_string()],
+            top_level_files: false,
+        }
+    }
+}
+
+impl LayoutOptions {
+    /// Check if a relative (Unix-style) path is part of the build layout
+    pub fn includes(&self, path: &str) -> bool {
+        if !path.contains('/') {
+            return self.top_level_files;
+        }
+        self.roots.iter().any(|root| Path::new(path).starts_with(root))
+    }
+}
+
+impl UpdaterOptions {
+    /// Format of the release notes, based on the file extension unless set explicitly
+    pub fn notes_format(&self) -> NotesFormat {
+        if self.notes_format != NotesFormat::Auto {
+            return self.notes_format;
+        }
+
+        match self.notes_file.extension().and_then(|e| e.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("md") || ext.eq_ignore_ascii_case("markdown") => {
+                NotesFormat::Markdown
+            }
+            _ => NotesFormat::Rst,
+        }
+    }
+}
+
 impl Default for CopyOptions {
     fn default() -> Self {
         Self {
             excludes: Vec::new(),
-            never_copy: Vec::new(),
+            never_copy: PathFilter::default(),
             overrides: Vec::new(),
             overrides_sign: Vec::new(),
             always_copy: vec![
@@ -223,7 +460,8 @@ impl Default for CopyOptions {
                 "obs-frontend-api".to_string(),
                 "obs.dll".to_string(),
                 "obs.pdb".to_string(),
-            ],
+            ]
+            .into(),
         }
     }
 }
@@ -234,8 +472,8 @@ impl Default for GenerationOptions {
             patch_type: PatchType::Zstd,
             compress_files: true,
             removed_files: Vec::new(),
-            exclude_from_removal: Vec::new(),
-            exclude_from_parallel: Vec::new(),
+            exclude_from_removal: PathFilter::default(),
+            exclude_from_parallel: PathFilter::default(),
             packages: Vec::new(),
         }
     }
@@ -247,13 +485,53 @@ impl Default for ZipOptions {
             skip: false,
             name: "OBS-Studio-{version}.zip".to_string(),
             pdb_name: "OBS-Studio-{version}-pdbs.zip".to_string(),
+            backend: ZipBackend::default(),
+            compression: ZipCompression::default(),
+            level: None,
+        }
+    }
+}
+
+impl Default for ArchiveOptions {
+    fn default() -> Self {
+        Self {
+            skip: false,
+            format: ArchiveFormat::default(),
+            source: "install".to_string(),
+            name: String::new(),
+            level: None,
+            backend: ZipBackend::default(),
+            compression: ZipCompression::default(),
+            extra_files: BTreeMap::new(),
+            exclude_packages: Vec::new(),
+        }
+    }
+}
+
+impl Default for WingetOptions {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            schema_version: "1.10.0".to_string(),
+            package_identifier: "OBSProject.OBSStudio".to_string(),
+            package_name: "OBS Studio".to_string(),
+            publisher: "OBS Project".to_string(),
+            publisher_url: "https://obsproject.com".to_string(),
+            license: "GPL-2.0".to_string(),
+            short_description: "Free and open source software for video recording and live streaming".to_string(),
+            locale: "en-US".to_string(),
+            installer_url: "https://cdn-fastly.obsproject.com/downloads/{installer}".to_string(),
+            release_notes_url: "https://github.com/obsproject/obs-studio/releases/tag/{tag_version}".to_string(),
         }
     }
 }
 
 impl Default for PostOptions {
     fn default() -> Self {
-        Self { copy_to_old: true }
+        Self {
+            copy_to_old: true,
+            report: true,
+        }
     }
 }
 
@@ -271,11 +549,10 @@ impl Config {
     }
 
     pub fn apply_args(&mut self, args: &MainArgs) -> Result<()> {
-        self.set_version(
-            &args.version,
-            args.beta.unwrap_or_default(),
-            args.rc.unwrap_or_default(),
-        )?;
+        let Some(version) = &args.version else {
+            bail!("No version specified (--version)")
+        };
+        self.set_version(version, args.beta.unwrap_or_default(), args.rc.unwrap_or_default())?;
 
         if let Some(input) = &args.input {
             self.env.input_dir = input.clone();
@@ -299,33 +576,51 @@ impl Config {
         self.package.installer.skip = args.skip_installer || self.package.installer.skip;
         self.package.updater.skip_sign = args.skip_manifest_signing || self.package.updater.skip_sign;
 
-        if !args.packaging_only {
-            if let Some(privkey) = &args.private_key {
-                self.package.updater.private_key = Some(privkey.to_owned());
+        // Stages that do not touch the updater files do not require updater settings
+        let packaging_only =
+            args.packaging_only || matches!(args.command, Some(Command::Prepare | Command::Package | Command::Post));
+
+        if !packaging_only {
+            if !args.private_key.is_empty() {
+                self.package.updater.private_key = None;
+                self.package.updater.private_keys = args.private_key.to_owned();
             }
             if let Some(notes_file) = &args.notes_file {
-                self.package.updater.notes_file = fs::canonicalize(notes_file)?;
+                self.package.updater.notes_file =
+                    fs::canonicalize(notes_file).context(format!("Notes file error ({notes_file:?})"))?;
             }
         }
 
-        self.validate(false, args.packaging_only)
+        // Explaining only requires the directories and filters to be valid
+        let explain = matches!(args.command, Some(Command::Explain { .. }));
+        self.validate(explain, packaging_only)?;
+
+        // Dry runs must not modify the previous builds directory
+        if !args.dry_run {
+            self.create_previous_dirs()?;
+        }
+
+        Ok(())
+    }
+
+    /// Ensure subdirectories of previous folder exist
+    pub fn create_previous_dirs(&self) -> Result<()> {
+        fs::create_dir_all(self.env.previous_dir.join("builds"))?;
+        fs::create_dir_all(self.env.previous_dir.join("pdbs"))?;
+
+        Ok(())
     }
 
     pub fn validate(&mut self, deltas_only: bool, packaging_only: bool) -> Result<()> {
         // Output folder cannot be checked as it may not exist yet
         match fs::canonicalize(&self.env.input_dir) {
             Ok(res) => self.env.input_dir = res,
-            Err(e) => bail!("Input dir error: {}", e),
+            Err(e) => bail!("Input dir error ({:?}): {}", self.env.input_dir, e),
         }
 
-        // Ensure previous folder and subdirectories exist
         match fs::canonicalize(&self.env.previous_dir) {
-            Ok(res) => {
-                fs::create_dir_all(res.join("builds"))?;
-                fs::create_dir_all(res.join("pdbs"))?;
-                self.env.previous_dir = res;
-            }
-            Err(e) => bail!("Previous dir error: {}", e),
+            Ok(res) => self.env.previous_dir = res,
+            Err(e) => bail!("Previous dir error ({:?}): {}", self.env.previous_dir, e),
         }
 
         // This function will just return the original path if it doesn't succeed.
@@ -339,6 +634,150 @@ impl Config {
             });
         }
 
+        // Without any archives configured, use the (legacy) ZIP options for the build and PDB ZIPs
+        if self.package.archives.is_empty() {
+            let zip = &self.package.zip;
+            for (source, name) in [("install", &zip.name), ("pdbs", &zip.pdb_name)] {
+                self.package.archives.push(ArchiveOptions {
+                    skip: zip.skip,
+                    source: source.to_string(),
+                    name: name.to_owned(),
+                    level: zip.level,
+                    backend: zip.backend,
+                    compression: zip.compression,
+                    ..Default::default()
+                });
+            }
+        }
+
+        // Check output name templates
+        let mut templates = vec![("package.installer.name", &self.package.installer.name)];
+        templates.extend(
+            self.package
+                .installer
+                .defines
+                .values()
+                .map(|v| ("package.installer.defines", v)),
+        );
+        templates.extend(self.package.archives.iter().map(|a| ("package.archives.name", &a.name)));
+        for (name, template) in templates {
+            if let Err(e) = template::check(template) {
+                bail!("Invalid template in \"{name}\": {e}")
+            }
+        }
+
+        if self.package.winget.enabled {
+            let winget = &self.package.winget;
+            if let Err(e) = template::check_with(&winget.installer_url, &["installer"]) {
+                bail!("Invalid template in \"package.winget.installer_url\": {e}")
+            }
+            if let Err(e) = template::check(&winget.release_notes_url) {
+                bail!("Invalid template in \"package.winget.release_notes_url\": {e}")
+            }
+            winget::check(self).context("Invalid winget settings")?;
+        }
+
+        let mut archive_names = HashSet::new();
+        for archive in self.package.archives.iter().filter(|a| !a.skip) {
+            let source = &archive.source;
+            if source != "install" && source != "pdbs" && !self.generate.packages.iter().any(|p| &p.name == source) {
+                bail!("Archive source \"{source}\" is neither \"install\", \"pdbs\", nor a package name");
+            }
+            if archive.name.is_empty() {
+                bail!("Archive with source \"{source}\" has no name");
+            }
+            if !archive_names.insert(&archive.name) {
+                bail!("Archive name \"{}\" is used more than once", archive.name);
+            }
+            if archive.backend == ZipBackend::SevenZip
+                && (archive.format != ArchiveFormat::Zip || source != "install" && source != "pdbs")
+            {
+                bail!(
+                    "Archive \"{}\": the 7zip backend only supports ZIP files of \"install\" or \"pdbs\"",
+                    archive.name
+                );
+            }
+            if archive.backend == ZipBackend::SevenZip
+                && (!archive.extra_files.is_empty() || !archive.exclude_packages.is_empty())
+            {
+                bail!(
+                    "Archive \"{}\": the 7zip backend does not support extra files or excluded packages",
+                    archive.name
+                );
+            }
+            if !archive.exclude_packages.is_empty() && source != "install" {
+                bail!(
+                    "Archive \"{}\": packages can only be excluded from \"install\"",
+                    archive.name
+                );
+            }
+            for package in &archive.exclude_packages {
+                if !self.generate.packages.iter().any(|p| &p.name == package) {
+                    bail!(
+                        "Archive \"{}\": excluded package \"{package}\" does not exist",
+                        archive.name
+                    );
+                }
+            }
+            for (name, file) in &archive.extra_files {
+                // Names are checked the same way on all platforms, absolute paths include drive prefixes ("C:")
+                let path = name.replace('\\', "/");
+                let drive = path.as_bytes().first().is_some_and(u8::is_ascii_alphabetic) && path.get(1..2) == Some(":");
+                if path.is_empty() || path.starts_with('/') || drive || path.split('/').any(|c| c == "..") {
+                    bail!("Archive \"{}\": invalid extra file name \"{name}\"", archive.name);
+                }
+                if !file.is_file() {
+                    bail!("Archive \"{}\": extra file {file:?} does not exist", archive.name);
+                }
+            }
+        }
+
+        if self.prepare.layout.roots.is_empty() && !self.prepare.layout.top_level_files {
+            bail!("Build layout does not include any files!")
+        }
+
+        // Deprecated options are merged first so that explaining and delta generation see them as well
+        if !self.prepare.copy.excludes.is_empty() {
+            warn!("\"excludes\" is deprecated in favour of \"never_copy\"");
+            let excludes = std::mem::take(&mut self.prepare.copy.excludes);
+            self.prepare.copy.never_copy.extend(excludes);
+        }
+
+        if !self.prepare.copy.overrides_sign.is_empty() {
+            warn!("\"overrides_sign\" is deprecated in favour of \"overrides\"");
+            self.prepare
+                .copy
+                .overrides
+                .append(&mut self.prepare.copy.overrides_sign);
+        }
+
+        // Check that all path filters are valid
+        let mut filters = vec![
+            ("prepare.copy.never_copy", &self.prepare.copy.never_copy),
+            ("prepare.copy.always_copy", &self.prepare.copy.always_copy),
+            ("prepare.codesign.sign_filter", &self.prepare.codesign.sign_filter),
+            (
+                "prepare.verify_signatures.exclude",
+                &self.prepare.verify_signatures.exclude,
+            ),
+            ("prepare.strip_pdbs.exclude", &self.prepare.strip_pdbs.exclude),
+            ("generate.exclude_from_parallel", &self.generate.exclude_from_parallel),
+            ("generate.exclude_from_removal", &self.generate.exclude_from_removal),
+        ];
+        if let Some(codesign_extra) = &self.prepare.codesign_extra {
+            filters.push(("prepare.codesign_extra.sign_filter", &codesign_extra.sign_filter));
+        }
+        for package in &self.generate.packages {
+            if let Some(include_files) = &package.include_files {
+                filters.push(("generate.packages.include_files", include_files));
+            }
+        }
+        for (name, filter) in filters {
+            if let Err(e) = filter.validate() {
+                bail!("Invalid filter in \"{}\": {}", name, e)
+            }
+        }
+
         // This is all we care about if we're only generating deltas
         if deltas_only {
             return Ok(());
@@ -347,56 +786,64 @@ impl Config {
         // Check file paths (for binaries, also check if they are in %PATH%)
         misc::check_binary_path(&mut self.env.pdbcopy_path)?;
         misc::check_binary_path(&mut self.env.makensis_path)?;
-        if !self.package.zip.skip {
+        if self
+            .package
+            .archives
+            .iter()
+            .any(|a| !a.skip && a.backend == ZipBackend::SevenZip)
+        {
             misc::check_binary_path(&mut self.env.sevenzip_path)?;
         }
-        if !packaging_only {
+        if !packaging_only && self.package.updater.notes_converter == NotesConverter::Pandoc {
             misc::check_binary_path(&mut self.env.pandoc_path)?;
         }
 
-        // Check if private key is set correctly (if signing is enabled)
+        // Single key is used as the first of the list of keys
+        if let Some(privkey) = self.package.updater.private_key.take() {
+            self.package.updater.private_keys.insert(0, privkey);
+        }
+
+        // Check if private keys are set correctly (if signing is enabled)
         if !self.package.updater.skip_sign {
-            if let Err(e) = Signer::check_key(self.package.updater.private_key.as_ref()) {
+            if let Err(e) = Signer::check_key(&self.package.updater.private_keys) {
                 bail!("Failed loading private key: {}", e)
             }
         }
 
-        // Check if codesigning parameters are set (if enabled)
-        #[cfg(windows)]
+        // Signtool is only available on Windows, codesigning is skipped elsewhere (as it always has been)
         if !self.prepare.codesign.skip_sign
-            && ((self
-                .prepare
-                .codesign
-                .sign_name
-                .as_deref()
-                .unwrap_or_default()
-                .is_empty()
-                && self
-                    .prepare
-                    .codesign
-                    .sign_cert_file
-                    .as_deref()
-                    .unwrap_or_default()
-                    .is_empty())
-                || self.prepare.codesign.sign_digest.is_empty()
-                || self.prepare.codesign.sign_ts_serv.is_empty()
-                || self.prepare.codesign.sign_exts.is_empty()
-                || (self.prepare.codesign.sign_append && self.prepare.codesign.sign_ts_algo.is_none()))
+            && self.prepare.codesign.sign_backend == CodesignBackend::Signtool
+            && cfg!(not(windows))
+        {
+            warn!("The signtool codesigning backend is only supported on Windows, codesigning will be skipped.");
+            self.prepare.codesign.skip_sign = true;
+            self.package.installer.skip_sign = true;
+        }
+        if self
+            .prepare
+            .codesign_extra
+            .as_ref()
+            .is_some_and(|c| !c.skip_sign && c.sign_backend == CodesignBackend::Signtool && cfg!(not(windows)))
         {
-            bail!("Codesigning settings are incomplete!")
+            warn!("The signtool codesigning backend is only supported on Windows, additional codesigning will be skipped.");
+            self.prepare.codesign_extra = None;
         }
 
-        if !self.prepare.copy.excludes.is_empty() {
-            warn!("\"excludes\" is deprecated in favour of \"never_copy\"");
-            self.prepare.copy.never_copy.append(&mut self.prepare.copy.excludes);
+        // Check if codesigning parameters are set (if enabled)
+        if !self.prepare.codesign.skip_sign {
+            self.prepare.codesign.validate()?;
+            if let Some(codesign_extra) = &self.prepare.codesign_extra {
+                codesign_extra.validate()?;
+            }
         }
 
-        if !self.prepare.copy.overrides_sign.is_empty() {
-            warn!("\"overrides_sign\" is deprecated in favour of \"overrides\"");
-            self.prepare
-                .copy
-                .overrides
-                .append(&mut self.prepare.copy.overrides_sign);
+        // Normalise thumbprints (as copied from Windows' certificate dialog or signtool output)
+        for thumbprint in self.prepare.verify_signatures.thumbprints.iter_mut() {
+            thumbprint.retain(|c| !c.is_whitespace() && c != ':');
+            if !(thumbprint.len() == 40 || thumbprint.len() == 64) || !thumbprint.chars().all(|c| c.is_ascii_hexdigit())
+            {
+                bail!("Invalid certificate thumbprint \"{}\"", thumbprint)
+            }
         }
 
         // Check that NSIS script exists if installer not skipped
@@ -428,12 +875,70 @@ impl Config {
 
     pub fn from_file(path: &Path) -> Result<Config> {
         let config_str = fs::read_to_string(path)?;
-        let config = toml::from_str::<Config>(config_str.as_str())?;
+        let mut config = toml::from_str::<Config>(config_str.as_str())?;
+
+        // Extra archive files are relative to the config file
+        let config_dir = path.parent().unwrap_or(Path::new(""));
+        for archive in config.package.archives.iter_mut() {
+            for file in archive.extra_files.values_mut() {
+                if file.is_relative() {
+                    *file = config_dir.join(&file);
+                }
+            }
+        }
 
         Ok(config)
     }
 }
 
+impl CodesignOptions {
+    fn validate(&self) -> Result<()> {
+        let is_empty = |opt: &Option<String>| opt.as_deref().unwrap_or_default().is_empty();
+
+        match self.sign_backend {
+            CodesignBackend::Signtool => {
+                if (is_empty(&self.sign_name) && is_empty(&self.sign_cert_file))
+                    || self.sign_digest.is_empty()
+                    || self.sign_ts_serv.is_empty()
+                    || self.sign_exts.is_empty()
+                    || (self.sign_append && self.sign_ts_algo.is_none())
+                {
+                    bail!("Codesigning settings are incomplete!")
+                }
+            }
+            CodesignBackend::Native => {
+                if is_empty(&self.sign_cert_file)
+                    || is_empty(&self.sign_key_file) == self.sign_key_command.is_empty()
+                    || self.sign_exts.is_empty()
+                {
+                    bail!("Codesigning settings are incomplete! (exactly one of \"sign_key_file\" and \"sign_key_command\" is required)")
+                }
+                if self.sign_kms_key_id.is_some() {
+                    bail!("KMS keys are not supported by the native codesigning backend, use \"sign_key_command\" instead!")
+                }
+                DigestAlgorithm::from_str(&self.sign_digest)?;
+                if let Some(ts_algo) = &self.sign_ts_algo {
+                    DigestAlgorithm::from_str(ts_algo)?;
+                }
+            }
+            CodesignBackend::Command => {
+                if self.sign_command.is_empty() || self.sign_exts.is_empty() {
+                    bail!("Codesigning settings are incomplete!")
+                }
+                if !self.sign_command.iter().any(|arg| arg == "{files}") {
+                    bail!("Codesigning command must contain \"{{files}}\" as a separate argument!")
+                }
+            }
+        }
+
+        if self.sign_batch_size == 0 {
+            bail!("Codesigning batch size must be greater than zero!")
+        }
+
+        Ok(())
+    }
+}
+
 #[derive(Debug, PartialEq, Eq, Default, Deserialize)]
 pub enum PatchType {
     BsdiffLzma,
@@ -0,0 +1,38 @@
+use std::fs::File;
+use std::io::Write;
+use std::path::PathBuf;
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+/// Index of all files in the updater directory, used to verify a complete upload
+#[derive(Serialize, Deserialize, Default)]
+pub struct FileIndex {
+    pub version: String,
+    pub branch: String,
+    pub files: Vec<IndexEntry>,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+pub struct IndexEntry {
+    /// Path relative to the updater directory
+    pub name: String,
+    pub size: u64,
+    pub blake2: String,
+    pub sha256: String,
+}
+
+impl FileIndex {
+    pub fn to_file(&self, filename: &PathBuf, pretty: bool) -> Result<()> {
+        let data = if pretty {
+            serde_json::to_string_pretty(&self)?
+        } else {
+            serde_json::to_string(&self)?
+        };
+
+        let mut f = File::create(filename)?;
+        f.write_all(data.as_bytes())?;
+
+        Ok(())
+    }
+}
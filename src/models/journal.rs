@@ -0,0 +1,333 @@
+use std::fs;
+use std::fs::File;
+use std::io::{BufReader, Write};
+use std::path::PathBuf;
+use std::time::Instant;
+
+use anyhow::{Context, Result};
+use log::{info, warn};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use crate::models::args::MainArgs;
+use crate::models::config::Config;
+use crate::models::report::StageTime;
+use crate::utils::hash::{get_path_fingerprint, hash_string};
+use crate::utils::misc::get_filename_version;
+use crate::utils::progress;
+
+const JOURNAL_FILE: &str = "journal.json";
+
+/// Stages of a run in the order they are executed
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum Stage {
+    /// Copying the new build and code section analysis
+    Copy,
+    Codesign,
+    /// PDB stripping, copying reused files, and signature verification
+    Strip,
+    /// Change analysis, copying/compressing files to the updater directory
+    Generate,
+    Patches,
+    Installer,
+    Zips,
+    /// Creating winget manifests for the installer
+    Winget,
+    /// Finalising, indexing, and signing the manifest
+    Manifest,
+    /// Writing "SHA256SUMS" and "release.json"
+    Checksums,
+    Post,
+}
+
+const STAGES: [Stage; 11] = [
+    Stage::Copy,
+    Stage::Codesign,
+    Stage::Strip,
+    Stage::Generate,
+    Stage::Patches,
+    Stage::Installer,
+    Stage::Zips,
+    Stage::Winget,
+    Stage::Manifest,
+    Stage::Checksums,
+    Stage::Post,
+];
+
+#[derive(Serialize, Deserialize)]
+struct StageRecord {
+    stage: Stage,
+    /// Fingerprint of the inputs and settings of the stage
+    fingerprint: String,
+    /// Stage results needed by later stages
+    data: serde_json::Value,
+    /// Time the stage took to run, not set for skipped stages
+    #[serde(default)]
+    duration_ms: Option<u64>,
+}
+
+/// Record of completed stages, used to resume a failed run
+#[derive(Serialize, Deserialize, Default)]
+pub struct Journal {
+    version: String,
+    completed: Vec<StageRecord>,
+    #[serde(skip)]
+    path: PathBuf,
+    #[serde(skip)]
+    fingerprints: Vec<(Stage, String)>,
+    /// Number of completed stages loaded from the previous run
+    #[serde(skip)]
+    resumed: usize,
+}
+
+impl Journal {
+    /// Create new journal, or load the existing one in the output directory if resuming.
+    /// Only the completed stages up to the first one with changed inputs are kept.
+    pub fn init(conf: &Config, args: &MainArgs) -> Result<Self> {
+        // Settings and inputs all stages depend on
+        let common = format!(
+            "{:?}|{}|{}|{}|{}",
+            conf.obs_version,
+            conf.general.branch,
+            get_path_fingerprint(&conf.env.input_dir, &[]),
+            args.updater_data_only,
+            args.packaging_only
+        );
+
+        let mut journal = Self {
+            version: conf.obs_version.version_str.to_owned(),
+            path: conf.env.output_dir.join(JOURNAL_FILE),
+            fingerprints: STAGES
+                .iter()
+                .map(|s| (*s, stage_fingerprint(conf, args, *s, &common)))
+                .collect(),
+            ..Default::default()
+        };
+
+        if !args.resume {
+            return Ok(journal);
+        }
+
+        let previous: Journal = match File::open(&journal.path) {
+            Ok(f) => serde_json::from_reader(BufReader::new(f)).context("Failed reading journal")?,
+            Err(_) => {
+                warn!("No journal found, starting from the beginning.");
+                return Ok(journal);
+            }
+        };
+
+        for (record, (stage, fingerprint)) in previous.completed.into_iter().zip(journal.fingerprints.iter()) {
+            if record.stage != *stage || record.fingerprint != *fingerprint {
+                info!("Inputs of stage \"{stage:?}\" have changed since the previous run.");
+                break;
+            }
+            journal.completed.push(record);
+        }
+        journal.resumed = journal.completed.len();
+
+        match journal.fingerprints.get(journal.completed.len()) {
+            Some((stage, _)) => info!("Resuming run from stage \"{stage:?}\""),
+            None => info!("All stages have been completed in the previous run."),
+        }
+
+        Ok(journal)
+    }
+
+    /// Journal that is not saved, used when running individual stages
+    pub fn in_memory() -> Self {
+        Self::default()
+    }
+
+    pub fn is_complete(&self, stage: Stage) -> bool {
+        self.completed.iter().any(|r| r.stage == stage)
+    }
+
+    /// Run stage unless it was already completed, returning the (stored) result
+    pub fn run_stage<T, F>(&mut self, stage: Stage, f: F) -> Result<T>
+    where
+        T: Serialize + DeserializeOwned,
+        F: FnOnce() -> Result<T>,
+    {
+        if let Some(record) = self.completed.iter().find(|r| r.stage == stage) {
+            info!("Skipping stage \"{stage:?}\" (completed in previous run)");
+            return Ok(serde_json::from_value(record.data.clone())?);
+        }
+
+        progress::stage_started(stage);
+        let start = Instant::now();
+        let result = f()?;
+        let duration_ms = start.elapsed().as_millis() as u64;
+        self.complete(stage, serde_json::to_value(&result)?, Some(duration_ms))?;
+        progress::stage_finished(stage);
+
+        Ok(result)
+    }
+
+    /// Mark stages that are not part of this run as completed
+    pub fn skip_stages(&mut self, stages: &[Stage]) -> Result<()> {
+        for stage in stages {
+            if !self.is_complete(*stage) {
+                self.complete(*stage, serde_json::Value::Null, None)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Get the stored result of a completed stage
+    pub fn stage_data<T: DeserializeOwned>(&self, stage: Stage) -> Option<T> {
+        self.completed
+            .iter()
+            .find(|r| r.stage == stage)
+            .and_then(|r| serde_json::from_value(r.data.clone()).ok())
+    }
+
+    /// Durations of all completed stages, including ones completed in a previous run
+    pub fn stage_times(&self) -> Vec<StageTime> {
+        self.completed
+            .iter()
+            .enumerate()
+            .map(|(idx, r)| StageTime {
+                stage: r.stage,
+                duration_ms: r.duration_ms,
+                resumed: idx < self.resumed,
+            })
+            .collect()
+    }
+
+    fn complete(&mut self, stage: Stage, data: serde_json::Value, duration_ms: Option<u64>) -> Result<()> {
+        let fingerprint = self
+            .fingerprints
+            .iter()
+            .find(|(s, _)| *s == stage)
+            .map(|(_, f)| f.to_owned())
+            .unwrap_or_default();
+
+        self.completed.push(StageRecord {
+            stage,
+            fingerprint,
+            data,
+            duration_ms,
+        });
+        if self.path.as_os_str().is_empty() {
+            return Ok(());
+        }
+        self.to_file()
+    }
+
+    fn to_file(&self) -> Result<()> {
+        let data = serde_json::to_string(&self)?;
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut f = File::create(&self.path).context("Failed writing journal")?;
+        f.write_all(data.as_bytes())?;
+
+        Ok(())
+    }
+}
+
+/// Create fingerprint of the inputs and settings a stage depends on
+fn stage_fingerprint(conf: &Config, args: &MainArgs, stage: Stage, common: &str) -> String {
+    // Files of the version being built and the hash cache are written by bouf itself
+    let version = get_filename_version(&conf.obs_version, false);
+    let previous_builds = || get_path_fingerprint(&conf.env.previous_dir.join("builds"), &[&version, "cache.json"]);
+
+    let inputs = match stage {
+        Stage::Copy => {
+            let overrides: Vec<String> = conf
+                .prepare
+                .copy
+                .overrides
+                .iter()
+                .map(|(_, f)| get_path_fingerprint(f.as_ref(), &[]))
+                .collect();
+            format!(
+                "{:?}|{:?}|{:?}|{}",
+                conf.prepare.layout,
+                conf.prepare.copy,
+                overrides,
+                previous_builds()
+            )
+        }
+        Stage::Codesign => format!("{:?}|{:?}", conf.prepare.codesign, conf.prepare.codesign_extra),
+        Stage::Strip => format!(
+            "{:?}|{:?}|{:?}",
+            conf.prepare.strip_pdbs, conf.prepare.verify_signatures, conf.env.pdbcopy_path
+        ),
+        Stage::Generate => format!("{:?}|{}", conf.generate, previous_builds()),
+        Stage::Patches => format!("{:?}|{}", conf.generate.patch_type, args.skip_patches),
+        Stage::Installer => format!(
+            "{:?}|{}",
+            conf.package.installer,
+            get_path_fingerprint(&conf.package.installer.nsis_script, &[])
+        ),
+        Stage::Zips => {
+            let extra_files: Vec<String> = conf
+                .package
+                .archives
+                .iter()
+                .flat_map(|a| a.extra_files.values())
+                .map(|f| get_path_fingerprint(f, &[]))
+                .collect();
+            format!("{:?}|{:?}", conf.package.archives, extra_files)
+        }
+        Stage::Winget => format!("{:?}", conf.package.winget),
+        Stage::Manifest => format!(
+            "{:?}|{}|{}",
+            conf.package.updater,
+            get_path_fingerprint(&conf.package.updater.notes_file, &[]),
+            get_path_fingerprint(&conf.package.updater.vc_redist_path, &[])
+        ),
+        Stage::Checksums => format!("{:?}|{}", conf.package.checksums, conf.package.updater.skip_sign),
+        Stage::Post => format!("{:?}", conf.post),
+    };
+
+    hash_string(&format!("{common}|{inputs}"))
+}
+
+#[cfg(test)]
+mod journal_tests {
+    use super::*;
+    use crate::utils::misc::TestDir;
+    use clap::Parser;
+
+    #[test]
+    fn test_resume() {
+        let dir = TestDir::new("journal");
+        let mut conf = Config::default();
+        conf.env.output_dir = dir.to_owned();
+        let args = MainArgs::parse_from(["bouf", "-c", "config.toml", "-v", "31.0.0", "--resume"]);
+
+        // First run fails after Codesign
+        let mut journal = Journal::init(&conf, &args).unwrap();
+        let reused: Vec<String> = journal
+            .run_stage(Stage::Copy, || Ok(vec!["obs.dll".to_string()]))
+            .unwrap();
+        assert_eq!(reused, ["obs.dll"]);
+        journal.skip_stages(&[Stage::Codesign]).unwrap();
+        assert!(journal
+            .run_stage(Stage::Strip, || -> Result<()> { anyhow::bail!("failed") })
+            .is_err());
+
+        // Completed stages are skipped and return the stored results
+        let mut journal = Journal::init(&conf, &args).unwrap();
+        assert!(journal.is_complete(Stage::Copy) && journal.is_complete(Stage::Codesign));
+        assert!(!journal.is_complete(Stage::Strip));
+        let reused: Vec<String> = journal.run_stage(Stage::Copy, || panic!("Stage ran again")).unwrap();
+        assert_eq!(reused, ["obs.dll"]);
+        assert_eq!(journal.stage_data::<Vec<String>>(Stage::Copy).unwrap(), ["obs.dll"]);
+        journal.run_stage(Stage::Strip, || Ok(())).unwrap();
+
+        // Changing the settings of a stage invalidates it and all following stages
+        conf.prepare.codesign.skip_sign = !conf.prepare.codesign.skip_sign;
+        let journal = Journal::init(&conf, &args).unwrap();
+        assert!(journal.is_complete(Stage::Copy));
+        assert!(!journal.is_complete(Stage::Codesign) && !journal.is_complete(Stage::Strip));
+
+        // Without "--resume" all stages run
+        let args = MainArgs::parse_from(["bouf", "-c", "config.toml", "-v", "31.0.0"]);
+        assert!(!Journal::init(&conf, &args).unwrap().is_complete(Stage::Copy));
+    }
+}
@@ -1,3 +1,9 @@
 pub mod args;
 pub mod config;
+pub mod index;
+pub mod journal;
 pub mod manifest;
+pub mod plan;
+pub mod release;
+pub mod report;
+pub mod update_plan;
@@ -0,0 +1,153 @@
+use std::fs::File;
+use std::io::Write;
+use std::path::PathBuf;
+
+use anyhow::Result;
+use log::info;
+use serde::Serialize;
+
+/// Everything a run with the current config and arguments would do (see `--dry-run`)
+#[derive(Serialize, Default)]
+pub struct Plan {
+    pub version: String,
+    pub branch: String,
+    pub previous_version: Option<String>,
+    pub prepare: Option<PreparePlan>,
+    pub generate: Option<GeneratePlan>,
+    pub package: PackagePlan,
+    /// Directories the install and PDB directories are copied to after the run
+    pub post_copy: Vec<PathBuf>,
+}
+
+#[derive(Serialize, Default)]
+pub struct PreparePlan {
+    /// Files copied from the new build
+    pub copy: Vec<String>,
+    /// Files copied from the previous build due to identical code sections
+    pub reuse: Vec<String>,
+    /// Files replaced by override files
+    pub overrides: Vec<String>,
+    pub sign: Vec<String>,
+    /// Files additionally signed by `codesign_extra`
+    pub sign_extra: Vec<String>,
+    pub strip_pdbs: Vec<String>,
+}
+
+#[derive(Serialize, Default)]
+pub struct GeneratePlan {
+    pub added: Vec<String>,
+    pub changed: Vec<String>,
+    pub unchanged: Vec<String>,
+    pub removed: Vec<String>,
+    /// Number of files per package
+    pub packages: Vec<PackageFiles>,
+    pub patches: Vec<PatchPlan>,
+}
+
+#[derive(Serialize, Default)]
+pub struct PackageFiles {
+    pub name: String,
+    pub files: usize,
+}
+
+#[derive(Serialize, Default)]
+pub struct PatchPlan {
+    pub name: String,
+    pub package: String,
+    /// Hash of the old file the patch applies to
+    pub old_hash: String,
+    pub old_size: u64,
+    pub new_size: u64,
+    pub estimated_size: u64,
+}
+
+#[derive(Serialize, Default)]
+pub struct PackagePlan {
+    pub installer: Option<PathBuf>,
+    pub zips: Vec<PathBuf>,
+    /// Directory the winget manifests are written to
+    pub winget: Option<PathBuf>,
+    pub manifest: Option<PathBuf>,
+    pub file_index: Option<PathBuf>,
+}
+
+impl Plan {
+    pub fn to_file(&self, filename: &PathBuf) -> Result<()> {
+        let data = serde_json::to_string_pretty(&self)?;
+        let mut f = File::create(filename)?;
+        f.write_all(data.as_bytes())?;
+
+        Ok(())
+    }
+
+    /// Log a summary of the plan
+    pub fn print(&self, verbose: bool) {
+        let list = |name: &str, files: &[String]| {
+            info!("  - {name}: {}", files.len());
+            if verbose {
+                files.iter().for_each(|f| info!("      {f}"));
+            }
+        };
+
+        info!("Plan for version {} (branch \"{}\"):", self.version, self.branch);
+        info!(
+            "  Previous build: {}",
+            self.previous_version.as_deref().unwrap_or("None")
+        );
+
+        match &self.prepare {
+            Some(prepare) => {
+                info!("Preparation:");
+                list("Copy", &prepare.copy);
+                list("Reuse", &prepare.reuse);
+                list("Override", &prepare.overrides);
+                list("Sign", &prepare.sign);
+                list("Sign (extra)", &prepare.sign_extra);
+                list("Strip PDB", &prepare.strip_pdbs);
+            }
+            None => info!("Preparation: Skipped"),
+        }
+
+        match &self.generate {
+            Some(generate) => {
+                info!("Updater:");
+                list("Added", &generate.added);
+                list("Changed", &generate.changed);
+                list("Unchanged", &generate.unchanged);
+                list("Removed", &generate.removed);
+                for package in &generate.packages {
+                    info!("  - Package \"{}\": {} files", package.name, package.files);
+                }
+                let total: u64 = generate.patches.iter().map(|p| p.estimated_size).sum();
+                info!(
+                    "  - Patches: {} (estimated total size: {:.1} MiB)",
+                    generate.patches.len(),
+                    total as f64 / (1024.0 * 1024.0)
+                );
+                if verbose {
+                    for patch in &generate.patches {
+                        info!(
+                            "      {} ({}): {} -> ~{} bytes",
+                            patch.name, patch.old_hash, patch.new_size, patch.estimated_size
+                        );
+                    }
+                }
+            }
+            None => info!("Updater: Skipped"),
+        }
+
+        info!("Packaging:");
+        let outputs = [
+            &self.package.installer,
+            &self.package.manifest,
+            &self.package.file_index,
+        ];
+        for path in outputs.into_iter().flatten().chain(self.package.zips.iter()) {
+            info!("  - {}", path.display());
+        }
+
+        for path in &self.post_copy {
+            info!("Copy to: {}", path.display());
+        }
+    }
+}
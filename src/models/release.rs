@@ -0,0 +1,67 @@
+use std::fs::File;
+use std::io::Write;
+use std::path::PathBuf;
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+/// Release metadata listing every artefact in the output directory with its checksums
+#[derive(Serialize, Deserialize, Default)]
+pub struct Release {
+    pub version: String,
+    pub tag_version: String,
+    pub short_version: String,
+    pub major: u8,
+    pub minor: u8,
+    pub patch: u8,
+    pub beta: u8,
+    pub rc: u8,
+    pub commit: String,
+    pub branch: String,
+    pub artefacts: Vec<Artefact>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum ArtefactKind {
+    Installer,
+    Archive,
+    Manifest,
+    FileIndex,
+    Checksums,
+    Signature,
+    /// Compressed file or patch in the updater directory
+    UpdaterFile,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Artefact {
+    /// Path relative to the output directory
+    pub name: String,
+    pub kind: ArtefactKind,
+    pub size: u64,
+    pub sha256: String,
+    pub blake2: String,
+}
+
+impl Release {
+    /// Checksums in the format of `sha256sum` ("<hash>  <name>")
+    pub fn sha256sums(&self) -> String {
+        self.artefacts
+            .iter()
+            .map(|a| format!("{}  {}\n", a.sha256, a.name))
+            .collect()
+    }
+
+    pub fn to_file(&self, filename: &PathBuf, pretty: bool) -> Result<()> {
+        let data = if pretty {
+            serde_json::to_string_pretty(&self)?
+        } else {
+            serde_json::to_string(&self)?
+        };
+        let mut f = File::create(filename)?;
+        f.write_all(data.as_bytes())?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,360 @@
+use std::fmt::Write as FmtWrite;
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::models::journal::Stage;
+use crate::utils::misc::{escape_html, format_size};
+
+/// Summary of a run with size and savings analytics (written after `run`)
+#[derive(Serialize, Default)]
+pub struct Report {
+    pub version: String,
+    pub branch: String,
+    pub changes: Option<Changes>,
+    /// Number of files codesigned during preparation
+    pub signed: Option<usize>,
+    pub pdbs_stripped: Option<usize>,
+    pub packages: Vec<PackageReport>,
+    pub largest_files: Vec<FileReport>,
+    pub largest_patches: Vec<PatchReport>,
+    /// Patch sizes compared to the full compressed size for every file with patches
+    pub patch_savings: Vec<PatchSavings>,
+    pub stages: Vec<StageTime>,
+    /// Download size to update from each previous version
+    pub download_sizes: Vec<DownloadSize>,
+}
+
+#[derive(Serialize, Default)]
+pub struct Changes {
+    pub added: usize,
+    pub changed: usize,
+    pub unchanged: usize,
+    pub removed: usize,
+}
+
+#[derive(Serialize, Default)]
+pub struct PackageReport {
+    pub name: String,
+    pub files: usize,
+    pub removed_files: usize,
+    pub size: u64,
+    pub compressed_size: u64,
+    pub patches: usize,
+    pub patch_size: u64,
+}
+
+#[derive(Serialize, Default)]
+pub struct FileReport {
+    pub name: String,
+    pub package: String,
+    pub size: u64,
+    pub compressed_size: u64,
+}
+
+#[derive(Serialize, Default)]
+pub struct PatchReport {
+    pub name: String,
+    pub package: String,
+    /// Hash of the old file the patch applies to
+    pub old_hash: String,
+    pub size: u64,
+    /// Size of the compressed file that would be downloaded without the patch
+    pub compressed_size: u64,
+}
+
+#[derive(Serialize, Default)]
+pub struct PatchSavings {
+    pub name: String,
+    pub package: String,
+    pub compressed_size: u64,
+    pub patches: usize,
+    pub average_patch_size: u64,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+pub struct DownloadSize {
+    pub version: String,
+    /// Files updated using a delta patch
+    pub patched_files: usize,
+    pub patch_size: u64,
+    /// Files that have to be downloaded in full (compressed)
+    pub full_files: usize,
+    pub full_size: u64,
+    pub total_size: u64,
+}
+
+#[derive(Serialize)]
+pub struct StageTime {
+    pub stage: Stage,
+    pub duration_ms: Option<u64>,
+    /// Stage was completed in a previous run (see `--resume`)
+    pub resumed: bool,
+}
+
+/// Table rendered to both Markdown and HTML
+struct Table {
+    title: &'static str,
+    header: Vec<&'static str>,
+    rows: Vec<Vec<String>>,
+}
+
+/// Percentage of the full download saved by using a patch instead
+fn format_savings(patch_size: u64, full_size: u64) -> String {
+    if full_size == 0 {
+        return "-".to_string();
+    }
+    format!("{:.1}%", 100.0 - (patch_size as f64 / full_size as f64) * 100.0)
+}
+
+impl Report {
+    fn summary(&self) -> Vec<(&'static str, String)> {
+        let count = |c: Option<usize>| c.map_or("-".to_string(), |c| c.to_string());
+        let mut summary = vec![("Version", self.version.to_owned()), ("Branch", self.branch.to_owned())];
+        if let Some(changes) = &self.changes {
+            summary.push(("Added files", changes.added.to_string()));
+            summary.push(("Changed files", changes.changed.to_string()));
+            summary.push(("Unchanged files", changes.unchanged.to_string()));
+            summary.push(("Removed files", changes.removed.to_string()));
+        }
+        summary.push(("Codesigned files", count(self.signed)));
+        summary.push(("Stripped PDBs", count(self.pdbs_stripped)));
+
+        summary
+    }
+
+    fn tables(&self) -> Vec<Table> {
+        let packages = Table {
+            title: "Packages",
+            header: vec![
+                "Package",
+                "Files",
+                "Removed",
+                "Size",
+                "Compressed",
+                "Patches",
+                "Patch size",
+            ],
+            rows: self
+                .packages
+                .iter()
+                .map(|p| {
+                    vec![
+                        p.name.to_owned(),
+                        p.files.to_string(),
+                        p.removed_files.to_string(),
+                        format_size(p.size),
+                        format_size(p.compressed_size),
+                        p.patches.to_string(),
+                        format_size(p.patch_size),
+                    ]
+                })
+                .collect(),
+        };
+
+        let largest_files = Table {
+            title: "Largest files",
+            header: vec!["File", "Package", "Size", "Compressed"],
+            rows: self
+                .largest_files
+                .iter()
+                .map(|f| {
+                    vec![
+                        f.name.to_owned(),
+                        f.package.to_owned(),
+                        format_size(f.size),
+                        format_size(f.compressed_size),
+                    ]
+                })
+                .collect(),
+        };
+
+        let largest_patches = Table {
+            title: "Largest patches",
+            header: vec!["File", "Package", "Old hash", "Patch size", "Compressed", "Savings"],
+            rows: self
+                .largest_patches
+                .iter()
+                .map(|p| {
+                    vec![
+                        p.name.to_owned(),
+                        p.package.to_owned(),
+                        p.old_hash.chars().take(12).collect(),
+                        format_size(p.size),
+                        format_size(p.compressed_size),
+                        format_savings(p.size, p.compressed_size),
+                    ]
+                })
+                .collect(),
+        };
+
+        let patch_savings = Table {
+            title: "Patch savings",
+            header: vec!["File", "Package", "Compressed", "Patches", "Avg. patch size", "Savings"],
+            rows: self
+                .patch_savings
+                .iter()
+                .map(|p| {
+                    vec![
+                        p.name.to_owned(),
+                        p.package.to_owned(),
+                        format_size(p.compressed_size),
+                        p.patches.to_string(),
+                        format_size(p.average_patch_size),
+                        format_savings(p.average_patch_size, p.compressed_size),
+                    ]
+                })
+                .collect(),
+        };
+
+        let stages = Table {
+            title: "Stages",
+            header: vec!["Stage", "Duration"],
+            rows: self
+                .stages
+                .iter()
+                .map(|s| {
+                    let duration = match s.duration_ms {
+                        Some(ms) => format!("{:.1} s", ms as f64 / 1000.0),
+                        None => "skipped".to_string(),
+                    };
+                    let suffix = if s.resumed { " (previous run)" } else { "" };
+                    vec![format!("{:?}", s.stage), format!("{duration}{suffix}")]
+                })
+                .collect(),
+        };
+
+        let download_sizes = Table {
+            title: "Download size per version",
+            header: vec![
+                "Version",
+                "Patched files",
+                "Patch size",
+                "Full files",
+                "Full size",
+                "Total",
+            ],
+            rows: self
+                .download_sizes
+                .iter()
+                .map(|d| {
+                    vec![
+                        d.version.to_owned(),
+                        d.patched_files.to_string(),
+                        format_size(d.patch_size),
+                        d.full_files.to_string(),
+                        format_size(d.full_size),
+                        format_size(d.total_size),
+                    ]
+                })
+                .collect(),
+        };
+
+        vec![
+            packages,
+            download_sizes,
+            largest_files,
+            largest_patches,
+            patch_savings,
+            stages,
+        ]
+    }
+
+    /// Render report as GitHub-flavoured Markdown (e.g. for `$GITHUB_STEP_SUMMARY`)
+    pub fn to_markdown(&self) -> String {
+        let mut md = format!("# OBS Studio {} release report\n\n", self.version);
+
+        md += "| | |\n|---|---|\n";
+        for (name, value) in self.summary() {
+            writeln!(md, "| {name} | {value} |").unwrap();
+        }
+
+        for table in self.tables().iter().filter(|t| !t.rows.is_empty()) {
+            write!(md, "\n## {}\n\n", table.title).unwrap();
+            writeln!(md, "| {} |", table.header.join(" | ")).unwrap();
+            writeln!(md, "|{}", "---|".repeat(table.header.len())).unwrap();
+            for row in &table.rows {
+                let cells: Vec<String> = row.iter().map(|c| c.replace('|', "\\|")).collect();
+                writeln!(md, "| {} |", cells.join(" | ")).unwrap();
+            }
+        }
+
+        md
+    }
+
+    pub fn to_html(&self) -> String {
+        let title = format!("OBS Studio {} release report", escape_html(&self.version));
+        let mut html = format!(
+            "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{title}</title>\n\
+             <style>body {{ font-family: sans-serif; }} table {{ border-collapse: collapse; }} \
+             th, td {{ border: 1px solid #ccc; padding: 2px 8px; text-align: left; }}</style>\n\
+             </head>\n<body>\n<h1>{title}</h1>\n<table>\n"
+        );
+
+        for (name, value) in self.summary() {
+            writeln!(html, "<tr><th>{name}</th><td>{}</td></tr>", escape_html(&value)).unwrap();
+        }
+        html += "</table>\n";
+
+        for table in self.tables().iter().filter(|t| !t.rows.is_empty()) {
+            writeln!(html, "<h2>{}</h2>\n<table>", table.title).unwrap();
+            writeln!(html, "<tr><th>{}</th></tr>", table.header.join("</th><th>")).unwrap();
+            for row in &table.rows {
+                let cells: Vec<String> = row.iter().map(|c| escape_html(c)).collect();
+                writeln!(html, "<tr><td>{}</td></tr>", cells.join("</td><td>")).unwrap();
+            }
+            html += "</table>\n";
+        }
+
+        html += "</body>\n</html>\n";
+        html
+    }
+
+    /// Write report as "report.md", "report.html", and "report.json" to directory
+    pub fn to_files(&self, dir: &Path) -> Result<()> {
+        let json = serde_json::to_string_pretty(&self)?;
+        for (ext, data) in [("md", self.to_markdown()), ("html", self.to_html()), ("json", json)] {
+            let mut f = File::create(dir.join(format!("report.{ext}")))?;
+            f.write_all(data.as_bytes())?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod report_tests {
+    use super::*;
+
+    #[test]
+    fn test_markdown() {
+        let mut report = Report {
+            version: "31.0.0".to_string(),
+            branch: "stable".to_string(),
+            signed: Some(3),
+            ..Default::default()
+        };
+        // Empty tables are left out
+        assert_eq!(
+            report.to_markdown(),
+            "# OBS Studio 31.0.0 release report\n\n| | |\n|---|---|\n| Version | 31.0.0 |\n| Branch | stable |\n\
+             | Codesigned files | 3 |\n| Stripped PDBs | - |\n"
+        );
+
+        report.largest_files.push(FileReport {
+            name: "a|b.dll".to_string(),
+            package: "core".to_string(),
+            size: 2048,
+            compressed_size: 100,
+        });
+        let md = report.to_markdown();
+        assert!(md.ends_with(
+            "\n## Largest files\n\n| File | Package | Size | Compressed |\n|---|---|---|---|\n\
+             | a\\|b.dll | core | 2.0 KiB | 100 B |\n"
+        ));
+        assert!(!md.contains("## Packages"));
+    }
+}
@@ -0,0 +1,72 @@
+use std::fs::File;
+use std::io::Write;
+use std::path::PathBuf;
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::models::report::DownloadSize;
+
+/// Files a client on a specific previous version has to fetch to update
+#[derive(Serialize, Deserialize, Default)]
+pub struct UpdatePlan {
+    pub from_version: String,
+    pub to_version: String,
+    pub branch: String,
+    /// Total size of all files to fetch
+    pub download_size: u64,
+    pub files: Vec<PlanFile>,
+    pub removed_files: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum FetchType {
+    Patch,
+    Full,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct PlanFile {
+    pub name: String,
+    pub package: String,
+    #[serde(rename = "type")]
+    pub fetch: FetchType,
+    /// URL relative to the updater root
+    pub url: String,
+    /// Size of the object to fetch
+    pub size: u64,
+    /// Hash of the file after updating
+    pub hash: String,
+    /// Hash of the installed file the patch applies to
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub old_hash: Option<String>,
+}
+
+impl UpdatePlan {
+    pub fn download_size(&self) -> DownloadSize {
+        let (patches, full): (Vec<&PlanFile>, Vec<&PlanFile>) =
+            self.files.iter().partition(|f| f.fetch == FetchType::Patch);
+
+        DownloadSize {
+            version: self.from_version.to_owned(),
+            patched_files: patches.len(),
+            patch_size: patches.iter().map(|f| f.size).sum(),
+            full_files: full.len(),
+            full_size: full.iter().map(|f| f.size).sum(),
+            total_size: self.download_size,
+        }
+    }
+
+    pub fn to_file(&self, filename: &PathBuf, pretty: bool) -> Result<()> {
+        let data = if pretty {
+            serde_json::to_string_pretty(&self)?
+        } else {
+            serde_json::to_string(&self)?
+        };
+        let mut f = File::create(filename)?;
+        f.write_all(data.as_bytes())?;
+
+        Ok(())
+    }
+}
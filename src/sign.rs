@@ -1,19 +1,14 @@
-#![allow(dead_code)]
-
 use std::path::PathBuf;
 
-mod models;
-mod utils;
-
-use crate::utils::sign::Signer;
+use bouf::Signer;
 use clap::Parser;
 
 #[derive(Parser, Debug)]
 #[command(about, long_about = None)]
 struct Args {
-    // Will use "UPDATER_PRIVATE_KEY" env var if not set
+    // Will use "UPDATER_PRIVATE_KEY" env var if not set, may be specified multiple times
     #[arg(short, long, value_name = "Private key PEM file")]
-    private_key: Option<PathBuf>,
+    private_key: Vec<PathBuf>,
 
     #[arg(short, long, value_name = "Files to sign")]
     files: Vec<PathBuf>,
@@ -22,7 +17,10 @@ struct Args {
 fn main() {
     let args: Args = Args::parse();
 
-    let mut signer = Signer::init(args.private_key.as_ref());
+    let mut signer = Signer::init(&args.private_key);
+    for key_id in signer.key_ids().expect("Failed to load keys!") {
+        println!("Using key \"{key_id}\"");
+    }
 
     for f in args.files {
         println!("Signing \"{}\"", f.display());
@@ -0,0 +1,329 @@
+use std::fs;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::PathBuf;
+
+use anyhow::Result;
+use hashbrown::{HashMap, HashSet};
+use log::info;
+
+use crate::models::config::Config;
+use crate::steps::generate::find_package;
+use crate::steps::prepare::{copy_decision, find_previous_version, CopyDecision};
+use crate::utils::hash::{hash_file, hash_file_code, FileInfo};
+use crate::utils::misc::parse_version;
+
+const CODE_EXTS: [&str; 3] = ["exe", "dll", "pyd"];
+
+/// Report how the configured pipeline handles a (relative) path of the build
+pub struct Explainer<'a> {
+    config: &'a Config,
+    path: String,
+    input_file: PathBuf,
+    prev_version: Option<String>,
+}
+
+impl<'a> Explainer<'a> {
+    pub fn init(conf: &'a Config, path: &str) -> Self {
+        // Internally we always use Unix-style paths
+        let path = path.replace('\\', "/").trim_start_matches("./").to_string();
+
+        Self {
+            config: conf,
+            input_file: conf.env.input_dir.join(&path),
+            path,
+            prev_version: None,
+        }
+    }
+
+    /// Check if the code sections of the file (or the binary belonging to a PDB) are identical
+    /// to the previous build, returns the reused filename prefix in that case.
+    fn code_analysis(&self) -> Option<String> {
+        let prev_build = self
+            .config
+            .env
+            .previous_dir
+            .join("builds")
+            .join(self.prev_version.as_ref()?);
+        let (base, ext) = self.path.rsplit_once('.')?;
+
+        let candidates: Vec<String> = match ext {
+            "pdb" => CODE_EXTS.iter().map(|e| format!("{base}.{e}")).collect(),
+            _ if CODE_EXTS.contains(&ext) => vec![self.path.to_owned()],
+            _ => return None,
+        };
+
+        for candidate in candidates {
+            let new_file = self.config.env.input_dir.join(&candidate);
+            let old_file = prev_build.join(&candidate);
+            if !new_file.exists() || !old_file.exists() {
+                continue;
+            }
+            if hash_file_code(&new_file).hash == hash_file_code(&old_file).hash {
+                info!("  - Code sections of \"{candidate}\" are identical to the previous build");
+                return Some(format!("{base}."));
+            }
+            info!("  - Code sections of \"{candidate}\" differ from the previous build");
+        }
+
+        None
+    }
+
+    fn explain_copy(&self, decision: &CopyDecision) {
+        info!("Copying:");
+        match decision {
+            CopyDecision::OutsideLayout => info!(
+                "  - Not copied: Outside of build layout (roots: {:?}, top-level files: {})",
+                self.config.prepare.layout.roots, self.config.prepare.layout.top_level_files
+            ),
+            CopyDecision::NeverCopy(pattern) => info!("  - Not copied: Matches never_copy pattern \"{pattern}\""),
+            CopyDecision::Override(file) => info!("  - Replaced with override file \"{file}\""),
+            CopyDecision::Reused => info!(
+                "  - Reused: Copied from previous build \"{}\"",
+                self.prev_version.as_deref().unwrap_or_default()
+            ),
+            CopyDecision::AlwaysCopy(pattern) => {
+                info!("  - Copied from new build (matches always_copy pattern \"{pattern}\")")
+            }
+            CopyDecision::Copy => info!("  - Copied from new build"),
+        }
+    }
+
+    fn explain_signing(&self, decision: &CopyDecision) {
+        let prepare = &self.config.prepare;
+        info!("Codesigning:");
+
+        let signed = match decision {
+            CopyDecision::OutsideLayout | CopyDecision::NeverCopy(_) => {
+                info!("  - Not signed: File is not part of the output");
+                false
+            }
+            CopyDecision::Reused => {
+                info!("  - Not signed: File is copied from the previous build along with its existing signature");
+                false
+            }
+            _ if prepare.codesign.skip_sign => {
+                info!("  - Not signed: Codesigning is disabled");
+                false
+            }
+            _ if !prepare
+                .codesign
+                .sign_exts
+                .iter()
+                .any(|e| self.path.ends_with(e.as_str())) =>
+            {
+                info!(
+                    "  - Not signed: Extension not in sign_exts {:?}",
+                    prepare.codesign.sign_exts
+                );
+                false
+            }
+            _ => {
+                info!("  - Signed using {:?} backend", prepare.codesign.sign_backend);
+                true
+            }
+        };
+
+        if let Some(codesign_extra) = prepare.codesign_extra.as_ref().filter(|_| signed) {
+            if let Some(pattern) = codesign_extra.sign_filter.find_match(&self.path) {
+                info!(
+                    "  - Additionally signed using {:?} backend (matches sign_filter pattern \"{pattern}\")",
+                    codesign_extra.sign_backend
+                );
+            }
+        }
+
+        let verify = &prepare.verify_signatures;
+        if !verify.thumbprints.is_empty()
+            && !matches!(decision, CopyDecision::OutsideLayout | CopyDecision::NeverCopy(_))
+            && verify.exts.iter().any(|e| self.path.ends_with(e.as_str()))
+        {
+            match verify.exclude.find_match(&self.path) {
+                Some(pattern) => info!("  - Signature not verified (matches exclude pattern \"{pattern}\")"),
+                None => info!("  - Signature verified against configured thumbprints"),
+            }
+        }
+    }
+
+    fn explain_pdb(&self, decision: &CopyDecision) {
+        if !self.path.ends_with(".pdb") {
+            return;
+        }
+
+        info!("PDB stripping:");
+        match decision {
+            CopyDecision::OutsideLayout | CopyDecision::NeverCopy(_) => info!("  - Not processed"),
+            CopyDecision::Reused => info!("  - Stripped and unstripped PDBs are copied from the previous build"),
+            _ => match self.config.prepare.strip_pdbs.exclude.find_match(&self.path) {
+                Some(pattern) => info!("  - Not stripped (matches strip_pdbs.exclude pattern \"{pattern}\")"),
+                None => info!("  - Moved to PDB directory, stripped copy is shipped"),
+            },
+        }
+    }
+
+    /// Load hashes of old builds from the cache written by the generator (if available)
+    fn old_hashes_cache(&self) -> HashMap<String, FileInfo> {
+        let cache_file = self.config.env.previous_dir.join("builds/cache.json");
+        File::open(cache_file)
+            .ok()
+            .and_then(|f| serde_json::from_reader(BufReader::new(f)).ok())
+            .unwrap_or_default()
+    }
+
+    fn explain_updater(&self, decision: &CopyDecision) -> Result<()> {
+        let generate = &self.config.generate;
+        info!("Updater:");
+
+        // Figure out which file will end up in the install dir (after preparation)
+        let install_file = self.config.env.output_dir.join("install").join(&self.path);
+        let new_file: Option<PathBuf> = if install_file.exists() {
+            Some(install_file)
+        } else {
+            match decision {
+                CopyDecision::Copy | CopyDecision::AlwaysCopy(_) => Some(self.input_file.clone()),
+                CopyDecision::Override(file) => Some(PathBuf::from(file)),
+                CopyDecision::Reused => Some(
+                    self.config
+                        .env
+                        .previous_dir
+                        .join("builds")
+                        .join(self.prev_version.as_ref().unwrap())
+                        .join(&self.path),
+                ),
+                _ => None,
+            }
+        }
+        .filter(|f| f.exists());
+
+        if new_file.is_some() {
+            info!("  - Package: \"{}\"", find_package(self.config, &self.path));
+        }
+        let new_hash = new_file.as_ref().map(|f| hash_file(f).hash);
+        if let Some(new_file) = new_file
+            .as_ref()
+            .filter(|f| !f.starts_with(&self.config.env.output_dir) && *decision != CopyDecision::Reused)
+        {
+            info!(
+                "  - (Hash based on \"{}\", results may differ after signing)",
+                new_file.display()
+            );
+        }
+
+        // Collect versions in "old" directory
+        let builds_dir = self.config.env.previous_dir.join("builds");
+        let mut versions: Vec<String> = fs::read_dir(&builds_dir)?
+            .flatten()
+            .filter(|e| e.path().is_dir())
+            .map(|e| e.file_name().to_string_lossy().into_owned())
+            .collect();
+        versions.sort_by(|a, b| {
+            parse_version(a)
+                .ok()
+                .partial_cmp(&parse_version(b).ok())
+                .unwrap_or(std::cmp::Ordering::Equal)
+        });
+
+        let cache = self.old_hashes_cache();
+        let mut seen_hashes: HashMap<String, String> = HashMap::new();
+        let mut in_old = false;
+        let mut changed = false;
+        let mut unchanged = false;
+
+        for version in versions {
+            // For backwards-compatibility: Files may be in "core/" and "obs-browser/" package folders
+            let Some(old_name) = [
+                format!("{version}/{}", self.path),
+                format!("{version}/core/{}", self.path),
+                format!("{version}/obs-browser/{}", self.path),
+            ]
+            .into_iter()
+            .find(|n| builds_dir.join(n).exists()) else {
+                continue;
+            };
+            in_old = true;
+
+            let Some(new_hash) = &new_hash else {
+                continue;
+            };
+            let old_hash = match cache.get(&old_name) {
+                Some(info) if !info.hash.is_empty() => info.hash.to_owned(),
+                _ => hash_file(&builds_dir.join(&old_name)).hash,
+            };
+
+            if old_hash == *new_hash {
+                unchanged = true;
+                info!("  - {version}: Unchanged");
+            } else if let Some(other) = seen_hashes.get(&old_hash) {
+                changed = true;
+                info!("  - {version}: Uses delta patch of {other} (identical file)");
+            } else {
+                changed = true;
+                info!("  - {version}: Delta patch from {old_hash}");
+                seen_hashes.insert(old_hash, version);
+            }
+        }
+
+        if new_hash.is_some() {
+            let single_threaded = generate.exclude_from_parallel.find_match(&self.path);
+            if let (true, Some(pattern)) = (changed, single_threaded) {
+                info!(
+                    "  - Patches are generated single-threaded (matches exclude_from_parallel pattern \"{pattern}\")"
+                );
+            }
+        }
+
+        let status = match (&new_hash, in_old) {
+            (None, _) if generate.removed_files.contains(&self.path) => "Removed (listed in removed_files)".to_string(),
+            (None, false) => "Not part of the update".to_string(),
+            (None, true) => match generate.exclude_from_removal.find_match(&self.path) {
+                Some(pattern) => format!("Not removed (matches exclude_from_removal pattern \"{pattern}\")"),
+                None => "Removed".to_string(),
+            },
+            (Some(_), false) => "Added".to_string(),
+            (Some(_), true) => match (changed, unchanged) {
+                (true, true) => "Changed (unchanged for some versions)".to_string(),
+                (true, false) => "Changed".to_string(),
+                _ => "Unchanged".to_string(),
+            },
+        };
+        info!("  - Status: {status}");
+
+        Ok(())
+    }
+
+    pub fn run(mut self) -> Result<()> {
+        info!("Explaining \"{}\"", self.path);
+
+        match find_previous_version(self.config) {
+            Ok(version) => {
+                info!("Previous build used: \"{version}\"");
+                self.prev_version = Some(version);
+            }
+            Err(_) => info!("No previous builds found."),
+        }
+
+        info!("Code analysis:");
+        let reused: HashSet<String> = self.code_analysis().into_iter().collect();
+        if reused.is_empty() {
+            info!("  - Not reused from previous build");
+        }
+
+        let decision = copy_decision(
+            &self.config.prepare.copy,
+            &self.config.prepare.layout,
+            &self.path,
+            &reused,
+        );
+        // Overrides may add files that do not exist in the build
+        if self.input_file.exists() || matches!(decision, CopyDecision::Override(_)) {
+            self.explain_copy(&decision);
+            self.explain_signing(&decision);
+            self.explain_pdb(&decision);
+        } else {
+            info!("File does not exist in the new build.");
+        }
+        self.explain_updater(&decision)?;
+
+        Ok(())
+    }
+}
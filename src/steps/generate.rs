@@ -1,21 +1,29 @@
+use std::cmp::Ordering;
 use std::fs;
 use std::io::Write;
 use std::path::PathBuf;
 use std::sync::{Arc, Mutex};
 
-use anyhow::Result;
+use anyhow::{Context, Result};
 use hashbrown::{HashMap, HashSet};
-use indicatif::{ParallelProgressIterator, ProgressBar, ProgressFinish, ProgressIterator, ProgressStyle};
 use log::{info, warn};
 use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
 
 use crate::models::config::{Config, PatchType};
+use crate::models::journal::{Journal, Stage};
 use crate::models::manifest::{FileEntry, Manifest, Package};
+use crate::models::plan::{GeneratePlan, PackageFiles, PatchPlan};
+use crate::models::update_plan::{FetchType, PlanFile, UpdatePlan};
 use crate::utils;
+use crate::utils::estimate::estimate_patch_size_files;
 use crate::utils::hash::FileInfo;
 use crate::utils::misc;
+use crate::utils::progress::{emit, Event, Task};
 use crate::utils::zstd::compress_file;
 
+/// File in the output directory with the download size per previous version
+pub const DOWNLOAD_SIZES_FILE: &str = "download_sizes.json";
+
 struct Patch {
     hash: String,
     name: String,
@@ -40,6 +48,8 @@ struct Analysis {
     // Input file hashmap
     input_map: HashMap<String, FileInfo>,
     compressed_map: HashMap<String, FileInfo>,
+    // Hashes of the files of each previous version as version => (file => hash)
+    old_versions: HashMap<String, HashMap<String, String>>,
     // Sets of added/new files as well as removed/seen ones for processing
     added_files: HashSet<String>,
     all_files: HashSet<String>,
@@ -66,33 +76,26 @@ impl<'a> Generator<'a> {
         ret
     }
 
-    /// Create mapping of filenames to pattern
+    /// Create mapping of filenames to package
     fn fill_package_map(&mut self) {
         let analysis = self.analysis.as_mut().unwrap();
-        // This is a simple list we use to sort files into packages
-        // containing (pattern, package_name) tuples
-        let mut pattern_list: Vec<(&String, &String)> = Vec::new();
-        // If a file matches no pattern, we use the first package without rules as the fallback.
-        // The config validator ensures this exists, we just initialise with the last entry.
-        analysis.default_pkg = self.config.generate.packages.last().unwrap().name.to_owned();
-        for package in &self.config.generate.packages {
-            match &package.include_files {
-                Some(patterns) => {
-                    for pattern in patterns {
-                        pattern_list.push((pattern, &package.name));
-                    }
-                }
-                None => {
-                    analysis.default_pkg = package.name.to_owned();
-                    break;
-                }
-            }
-        }
+        // If a file matches no filter, we use the first package without rules as the fallback.
+        // The config validator ensures this exists.
+        analysis.default_pkg = self
+            .config
+            .generate
+            .packages
+            .iter()
+            .find(|p| p.include_files.is_none())
+            .unwrap_or(self.config.generate.packages.last().unwrap())
+            .name
+            .to_owned();
 
         // (we will look up the same filename multiple times, so precomputing this is probably more efficient!)
         for filename in analysis.all_files.iter() {
-            if let Some((_, pkg_name)) = pattern_list.iter().find(|(pattern, _)| filename.contains(*pattern)) {
-                analysis.package_map.insert(filename.to_owned(), (*pkg_name).to_owned());
+            let pkg_name = find_package(self.config, filename);
+            if *pkg_name != analysis.default_pkg {
+                analysis.package_map.insert(filename.to_owned(), pkg_name.to_owned());
             }
         }
     }
@@ -109,6 +112,13 @@ impl<'a> Generator<'a> {
         info!("  - Unchanged : {} (see unchanged.txt)", unchanged_files_list.len());
         info!("  -   Removed : {} (see removed.txt)", removed_files_list.len());
         info!("  -   Patches : {}", analysis.patch_list.len());
+        emit(Event::Changes {
+            added: added_files_list.len(),
+            changed: changed_files_list.len(),
+            unchanged: unchanged_files_list.len(),
+            removed: removed_files_list.len(),
+            patches: analysis.patch_list.len(),
+        });
 
         write_file_unchecked(self.out_path.join("added.txt"), added_files_list.join("\n"));
         write_file_unchecked(self.out_path.join("removed.txt"), removed_files_list.join("\n"));
@@ -118,12 +128,28 @@ impl<'a> Generator<'a> {
 
     /// Run analysis steps (hashing, creating list of patches, etc.)
     fn analyse(&mut self, skip_patches: bool) {
-        let mut analysis = Analysis { ..Default::default() };
-
         info!("Building hash list for new build");
-        analysis.input_map = utils::hash::get_dir_hashes(&self.inp_path, None);
+        let input_map = utils::hash::get_dir_hashes(&self.inp_path, None, Some(&self.config.prepare.layout));
         info!("Building hash list for old builds");
-        let old_hashes = utils::hash::get_dir_hashes_cache(&self.old_path);
+        let old_hashes = utils::hash::get_dir_hashes_cache(&self.old_path, true);
+
+        let analysis = self.compare(input_map, old_hashes, skip_patches);
+        self.write_file_lists(&analysis);
+
+        self.analysis = Some(analysis);
+    }
+
+    /// Compare hashes of new and old builds to build lists of changes and patches
+    fn compare(
+        &self,
+        input_map: HashMap<String, FileInfo>,
+        old_hashes: HashMap<String, FileInfo>,
+        skip_patches: bool,
+    ) -> Analysis {
+        let mut analysis = Analysis {
+            input_map,
+            ..Default::default()
+        };
         info!("Building list of changes/patches...");
 
         // Initialise added files with all new files, and remove duplicates later
@@ -134,11 +160,17 @@ impl<'a> Generator<'a> {
 
         for (path, fileinfo) in old_hashes {
             // Strip version (first folder name) from path
+            let version = &path[..path.find('/').unwrap_or(0)];
             let mut rel_path = path[path.find('/').unwrap_or(0) + 1..].to_owned();
             // For backwards-compatibility: Remove "core/" and "obs-browser/" package prefixes in filenames
             if rel_path.starts_with("core") || rel_path.starts_with("obs-browser") {
                 rel_path = rel_path[rel_path.find('/').unwrap_or(0) + 1..].parse().unwrap();
             }
+            analysis
+                .old_versions
+                .entry(version.to_owned())
+                .or_default()
+                .insert(rel_path.to_owned(), fileinfo.hash.to_owned());
 
             // Skip (hash, filename) pairs we already added to the patch list
             let seen_key = (fileinfo.hash.to_owned(), rel_path.to_owned());
@@ -146,13 +178,7 @@ impl<'a> Generator<'a> {
                 continue;
             } else if !analysis.input_map.contains_key(&rel_path) {
                 // Only add files to removed that do not match any exclusion filter
-                if !self
-                    .config
-                    .generate
-                    .exclude_from_removal
-                    .iter()
-                    .any(|s| rel_path.contains(s))
-                {
+                if !self.config.generate.exclude_from_removal.matches(&rel_path) {
                     analysis.removed_files.insert(rel_path);
                 }
                 continue;
@@ -185,9 +211,71 @@ impl<'a> Generator<'a> {
             .extend(self.config.generate.removed_files.iter().cloned());
         analysis.all_files.extend(analysis.removed_files.iter().cloned());
 
-        self.write_file_lists(&analysis);
+        analysis
+    }
+
+    /// Determine changes, packages, and patches (with estimated sizes) without writing anything.
+    /// If specified, the files of the prepared build (relative path => source file) are used
+    /// instead of the input directory.
+    pub fn plan(mut self, sources: Option<&HashMap<String, PathBuf>>, skip_patches: bool) -> Result<GeneratePlan> {
+        info!("Building hash list for new build");
+        let input_map = match sources {
+            Some(sources) => utils::hash::get_file_hashes(sources),
+            None => utils::hash::get_dir_hashes(&self.inp_path, None, Some(&self.config.prepare.layout)),
+        };
+        info!("Building hash list for old builds");
+        let old_hashes = utils::hash::get_dir_hashes_cache(&self.old_path, false);
 
+        let mut analysis = self.compare(input_map, old_hashes, skip_patches);
+        if let Some(sources) = sources {
+            for patch in analysis.patch_list.iter_mut() {
+                patch.new_file = sources[&patch.name].to_owned();
+            }
+        }
         self.analysis = Some(analysis);
+        self.fill_package_map();
+        let analysis = self.analysis.as_ref().unwrap();
+
+        let package_name = |f: &String| analysis.package_map.get(f).unwrap_or(&analysis.default_pkg).to_owned();
+        let packages = self
+            .config
+            .generate
+            .packages
+            .iter()
+            .map(|p| PackageFiles {
+                name: p.name.to_owned(),
+                files: analysis.input_map.keys().filter(|f| package_name(f) == p.name).count(),
+            })
+            .collect();
+
+        info!("Estimating size of {} patches...", analysis.patch_list.len());
+        let task = Task::start("estimate", analysis.patch_list.len() as u64);
+        let mut patches = analysis
+            .patch_list
+            .par_iter()
+            .map(|patch| {
+                let plan = PatchPlan {
+                    name: patch.name.to_owned(),
+                    package: package_name(&patch.name),
+                    old_hash: patch.hash.to_owned(),
+                    old_size: fs::metadata(&patch.old_file)?.len(),
+                    new_size: analysis.input_map[&patch.name].size,
+                    estimated_size: estimate_patch_size_files(&patch.old_file, &patch.new_file)?,
+                };
+                task.inc();
+                Ok(plan)
+            })
+            .collect::<Result<Vec<PatchPlan>>>()?;
+        patches.sort_by(|a, b| a.name.cmp(&b.name).then(a.old_hash.cmp(&b.old_hash)));
+
+        Ok(GeneratePlan {
+            added: get_sorted_list(&analysis.added_files),
+            changed: get_sorted_list(&analysis.changed_files),
+            unchanged: get_sorted_list(&analysis.unchanged_files),
+            removed: get_sorted_list(&analysis.removed_files),
+            packages,
+            patches,
+        })
     }
 
     /// Create updater manifest from analysis results
@@ -227,14 +315,14 @@ impl<'a> Generator<'a> {
                 .collect();
 
             // Sort file lists alphabetically for a nicer manifest
-            manifest_package.removed_files.sort_by_key(|a| a.to_lowercase());
-            manifest_package.files.sort_by_key(|a| a.name.to_lowercase());
+            manifest_package.removed_files.sort_by(|a, b| cmp_names(a, b));
+            manifest_package.files.sort_by(|a, b| cmp_names(&a.name, &b.name));
 
             manifest.packages.push(manifest_package);
         }
 
         // Sort packages by name as well
-        manifest.packages.sort_by_key(|a| a.name.to_lowercase());
+        manifest.packages.sort_by(|a, b| cmp_names(&a.name, &b.name));
 
         manifest
     }
@@ -244,83 +332,66 @@ impl<'a> Generator<'a> {
         let analysis = self.analysis.as_mut().unwrap();
         std::fs::create_dir_all(&self.out_path).expect("Failed to create output directory");
 
-        let style =
-            ProgressStyle::with_template("[{elapsed_precise}] {bar:40.cyan/blue} {pos:>7}/{len:7} {msg}").unwrap();
-        let progress_bar = ProgressBar::new(analysis.input_map.len() as u64)
-            .with_style(style)
-            .with_finish(ProgressFinish::AndLeave);
+        let task = Task::start("compress", analysis.input_map.len() as u64);
 
         let comp_map = Arc::new(Mutex::new(&mut analysis.compressed_map));
         let branch = &self.config.general.branch;
         info!("Copying/Compressing new build to updater structure...");
-        analysis
-            .input_map
-            .par_iter()
-            .progress_with(progress_bar)
-            .for_each(|(filename, _)| {
-                let package: &String = analysis.package_map.get(filename).unwrap_or(&analysis.default_pkg);
-                let mut patch_filename = format!("updater/update_studio/{branch}/{package}/{filename}");
-                if self.config.generate.compress_files {
-                    patch_filename += ".zst";
-                }
-                let updater_file = self.out_path.join(patch_filename);
-                let build_file = self.inp_path.join(filename);
-                fs::create_dir_all(updater_file.parent().unwrap()).expect("Failed creating folder!");
+        analysis.input_map.par_iter().for_each(|(filename, _)| {
+            let package: &String = analysis.package_map.get(filename).unwrap_or(&analysis.default_pkg);
+            let mut patch_filename = format!("updater/update_studio/{branch}/{package}/{filename}");
+            if self.config.generate.compress_files {
+                patch_filename += ".zst";
+            }
+            let updater_file = self.out_path.join(patch_filename);
+            let build_file = self.inp_path.join(filename);
+            fs::create_dir_all(updater_file.parent().unwrap()).expect("Failed creating folder!");
 
-                if self.config.generate.compress_files {
-                    let info = compress_file(&build_file, &updater_file).expect("Failed compressing file!");
-                    comp_map.lock().unwrap().insert(filename.to_owned(), info);
-                } else {
-                    fs::copy(build_file, updater_file).expect("Failed copying file!");
-                }
-            });
+            if self.config.generate.compress_files {
+                let info = compress_file(&build_file, &updater_file).expect("Failed compressing file!");
+                comp_map.lock().unwrap().insert(filename.to_owned(), info);
+            } else {
+                fs::copy(build_file, updater_file).expect("Failed copying file!");
+            }
+            task.inc();
+        });
     }
 
-    /// Create patches for old -> new folder
-    /// (Note: can be called standalone to just create deltas)
-    pub fn create_patches(&mut self) -> Result<()> {
+    /// Run analysis if it has not been run yet (e.g. when resuming)
+    fn ensure_analysis(&mut self, skip_patches: bool) {
         if self.analysis.is_none() {
-            self.analyse(false);
+            self.analyse(skip_patches);
             self.fill_package_map();
         }
+    }
+
+    /// Create patches for old -> new folder
+    /// (Note: can be called standalone to just create deltas)
+    pub fn create_patches(&mut self) -> Result<()> {
+        self.ensure_analysis(false);
         std::fs::create_dir_all(&self.out_path).expect("Failed to create output directory");
         let analysis = self.analysis.as_ref().unwrap();
+        if analysis.patch_list.is_empty() {
+            info!("No patches to create");
+            return Ok(());
+        }
 
         // Patches to generate in single-threaded mode (e.g. CEF on CI)
         let patch_list_st: Vec<&Patch> = analysis
             .patch_list
             .iter()
-            .filter(|p| {
-                self.config
-                    .generate
-                    .exclude_from_parallel
-                    .iter()
-                    .any(|s| p.name.contains(s))
-            })
+            .filter(|p| self.config.generate.exclude_from_parallel.matches(&p.name))
             .collect();
         // Patches to generate in multi-threaded mode (yay rayon)
         let patch_list_mt: Vec<&Patch> = analysis
             .patch_list
             .iter()
-            .filter(|p| {
-                !self
-                    .config
-                    .generate
-                    .exclude_from_parallel
-                    .iter()
-                    .any(|s| p.name.contains(s))
-            })
+            .filter(|p| !self.config.generate.exclude_from_parallel.matches(&p.name))
             .collect();
 
         let branch = &self.config.general.branch;
         let num = patch_list_mt.len() as u64;
 
-        let style =
-            ProgressStyle::with_template("[{elapsed_precise}] {bar:40.cyan/blue} {pos:>7}/{len:7} {msg}").unwrap();
-        let progress_bar_mt = ProgressBar::new(num)
-            .with_style(style.clone())
-            .with_finish(ProgressFinish::AndLeave);
-
         info!(
             "Creating delta-patches... (using: {:?})",
             self.config.generate.patch_type
@@ -330,63 +401,214 @@ impl<'a> Generator<'a> {
             PatchType::Zstd => utils::zstd::create_patch,
         };
 
-        patch_list_mt
-            .par_iter()
-            .progress_with(progress_bar_mt)
-            .for_each(|patch| {
-                let package: &String = analysis.package_map.get(&patch.name).unwrap_or(&analysis.default_pkg);
-                let patch_filename = format!(
-                    "updater/patches_studio/{}/{}/{}/{}",
-                    branch, package, patch.name, patch.hash
-                );
-                let outfile = self.out_path.join(patch_filename);
-                // Ensure directories exist (Note: this is thread-safe in Rust!)
-                fs::create_dir_all(outfile.parent().unwrap()).expect("Failed creating folder!");
-                patch_fun(&patch.old_file, &patch.new_file, &outfile).expect("Creating patch failed horribly.");
+        let make_patch = |patch: &Patch| {
+            let package: &String = analysis.package_map.get(&patch.name).unwrap_or(&analysis.default_pkg);
+            let patch_filename = format!(
+                "updater/patches_studio/{}/{}/{}/{}",
+                branch, package, patch.name, patch.hash
+            );
+            let outfile = self.out_path.join(patch_filename);
+            // Ensure directories exist (Note: this is thread-safe in Rust!)
+            fs::create_dir_all(outfile.parent().unwrap()).expect("Failed creating folder!");
+            patch_fun(&patch.old_file, &patch.new_file, &outfile).expect("Creating patch failed horribly.");
+
+            let size = |path: &PathBuf| fs::metadata(path).map(|m| m.len()).unwrap_or_default();
+            emit(Event::PatchCreated {
+                name: &patch.name,
+                package,
+                old_hash: &patch.hash,
+                old_size: size(&patch.old_file),
+                new_size: size(&patch.new_file),
+                patch_size: size(&outfile),
             });
+        };
+
+        let task = Task::start("patch", num);
+        patch_list_mt.par_iter().for_each(|patch| {
+            make_patch(patch);
+            task.inc();
+        });
+        drop(task);
 
         // If any patches were assigned to the non-parallel patch list run them here
         if !patch_list_st.is_empty() {
-            let num = patch_list_st.len() as u64;
-            let progress_bar_st = ProgressBar::new(num)
-                .with_style(style)
-                .with_finish(ProgressFinish::AndLeave);
-
             info!("Creating non-parallel delta-patches...");
-            patch_list_st.iter().progress_with(progress_bar_st).for_each(|patch| {
-                let package: &String = analysis.package_map.get(&patch.name).unwrap_or(&analysis.default_pkg);
-                let patch_filename = format!(
-                    "updater/patches_studio/{}/{}/{}/{}",
-                    branch, package, patch.name, patch.hash
-                );
-                let outfile = self.out_path.join(patch_filename);
-                fs::create_dir_all(outfile.parent().unwrap()).expect("Failed creating folder!");
-                patch_fun(&patch.old_file, &patch.new_file, &outfile).expect("Creating patch failed horribly.");
+            let task = Task::start("patch", patch_list_st.len() as u64);
+            patch_list_st.iter().for_each(|patch| {
+                make_patch(patch);
+                task.inc();
             });
         }
 
         Ok(())
     }
 
-    pub fn run(mut self, skip_patches: bool) -> Result<Manifest> {
-        // ToDo add errors to individual steps
-        self.analyse(skip_patches);
-        self.fill_package_map();
-        self.copy_build();
-        let manifest = self.create_manifest();
-
+    /// Determine which files a user on each previous version has to fetch to update, using
+    /// patches where they exist and the (compressed) full files otherwise
+    fn update_plans(&self) -> Result<Vec<UpdatePlan>> {
         let analysis = self.analysis.as_ref().unwrap();
-        if skip_patches || analysis.patch_list.is_empty() {
-            info!("No patches to create or patch generation skipped");
-            return Ok(manifest);
+        let branch = &self.config.general.branch;
+        let current = misc::get_filename_version(&self.config.obs_version, false);
+        let patches: HashSet<(&str, &str)> = analysis
+            .patch_list
+            .iter()
+            .map(|p| (p.name.as_str(), p.hash.as_str()))
+            .collect();
+
+        let mut plans = Vec::new();
+        for (version, old_files) in analysis.old_versions.iter().filter(|(v, _)| **v != current) {
+            let mut plan = UpdatePlan {
+                from_version: version.to_owned(),
+                to_version: self.config.obs_version.version_str.to_owned(),
+                branch: branch.to_owned(),
+                ..Default::default()
+            };
+
+            for (name, info) in &analysis.input_map {
+                let old_hash = old_files.get(name);
+                if old_hash == Some(&info.hash) {
+                    continue;
+                }
+
+                let package = analysis.package_map.get(name).unwrap_or(&analysis.default_pkg);
+                let mut url = format!("update_studio/{branch}/{package}/{name}");
+                if self.config.generate.compress_files {
+                    url += ".zst";
+                }
+                // Compressed sizes are not known if Generate was completed in a previous run,
+                // so use the size of the file that is actually downloaded
+                let full_path = self.out_path.join("updater").join(&url);
+                let size = fs::metadata(&full_path)
+                    .context(format!("Updater file {full_path:?} not found"))?
+                    .len();
+                let mut file = PlanFile {
+                    name: name.to_owned(),
+                    package: package.to_owned(),
+                    fetch: FetchType::Full,
+                    url,
+                    size,
+                    hash: info.hash.to_owned(),
+                    old_hash: None,
+                };
+
+                if let Some(old_hash) = old_hash.filter(|h| patches.contains(&(name.as_str(), h.as_str()))) {
+                    let patch_url = format!("patches_studio/{branch}/{package}/{name}/{old_hash}");
+                    match fs::metadata(self.out_path.join("updater").join(&patch_url)) {
+                        Ok(meta) => {
+                            file.fetch = FetchType::Patch;
+                            file.url = patch_url;
+                            file.size = meta.len();
+                            file.old_hash = Some(old_hash.to_owned());
+                        }
+                        Err(_) => {
+                            warn!("Patch for \"{name}\" ({old_hash}) is missing, file has to be downloaded in full")
+                        }
+                    }
+                }
+
+                plan.files.push(file);
+            }
+
+            plan.removed_files = analysis
+                .removed_files
+                .iter()
+                .filter(|f| old_files.contains_key(*f))
+                .cloned()
+                .collect();
+            plan.files.sort_by(|a, b| a.name.cmp(&b.name));
+            plan.removed_files.sort();
+            plan.download_size = plan.files.iter().map(|f| f.size).sum();
+            plans.push(plan);
         }
 
-        self.create_patches()?;
+        // Newest version first
+        plans.sort_by(|a, b| {
+            match (
+                misc::parse_version(&a.from_version),
+                misc::parse_version(&b.from_version),
+            ) {
+                (Ok(ver_a), Ok(ver_b)) => ver_b.partial_cmp(&ver_a).unwrap_or(Ordering::Equal),
+                _ => b.from_version.cmp(&a.from_version),
+            }
+        });
+
+        Ok(plans)
+    }
+
+    /// Write update plans for each previous version to "updater/plans/<branch>/<version>.json",
+    /// and their download sizes to "download_sizes.json"
+    fn write_update_plans(&mut self, skip_patches: bool) -> Result<()> {
+        self.ensure_analysis(skip_patches);
+        let plans = self.update_plans()?;
+
+        let plans_dir = self.out_path.join("updater/plans").join(&self.config.general.branch);
+        // Remove plans of a previous run, the old versions may have changed
+        if plans_dir.exists() {
+            fs::remove_dir_all(&plans_dir)?;
+        }
+        fs::create_dir_all(&plans_dir)?;
+
+        info!("Download size per previous version:");
+        let mut sizes = Vec::new();
+        for plan in &plans {
+            let size = plan.download_size();
+            info!(
+                "  - {:>12}: {:>10} ({} patches, {} full files)",
+                size.version,
+                misc::format_size(size.total_size),
+                size.patched_files,
+                size.full_files
+            );
+            sizes.push(size);
+
+            let filename = plans_dir.join(format!("{}.json", plan.from_version));
+            plan.to_file(&filename, self.config.package.updater.pretty_json)?;
+        }
+
+        let data = serde_json::to_string_pretty(&sizes)?;
+        fs::write(self.out_path.join(DOWNLOAD_SIZES_FILE), data)?;
+
+        Ok(())
+    }
+
+    pub fn run(mut self, skip_patches: bool, journal: &mut Journal) -> Result<Manifest> {
+        // ToDo add errors to individual steps
+        let manifest = journal.run_stage(Stage::Generate, || {
+            self.analyse(skip_patches);
+            self.fill_package_map();
+            self.copy_build();
+            Ok(self.create_manifest())
+        })?;
+
+        journal.run_stage(Stage::Patches, || {
+            // Generate may have been completed in a previous run, in which case nothing was analysed yet
+            self.ensure_analysis(skip_patches);
+            if skip_patches {
+                info!("Patch generation skipped");
+            } else {
+                self.create_patches()?;
+            }
+            self.write_update_plans(skip_patches)
+        })?;
 
         Ok(manifest)
     }
 }
 
+/// Find the package a file belongs to, files matching no filter (or only filters of packages after it)
+/// are put into the first package without filters
+pub fn find_package<'c>(config: &'c Config, filename: &str) -> &'c String {
+    for package in &config.generate.packages {
+        match &package.include_files {
+            Some(filter) if filter.matches(filename) => return &package.name,
+            Some(_) => continue,
+            None => return &package.name,
+        }
+    }
+
+    &config.generate.packages.last().unwrap().name
+}
+
 /// Write text file, logging but ultimately ignoring errors
 fn write_file_unchecked(filename: PathBuf, contents: String) {
     if let Ok(mut f) = fs::File::create(&filename) {
@@ -396,10 +618,138 @@ fn write_file_unchecked(filename: PathBuf, contents: String) {
     }
 }
 
+/// Compare names case-insensitively, names only differing in case are ordered
+/// by the original name so the order does not depend on hash map iteration
+fn cmp_names(a: &str, b: &str) -> Ordering {
+    a.to_lowercase().cmp(&b.to_lowercase()).then(a.cmp(b))
+}
+
 /// Turn string hashset into sorted vector
 fn get_sorted_list(inp: &HashSet<String>) -> Vec<String> {
     let mut list = inp.into_iter().cloned().collect::<Vec<_>>();
-    list.sort_by_key(|a| a.to_lowercase());
+    list.sort_by(|a, b| cmp_names(a, b));
 
     list
 }
+
+#[cfg(test)]
+mod generate_tests {
+    use super::*;
+    use crate::utils::misc::TestDir;
+
+    #[test]
+    fn test_update_plans() {
+        let dir = TestDir::new("plans");
+        let mut conf = Config::default();
+        conf.env.output_dir = dir.to_owned();
+        conf.set_version(&"31.0.0".to_string(), 0, 0).unwrap();
+        let branch = conf.general.branch.to_owned();
+
+        let info = |hash: &str, size: u64| FileInfo {
+            hash: hash.to_string(),
+            size,
+        };
+        let hashes = |files: &[(&str, &str)]| -> HashMap<String, String> {
+            files.iter().map(|(f, h)| (f.to_string(), h.to_string())).collect()
+        };
+        let patch = |name: &str, hash: &str| Patch {
+            hash: hash.to_string(),
+            name: name.to_string(),
+            old_file: PathBuf::new(),
+            new_file: PathBuf::new(),
+        };
+        let mut analysis = Analysis {
+            input_map: HashMap::from([
+                ("obs.dll".to_string(), info("new_dll", 100)),
+                ("obs.exe".to_string(), info("new_exe", 200)),
+                ("data.txt".to_string(), info("same", 10)),
+            ]),
+            old_versions: HashMap::from([
+                ("31.0.0".to_string(), hashes(&[("obs.dll", "new_dll")])),
+                (
+                    "30.0.0".to_string(),
+                    hashes(&[
+                        ("obs.dll", "old_dll"),
+                        ("obs.exe", "old_exe"),
+                        ("data.txt", "same"),
+                        ("gone.txt", "x"),
+                    ]),
+                ),
+                (
+                    "29.0.0".to_string(),
+                    hashes(&[("obs.dll", "older_dll"), ("data.txt", "same")]),
+                ),
+            ]),
+            patch_list: vec![
+                patch("obs.dll", "old_dll"),
+                patch("obs.dll", "older_dll"),
+                patch("obs.exe", "old_exe"),
+            ],
+            default_pkg: "core".to_string(),
+            ..Default::default()
+        };
+        analysis.removed_files.insert("gone.txt".to_string());
+
+        // Full files (compressed) and all patches except the one for "obs.exe"
+        let updater = dir.join("updater");
+        let files = [
+            (format!("update_studio/{branch}/core/obs.dll.zst"), 50),
+            (format!("update_studio/{branch}/core/obs.exe.zst"), 70),
+            (format!("update_studio/{branch}/core/data.txt.zst"), 5),
+            (format!("patches_studio/{branch}/core/obs.dll/old_dll"), 3),
+            (format!("patches_studio/{branch}/core/obs.dll/older_dll"), 4),
+        ];
+        for (path, size) in &files {
+            let path = updater.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, vec![0u8; *size]).unwrap();
+        }
+
+        let mut generator = Generator::init(&conf, false);
+        generator.analysis = Some(analysis);
+        let plans = generator.update_plans().unwrap();
+
+        // Newest version first, the current version has no plan
+        let versions: Vec<&str> = plans.iter().map(|p| p.from_version.as_str()).collect();
+        assert_eq!(versions, ["30.0.0", "29.0.0"]);
+
+        // Unchanged files are not fetched, the missing patch falls back to the full file
+        let summary = |plan: &UpdatePlan| -> Vec<(String, FetchType, String, u64)> {
+            plan.files
+                .iter()
+                .map(|f| (f.name.to_owned(), f.fetch, f.url.to_owned(), f.size))
+                .collect()
+        };
+        assert_eq!(
+            summary(&plans[0]),
+            [
+                (
+                    "obs.dll".to_string(),
+                    FetchType::Patch,
+                    format!("patches_studio/{branch}/core/obs.dll/old_dll"),
+                    3
+                ),
+                (
+                    "obs.exe".to_string(),
+                    FetchType::Full,
+                    format!("update_studio/{branch}/core/obs.exe.zst"),
+                    70
+                ),
+            ]
+        );
+        assert_eq!(plans[0].files[0].old_hash.as_deref(), Some("old_dll"));
+        assert_eq!(plans[0].files[1].old_hash, None);
+        assert_eq!(plans[0].removed_files, ["gone.txt"]);
+        assert_eq!(plans[0].download_size, 73);
+
+        // Files missing in the old version are fetched in full, removals only apply if the file existed
+        assert_eq!(summary(&plans[1])[0].1, FetchType::Patch);
+        assert_eq!(summary(&plans[1])[1].1, FetchType::Full);
+        assert!(plans[1].removed_files.is_empty());
+        assert_eq!(plans[1].download_size, 74);
+
+        // Full files that were not written are an error
+        fs::remove_file(updater.join(&files[1].0)).unwrap();
+        assert!(generator.update_plans().is_err());
+    }
+}
@@ -1,4 +1,9 @@
+pub mod explain;
 pub mod generate;
 pub mod package;
+pub mod pipeline;
 pub mod post;
 pub mod prepare;
+pub mod release;
+pub mod report;
+pub mod verify;
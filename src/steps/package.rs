@@ -3,25 +3,33 @@ use std::io::Write;
 use std::path::PathBuf;
 use std::process::Command;
 
-use anyhow::{anyhow, Result};
-use log::error;
-#[cfg(windows)]
-use log::info;
-
-#[cfg(windows)]
-use crate::utils::codesign::sign;
-
-use crate::models::config::{Config, EnvOptions};
+use anyhow::{anyhow, bail, Context, Result};
+use log::{error, info};
+use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
+use walkdir::WalkDir;
+
+use crate::models::config::{
+    ArchiveFormat, ArchiveOptions, Config, EnvOptions, NotesConverter, NotesFormat, ZipBackend,
+};
+use crate::models::index::{FileIndex, IndexEntry};
 use crate::models::manifest::Manifest;
-use crate::utils::hash::hash_file;
+use crate::models::plan::PackagePlan;
+use crate::steps::generate::find_package;
+use crate::utils::codesign::sign;
+use crate::utils::hash::{hash_file, hash_file_sha256};
 use crate::utils::misc;
+use crate::utils::misc::list_dir;
+use crate::utils::notes;
+use crate::utils::tar::create_tar;
+use crate::utils::template::Templates;
+use crate::utils::winget;
+use crate::utils::zip::create_zip;
 
-#[allow(dead_code)]
 pub struct Packaging<'a> {
     config: &'a Config,
     short_version: String,
-    #[allow(unused_variables)]
     tag_version: String,
+    templates: Templates,
 }
 
 impl<'a> Packaging<'a> {
@@ -30,29 +38,52 @@ impl<'a> Packaging<'a> {
             config: conf,
             short_version: misc::get_filename_version(&conf.obs_version, true),
             tag_version: misc::get_filename_version(&conf.obs_version, false),
+            templates: Templates::new(conf),
         }
     }
 
-    #[cfg(windows)]
-    #[allow(clippy::uninlined_format_args)]
-    pub fn run_nsis(&self) -> Result<()> {
-        // ToDo make installer name more configurable
-        let nsis_script = self.config.package.installer.nsis_script.canonicalize()?;
-        // The build dir is the "install" subfolder in the output dir
+    /// Build directory (the "install" subfolder in the output dir) in the form NSIS expects
+    fn nsis_build_dir(&self) -> Result<String> {
         let build_dir = self.config.env.output_dir.join("install").canonicalize()?;
-        let mut build_dir_str = build_dir.into_os_string().into_string().unwrap();
-        // Sanitise build dir string for NSIS
-        if build_dir_str.starts_with('\\') {
-            build_dir_str = build_dir_str.strip_prefix("\\\\?\\").unwrap().to_string();
+        let build_dir_str = build_dir
+            .into_os_string()
+            .into_string()
+            .map_err(|p| anyhow!("Build directory {p:?} is not valid UTF-8"))?;
+
+        // Sanitise build dir string for NSIS (no extended-length path prefix on Windows), on other platforms
+        // makensis converts the backslashes used in the script (e.g. "${BUILDDIR}\..") to forward slashes
+        match build_dir_str.strip_prefix("\\\\?\\") {
+            Some(stripped) => Ok(stripped.to_string()),
+            None => Ok(build_dir_str),
         }
+    }
 
-        let args: Vec<OsString> = vec![
-            format!("/DTAGVERSION={}", self.tag_version).into(),
-            format!("/DAPPVERSION={}", self.config.obs_version.version_str).into(),
-            format!("/DSHORTVERSION={}", self.short_version).into(),
-            format!("/DBUILDDIR={}", build_dir_str).into(),
-            nsis_script.into_os_string(),
+    pub fn run_nsis(&self) -> Result<()> {
+        let installer = &self.config.package.installer;
+        let nsis_script = installer.nsis_script.canonicalize()?;
+        let installer_name = self.templates.render(&installer.name);
+
+        let mut defines = vec![
+            ("TAGVERSION", self.tag_version.to_owned()),
+            ("APPVERSION", self.config.obs_version.version_str.to_owned()),
+            ("SHORTVERSION", self.short_version.to_owned()),
+            ("BUILDDIR", self.nsis_build_dir()?),
+            ("INSTALLERNAME", installer_name),
         ];
+        for (name, value) in &installer.defines {
+            if defines.iter().any(|(builtin, _)| builtin == name) {
+                bail!("NSIS define \"{name}\" is already set by bouf");
+            }
+            defines.push((name, self.templates.render(value)));
+        }
+
+        // makensis only accepts "-" as option prefix on non-Windows platforms
+        let prefix = if cfg!(windows) { "/" } else { "-" };
+        let mut args: Vec<OsString> = defines
+            .into_iter()
+            .map(|(name, value)| format!("{prefix}D{name}={value}").into())
+            .collect();
+        args.push(nsis_script.into_os_string());
 
         info!(" => Running NSIS...");
         let output = Command::new(&self.config.env.makensis_path).args(args).output()?;
@@ -66,7 +97,13 @@ impl<'a> Packaging<'a> {
         } else {
             info!("NSIS completed successfully!");
 
-            if !self.config.package.installer.skip_sign {
+            // The script writes the installer to "${BUILDDIR}\..", i.e. the output directory
+            let installer_path = self.installer_path();
+            if !installer_path.exists() {
+                bail!("Installer not found at {installer_path:?}, check the OutFile of the NSIS script");
+            }
+
+            if !installer.skip_sign {
                 self.sign_installer()?;
                 info!("Installer signed successfully!");
             }
@@ -75,18 +112,86 @@ impl<'a> Packaging<'a> {
         }
     }
 
-    #[cfg(unix)]
-    pub fn run_nsis(&self) -> Result<()> {
-        use log::warn;
-        warn!("Creating an installer is not (yet) supported on this platform.");
+    pub fn installer_path(&self) -> PathBuf {
+        let filename = self.templates.render(&self.config.package.installer.name);
+        self.config.env.output_dir.join(filename)
+    }
 
-        Ok(())
+    fn archive_path(&self, archive: &ArchiveOptions) -> PathBuf {
+        let name = self.templates.render(&archive.name);
+        self.config.env.output_dir.join(name)
+    }
+
+    /// Files in an archive's source directory, or the files of a package in "install/",
+    /// without excluded packages and with the extra files added
+    fn archive_files(&self, archive: &ArchiveOptions) -> Result<Vec<(String, PathBuf)>> {
+        let output_dir = &self.config.env.output_dir;
+        let mut files: Vec<(String, PathBuf)> = match archive.source.as_str() {
+            "install" | "pdbs" => list_dir(&output_dir.join(&archive.source))?,
+            package => list_dir(&output_dir.join("install"))?
+                .into_iter()
+                .filter(|(name, _)| find_package(self.config, name) == package)
+                .collect(),
+        };
+
+        if !archive.exclude_packages.is_empty() {
+            files.retain(|(name, _)| !archive.exclude_packages.contains(find_package(self.config, name)));
+        }
+        for (name, file) in &archive.extra_files {
+            let name = name.replace('\\', "/");
+            files.retain(|(existing, _)| *existing != name);
+            files.push((name, file.to_owned()));
+        }
+
+        Ok(files)
+    }
+
+    fn winget_dir(&self) -> PathBuf {
+        self.config.env.output_dir.join("winget")
+    }
+
+    pub fn manifest_path(&self) -> PathBuf {
+        let branch = &self.config.general.branch;
+        self.config.env.output_dir.join(branch_filename("manifest", branch))
+    }
+
+    pub fn file_index_path(&self) -> PathBuf {
+        let branch = &self.config.general.branch;
+        self.config.env.output_dir.join(branch_filename("files", branch))
+    }
+
+    /// List the files that packaging would create
+    pub fn plan(&self, prepared: bool, updater: bool) -> PackagePlan {
+        let mut plan = PackagePlan::default();
+
+        if prepared && !self.config.package.installer.skip {
+            plan.installer = Some(self.installer_path());
+        }
+        if prepared {
+            plan.zips = self
+                .config
+                .package
+                .archives
+                .iter()
+                .filter(|a| !a.skip)
+                .map(|a| self.archive_path(a))
+                .collect();
+        }
+        if prepared && !self.config.package.installer.skip && self.config.package.winget.enabled {
+            plan.winget = Some(self.winget_dir());
+        }
+        if updater {
+            plan.manifest = Some(self.manifest_path());
+            if self.config.package.updater.file_index {
+                plan.file_index = Some(self.file_index_path());
+            }
+        }
+
+        plan
     }
 
-    #[cfg(windows)]
     fn sign_installer(&self) -> Result<()> {
-        let filename = format!("OBS-Studio-{}-Full-Installer-x64.exe", self.short_version);
-        let path = self.config.env.output_dir.join(filename).canonicalize()?;
+        let path = self.installer_path().canonicalize()?;
 
         info!("Signing installer file \"{}\"", path.display());
         let files: Vec<PathBuf> = vec![path];
@@ -95,50 +200,117 @@ impl<'a> Packaging<'a> {
         Ok(())
     }
 
-    pub fn create_zips(&self) -> Result<()> {
-        let zip_name = self.config.package.zip.name.replace("{version}", &self.short_version);
-        let pdb_zip_name = self
-            .config
-            .package
-            .zip
-            .pdb_name
-            .replace("{version}", &self.short_version);
+    pub fn create_archives(&self) -> Result<()> {
+        for archive in self.config.package.archives.iter().filter(|a| !a.skip) {
+            let path = self.archive_path(archive);
+            info!(" => Creating \"{}\"...", path.display());
+
+            match (archive.format, archive.backend) {
+                (ArchiveFormat::Zip, ZipBackend::SevenZip) => {
+                    let source_dir = self.config.env.output_dir.join(&archive.source);
+                    run_sevenzip(&self.config.env.sevenzip_path, &source_dir.join("*"), &path)?
+                }
+                (ArchiveFormat::Zip, ZipBackend::Native) => {
+                    create_zip(&self.archive_files(archive)?, &path, archive.compression, archive.level)?
+                }
+                (format, _) => create_tar(&self.archive_files(archive)?, &path, format, archive.level)?,
+            }
+        }
 
-        let obs_path = self.config.env.output_dir.join("install/*");
-        let pdb_path = self.config.env.output_dir.join("pdbs/*");
-        let obs_zip_path = self.config.env.output_dir.join(zip_name);
-        let pdb_zip_path = self.config.env.output_dir.join(pdb_zip_name);
+        Ok(())
+    }
 
-        run_sevenzip(&self.config.env.sevenzip_path, &obs_path, &obs_zip_path)?;
-        run_sevenzip(&self.config.env.sevenzip_path, &pdb_path, &pdb_zip_path)?;
+    /// Create winget manifests for the installer
+    pub fn create_winget_manifests(&self) -> Result<()> {
+        let installer = self.installer_path();
+        if !installer.exists() {
+            bail!("Installer {installer:?} not found")
+        }
+
+        for path in winget::write_manifests(self.config, &installer, &self.winget_dir())? {
+            info!(" => Created \"{}\"", path.display());
+        }
 
         Ok(())
     }
 
     pub fn finalise_manifest(&self, manifest: &mut Manifest) -> Result<PathBuf> {
-        let branch = &self.config.general.branch;
-
-        let manifest_filename = if branch.is_empty() || branch == "stable" {
-            "manifest.json".to_string()
-        } else {
-            format!("manifest_{branch}.json")
+        let manifest_path = self.manifest_path();
+        let notes_ext = match self.config.package.updater.notes_format() {
+            NotesFormat::Markdown => "md",
+            _ => "rst",
         };
-
-        let manifest_path = self.config.env.output_dir.join(manifest_filename);
-        let notes_path = self.config.env.output_dir.join("notes.rst");
+        let notes_path = self.config.env.output_dir.join(format!("notes.{notes_ext}"));
 
         // Add VC hash
         let hash = hash_file(&self.config.package.updater.vc_redist_path);
         manifest.vc2019_redist_x64 = hash.hash;
 
         // Add notes and copy them to output
-        manifest.notes = run_pandoc(&self.config.package.updater.notes_file, &self.config.env)?;
-        std::fs::copy(&self.config.package.updater.notes_file, notes_path)?;
+        let updater = &self.config.package.updater;
+        let notes_format = updater.notes_format();
+        manifest.notes = match updater.notes_converter {
+            NotesConverter::Native => {
+                let notes = std::fs::read_to_string(&updater.notes_file)
+                    .context(format!("Failed reading release notes {:?}", updater.notes_file))?;
+                notes::to_html(&notes, notes_format)
+            }
+            NotesConverter::Pandoc => run_pandoc(&updater.notes_file, notes_format, &self.config.env)?,
+        };
+        std::fs::copy(&updater.notes_file, notes_path)?;
 
         manifest.to_file(&manifest_path, self.config.package.updater.pretty_json)?;
 
         Ok(manifest_path)
     }
+
+    /// Create index of all files in the updater directory (compressed files and patches)
+    pub fn create_file_index(&self) -> Result<PathBuf> {
+        let updater_path = self.config.env.output_dir.join("updater");
+        let index_path = self.file_index_path();
+
+        let files: Vec<PathBuf> = WalkDir::new(&updater_path)
+            .into_iter()
+            .filter_map(Result::ok)
+            .filter(|e| !e.file_type().is_dir())
+            .map(|e| e.into_path())
+            .collect();
+
+        info!(" => Hashing {} updater files...", files.len());
+        let mut entries = files
+            .par_iter()
+            .map(|path| {
+                let finfo = hash_file(path);
+                let relative_path = path.strip_prefix(&updater_path)?.to_str().unwrap();
+
+                Ok(IndexEntry {
+                    name: relative_path.replace('\\', "/"),
+                    size: finfo.size,
+                    blake2: finfo.hash,
+                    sha256: hash_file_sha256(path)?,
+                })
+            })
+            .collect::<Result<Vec<IndexEntry>>>()?;
+        entries.sort_by(|a, b| a.name.cmp(&b.name));
+
+        let index = FileIndex {
+            version: self.tag_version.to_owned(),
+            branch: self.config.general.branch.to_owned(),
+            files: entries,
+        };
+        index.to_file(&index_path, self.config.package.updater.pretty_json)?;
+
+        Ok(index_path)
+    }
+}
+
+/// Get name of file in output directory with optional branch suffix (e.g. "manifest_beta.json")
+fn branch_filename(base: &str, branch: &str) -> String {
+    if branch.is_empty() || branch == "stable" {
+        format!("{base}.json")
+    } else {
+        format!("{base}_{branch}.json")
+    }
 }
 
 fn run_sevenzip(sevenzip: &PathBuf, in_path: &PathBuf, out_path: &PathBuf) -> Result<()> {
@@ -164,10 +336,14 @@ fn run_sevenzip(sevenzip: &PathBuf, in_path: &PathBuf, out_path: &PathBuf) -> Re
     }
 }
 
-fn run_pandoc(path: &PathBuf, env: &EnvOptions) -> Result<String> {
+fn run_pandoc(path: &PathBuf, format: NotesFormat, env: &EnvOptions) -> Result<String> {
+    let from = match format {
+        NotesFormat::Markdown => "commonmark",
+        _ => "rst",
+    };
     let args: Vec<OsString> = vec![
         "--from".into(),
-        "rst".into(),
+        from.into(),
         "--to".into(),
         "html".into(),
         path.to_owned().into_os_string(),
@@ -184,3 +360,69 @@ fn run_pandoc(path: &PathBuf, env: &EnvOptions) -> Result<String> {
         Ok(String::from_utf8(output.stdout)?)
     }
 }
+
+#[cfg(test)]
+mod package_tests {
+    use std::fs;
+
+    use super::*;
+    use crate::models::config::ManifestPackageOptions;
+    use crate::utils::filter::PathFilter;
+    use crate::utils::misc::TestDir;
+
+    #[test]
+    fn test_archive_files() {
+        let dir = TestDir::new("package");
+        let mut conf = Config::default();
+        conf.env.output_dir = dir.to_owned();
+        conf.set_version(&"31.0.0".to_string(), 0, 0).unwrap();
+        conf.generate.packages = vec![
+            ManifestPackageOptions {
+                name: "browser".to_string(),
+                include_files: Some(PathFilter::from(vec!["obs-browser".to_string()])),
+            },
+            ManifestPackageOptions {
+                name: "core".to_string(),
+                include_files: None,
+            },
+        ];
+
+        for file in [
+            "install/bin/64bit/obs64.exe",
+            "install/obs-plugins/64bit/obs-browser.dll",
+            "install/config/global.ini",
+            "portable/global.ini",
+            "portable/portable_mode.txt",
+        ] {
+            let path = dir.join(file);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, file).unwrap();
+        }
+
+        let archive = ArchiveOptions {
+            name: "portable.zip".to_string(),
+            exclude_packages: vec!["browser".to_string()],
+            extra_files: [
+                ("config\\global.ini".to_string(), dir.join("portable/global.ini")),
+                ("portable_mode.txt".to_string(), dir.join("portable/portable_mode.txt")),
+            ]
+            .into(),
+            ..Default::default()
+        };
+        let mut files = Packaging::init(&conf).archive_files(&archive).unwrap();
+        files.sort();
+
+        // Excluded packages are left out, extra files replace files with the same name
+        assert_eq!(
+            files,
+            [
+                (
+                    "bin/64bit/obs64.exe".to_string(),
+                    dir.join("install/bin/64bit/obs64.exe")
+                ),
+                ("config/global.ini".to_string(), dir.join("portable/global.ini")),
+                ("portable_mode.txt".to_string(), dir.join("portable/portable_mode.txt")),
+            ]
+        );
+    }
+}
@@ -0,0 +1,273 @@
+use std::path::PathBuf;
+
+use anyhow::{bail, Context, Result};
+use log::{info, warn};
+
+use crate::models::args::{Command, MainArgs};
+use crate::models::config::Config;
+use crate::models::journal::{Journal, Stage};
+use crate::models::manifest::Manifest;
+use crate::models::plan::Plan;
+use crate::steps::explain::Explainer;
+use crate::steps::generate::Generator;
+use crate::steps::package::Packaging;
+use crate::steps::post;
+use crate::steps::prepare::{find_previous_version, Preparator};
+use crate::steps::release::write_release_files;
+use crate::steps::report::write_report;
+use crate::utils::sign::Signer;
+
+/// Run the command specified in the arguments (all stages by default) with a validated config
+pub fn execute(conf: &Config, args: &MainArgs) -> Result<()> {
+    if let Some(Command::Explain { path }) = &args.command {
+        return Explainer::init(conf, path).run();
+    }
+
+    if args.dry_run {
+        return dry_run(conf, args);
+    }
+
+    info!("bouf process started with the following locations set:");
+    info!(" - Input dir: {}", conf.env.input_dir.display());
+    info!(" - Previous versions dir: {}", conf.env.previous_dir.display());
+    info!(" - Output dir: {}", conf.env.output_dir.display());
+
+    let install_dir = conf.env.output_dir.join("install");
+    let mut journal = Journal::in_memory();
+    match args.command.as_ref().unwrap_or(&Command::Run) {
+        Command::Run => run(conf, args)?,
+        Command::Prepare => prepare(conf, &mut journal)?,
+        Command::Generate => {
+            if !args.updater_data_only && !install_dir.exists() {
+                bail!("Prepared build not found, run \"prepare\" first (or use --updater-data-only)")
+            }
+            let mut manifest = generate(conf, args, &mut journal)?;
+            finalise_manifest(conf, &mut manifest)?;
+        }
+        Command::Package => {
+            if !install_dir.exists() {
+                bail!("Prepared build not found, run \"prepare\" first")
+            }
+            package(conf, args, &mut journal)?;
+        }
+        Command::Sign => {
+            let packager = Packaging::init(conf);
+            let manifest_file = packager.manifest_path();
+            if !manifest_file.exists() {
+                bail!("Manifest not found, run \"generate\" first")
+            }
+            let index_file = Some(packager.file_index_path()).filter(|f| f.exists());
+            sign_manifest(conf, &manifest_file, index_file.as_ref())?;
+            write_checksums(conf)?;
+        }
+        Command::Post => {
+            if !install_dir.exists() {
+                bail!("Prepared build not found, run \"prepare\" first")
+            }
+            info!("Copying install dir and PDBs to backup directory...");
+            post::copy_to_old(conf).context("Copying files failed")?;
+        }
+        // "verify-repro" does not use a config and is handled before loading it
+        Command::Explain { .. } | Command::VerifyRepro { .. } => unreachable!(),
+    }
+
+    info!("*** Finished! ***");
+    Ok(())
+}
+
+/// Run all stages, skipping the ones completed in a previous run if resuming
+pub fn run(conf: &Config, args: &MainArgs) -> Result<()> {
+    let mut journal = Journal::init(conf, args).context("Initialising journal failed")?;
+
+    if !args.updater_data_only {
+        prepare(conf, &mut journal)?;
+    } else {
+        journal.skip_stages(&[Stage::Copy, Stage::Codesign, Stage::Strip])?;
+        info!("Skipped preparation, this will also disable installer/zip creation.")
+    }
+
+    let mut manifest: Option<Manifest> = None;
+    if !args.packaging_only {
+        manifest = Some(generate(conf, args, &mut journal)?);
+    } else {
+        journal.skip_stages(&[Stage::Generate, Stage::Patches])?;
+    }
+
+    package(conf, args, &mut journal)?;
+
+    journal.run_stage(Stage::Manifest, || {
+        // Finalise and sign manifest if it was created
+        let Some(mut mf) = manifest else {
+            return Ok(());
+        };
+        let (manifest_file, index_file) = finalise_manifest(conf, &mut mf)?;
+        sign_manifest(conf, &manifest_file, index_file.as_ref())
+    })?;
+
+    journal.run_stage(Stage::Checksums, || write_checksums(conf))?;
+
+    journal.run_stage(Stage::Post, || {
+        if !args.updater_data_only && conf.post.copy_to_old {
+            info!("Copying install dir and PDBs to backup directory...");
+            post::copy_to_old(conf).context("Copying files failed")?;
+        }
+        Ok(())
+    })?;
+
+    if conf.post.report {
+        // The report is informational only, so failing to create it should not fail the run
+        if let Err(err) = write_report(conf, &journal) {
+            warn!("Creating report failed: {err:#}");
+        }
+    }
+
+    Ok(())
+}
+
+fn prepare(conf: &Config, journal: &mut Journal) -> Result<()> {
+    let prep = Preparator::init(conf);
+    prep.run(journal).context("Preparation failed")
+}
+
+/// Create deltas and manifest
+fn generate(conf: &Config, args: &MainArgs, journal: &mut Journal) -> Result<Manifest> {
+    info!("Creating manifest and patches...");
+    let generator = Generator::init(conf, !args.updater_data_only);
+    generator
+        .run(args.skip_patches, journal)
+        .context("Error during generator run")
+}
+
+/// Create NSIS installer, archives, and winget manifests
+fn package(conf: &Config, args: &MainArgs, journal: &mut Journal) -> Result<()> {
+    let packager = Packaging::init(conf);
+
+    journal.run_stage(Stage::Installer, || {
+        if !conf.package.installer.skip && !args.updater_data_only {
+            info!("Creating Installer");
+            packager.run_nsis().context("NSIS creation/signing failed")?;
+        } else {
+            info!("Skipping installer creation...")
+        }
+        Ok(())
+    })?;
+
+    journal.run_stage(Stage::Zips, || {
+        if conf.package.archives.iter().all(|a| a.skip) {
+            return Ok(());
+        }
+        if !args.updater_data_only {
+            // Create archives of the install folder, PDBs, or packages
+            info!("Creating archives...");
+            packager.create_archives().context("Creating archives failed")?;
+            info!("Archives created successfully!")
+        } else {
+            info!(" Skipping archive creation as preparation was skipped...")
+        }
+        Ok(())
+    })?;
+
+    journal.run_stage(Stage::Winget, || {
+        if !conf.package.winget.enabled {
+            return Ok(());
+        }
+        if conf.package.installer.skip || args.updater_data_only {
+            info!("Skipping winget manifests as no installer was created...");
+            return Ok(());
+        }
+        info!("Creating winget manifests...");
+        packager
+            .create_winget_manifests()
+            .context("Creating winget manifests failed")
+    })
+}
+
+/// Write manifest (and file index) to output directory, returns the paths of both files
+fn finalise_manifest(conf: &Config, manifest: &mut Manifest) -> Result<(PathBuf, Option<PathBuf>)> {
+    let packager = Packaging::init(conf);
+
+    info!("Finalising manifest...");
+    let manifest_file = packager
+        .finalise_manifest(manifest)
+        .context("Finalising manifest failed")?;
+
+    let index_file = if conf.package.updater.file_index {
+        info!("Creating updater file index...");
+        Some(packager.create_file_index().context("Creating file index failed")?)
+    } else {
+        None
+    };
+
+    Ok((manifest_file, index_file))
+}
+
+fn sign_manifest(conf: &Config, manifest_file: &PathBuf, index_file: Option<&PathBuf>) -> Result<()> {
+    if conf.package.updater.skip_sign {
+        return Ok(());
+    }
+
+    info!("Signing manifest...");
+    let mut signer = Signer::init(&conf.package.updater.private_keys);
+    for key_id in signer.key_ids().context("Loading keys failed")? {
+        info!(" - Using key: {key_id}");
+    }
+    signer.sign_file(manifest_file).context("Signing file failed")?;
+
+    if let Some(index_file) = index_file {
+        info!("Signing file index...");
+        signer.sign_file(index_file).context("Signing file failed")?;
+    }
+
+    Ok(())
+}
+
+/// Write checksums and release metadata for all files created so far
+fn write_checksums(conf: &Config) -> Result<()> {
+    if conf.package.checksums.skip {
+        return Ok(());
+    }
+
+    info!("Writing checksums and release metadata...");
+    write_release_files(conf).context("Writing checksums failed")
+}
+
+/// Determine what a run would do without writing to the output or previous builds directories
+pub fn dry_run(conf: &Config, args: &MainArgs) -> Result<()> {
+    info!("Dry run, no files will be written.");
+    let mut plan = Plan {
+        version: conf.obs_version.version_str.to_owned(),
+        branch: conf.general.branch.to_owned(),
+        previous_version: find_previous_version(conf).ok(),
+        ..Default::default()
+    };
+
+    let mut sources = None;
+    if !args.updater_data_only {
+        let (prepare_plan, prepared) = Preparator::init(conf).plan().context("Preparation planning failed")?;
+        plan.prepare = Some(prepare_plan);
+        sources = Some(prepared);
+    }
+
+    if !args.packaging_only {
+        let generator = Generator::init(conf, false);
+        plan.generate = Some(
+            generator
+                .plan(sources.as_ref(), args.skip_patches)
+                .context("Updater planning failed")?,
+        );
+    }
+
+    plan.package = Packaging::init(conf).plan(!args.updater_data_only, !args.packaging_only);
+    if !args.updater_data_only && conf.post.copy_to_old {
+        let (build_path, pdbs_path) = post::old_paths(conf);
+        plan.post_copy = vec![build_path, pdbs_path];
+    }
+
+    plan.print(args.verbose);
+    if let Some(plan_file) = &args.plan_file {
+        plan.to_file(plan_file).context("Writing plan failed")?;
+        info!("Plan written to \"{}\"", plan_file.display());
+    }
+
+    Ok(())
+}
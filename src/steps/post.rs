@@ -29,14 +29,22 @@ fn copy_directory(input: &PathBuf, output: &PathBuf) -> Result<()> {
     Ok(())
 }
 
-pub fn copy_to_old(conf: &Config) -> Result<()> {
+/// Directories in the previous builds folder the build and PDBs are copied to
+pub fn old_paths(conf: &Config) -> (PathBuf, PathBuf) {
     let version = get_filename_version(&conf.obs_version, false);
 
-    let build_out_path = conf.env.previous_dir.join("builds").join(&version);
+    (
+        conf.env.previous_dir.join("builds").join(&version),
+        conf.env.previous_dir.join("pdbs").join(&version),
+    )
+}
+
+pub fn copy_to_old(conf: &Config) -> Result<()> {
+    let (build_out_path, pdbs_out_path) = old_paths(conf);
+
     let install_path = conf.env.output_dir.join("install");
     copy_directory(&install_path, &build_out_path)?;
 
-    let pdbs_out_path = conf.env.previous_dir.join("pdbs").join(&version);
     let pdbs_path = conf.env.output_dir.join("pdbs");
     copy_directory(&pdbs_path, &pdbs_out_path)?;
 
@@ -4,14 +4,14 @@ use std::path::{Path, PathBuf};
 use std::process::Command;
 
 use anyhow::{bail, Result};
-use hashbrown::HashSet;
+use hashbrown::{HashMap, HashSet};
 use log::{debug, info, warn};
 use walkdir::{DirEntry, WalkDir};
 
-#[cfg(windows)]
-use crate::utils::codesign::sign;
-
-use crate::models::config::{Config, CopyOptions, ObsVersion};
+use crate::models::config::{Config, CopyOptions, LayoutOptions, ObsVersion};
+use crate::models::journal::{Journal, Stage};
+use crate::models::plan::PreparePlan;
+use crate::utils::codesign::{sign, verify};
 use crate::utils::hash::get_dir_code_hashes;
 use crate::utils::misc;
 use crate::utils::misc::parse_version;
@@ -70,7 +70,14 @@ impl<'a> Preparator<'a> {
             self.install_path.display()
         );
 
-        copy_files(copy_opts, &self.input_path, &self.install_path, false, &self.exclude)?;
+        copy_files(
+            copy_opts,
+            &self.config.prepare.layout,
+            &self.input_path,
+            &self.install_path,
+            false,
+            &self.exclude,
+        )?;
 
         // Copy override files over
         debug!("Copying override files...");
@@ -91,36 +98,7 @@ impl<'a> Preparator<'a> {
 
     /// Copy excluded files from previous build and PDB directories
     fn find_previous(&mut self) -> Result<()> {
-        let is_prerelease = self.config.obs_version.rc > 0
-            || self.config.obs_version.beta > 0
-            || !self.config.obs_version.commit.is_empty();
-
-        // Iterate over old builds to find the latest one
-        let mut ver_str = String::from("0.0.0");
-        let mut latest_ver: ObsVersion = parse_version(&ver_str)?;
-        for item in fs::read_dir(self.config.env.previous_dir.join("builds"))?.flatten() {
-            let meta = item.metadata()?;
-            if !meta.is_dir() {
-                continue;
-            }
-            let name = String::from(item.file_name().to_str().unwrap());
-            let ver = parse_version(&name)?;
-
-            // Do not pull files from pre-release builds unless we're doing a pre-release build
-            if !is_prerelease && (ver.beta > 0 || ver.rc > 0 || !ver.commit.is_empty()) {
-                continue;
-            }
-
-            if ver > latest_ver && ver < self.config.obs_version {
-                latest_ver = ver;
-                ver_str = name;
-            }
-        }
-
-        if latest_ver.version_major == 0 && latest_ver.version_minor == 0 && latest_ver.version_patch == 0 {
-            bail!("No valid previous version found!")
-        }
-
+        let ver_str = find_previous_version(self.config)?;
         let build_path: PathBuf = self.config.env.previous_dir.join("builds").join(&ver_str);
         let pdb_path: PathBuf = self.config.env.previous_dir.join("pdbs").join(&ver_str);
 
@@ -151,7 +129,14 @@ impl<'a> Preparator<'a> {
             prev_bin_path.display(),
             self.install_path.display()
         );
-        copy_files(copy_opts, prev_bin_path, &self.install_path, true, &self.exclude)?;
+        copy_files(
+            copy_opts,
+            &self.config.prepare.layout,
+            prev_bin_path,
+            &self.install_path,
+            true,
+            &self.exclude,
+        )?;
 
         // Copy unstripped PDBs
         info!(
@@ -159,14 +144,23 @@ impl<'a> Preparator<'a> {
             prev_pdb_path.display(),
             self.pdbs_path.display()
         );
-        copy_files(copy_opts, prev_pdb_path, &self.pdbs_path, true, &self.exclude)?;
+        copy_files(
+            copy_opts,
+            &self.config.prepare.layout,
+            prev_pdb_path,
+            &self.pdbs_path,
+            true,
+            &self.exclude,
+        )?;
 
         Ok(())
     }
 
-    /// Move PDBs (except excluded) to separate dir, then strip remaining ones
-    fn strip_pdbs(&self) -> Result<()> {
+    /// Move PDBs (except excluded) to separate dir, then strip remaining ones,
+    /// returns the number of stripped files
+    fn strip_pdbs(&self) -> Result<usize> {
         let opts = &self.config.prepare.strip_pdbs;
+        let mut stripped = 0;
 
         info!(
             "Copying/stripping PDBs from \"{}\" to \"{}\"...",
@@ -186,11 +180,16 @@ impl<'a> Preparator<'a> {
             }
             let relative_path_str = String::from(relative_path).replace('\\', "/");
             let new_path = self.pdbs_path.join(relative_path);
+            // The output directory is empty before stripping, so an existing file was moved there by
+            // an interrupted run that is being resumed (and the file in "install" is already stripped)
+            if new_path.exists() {
+                continue;
+            }
             if let Some(_parent) = new_path.parent() {
                 fs::create_dir_all(_parent)?;
             }
             // Simply copy files excluded from stripping
-            if opts.exclude.iter().any(|x| relative_path_str.contains(x)) {
+            if opts.exclude.matches(&relative_path_str) {
                 fs::copy(file.path(), &new_path)?;
                 continue;
             }
@@ -202,19 +201,21 @@ impl<'a> Preparator<'a> {
                 .args([new_path.as_os_str(), file.path().as_os_str(), OsStr::new("-p")])
                 .output()
                 .expect("failed to run pdbcopy");
+            stripped += 1;
         }
-        Ok(())
+        Ok(stripped)
     }
 
-    /// Sign all eligible files in a folder using Signtool
-    #[cfg(windows)]
-    fn codesign(&self) -> Result<()> {
+    /// Sign all eligible files in a folder using the configured backend, returns the number of signed files
+    fn codesign(&self) -> Result<usize> {
         if self.config.prepare.codesign.skip_sign {
-            return Ok(());
+            return Ok(0);
         }
 
         info!("Signing files in \"{}\"", self.install_path.display());
         let mut to_sign: Vec<PathBuf> = Vec::new();
+        // Relative paths of files to sign, used for additional codesigning filters
+        let mut to_sign_names: Vec<String> = Vec::new();
         let signable_exts = &self.config.prepare.codesign.sign_exts;
 
         for file in WalkDir::new(&self.install_path)
@@ -223,12 +224,29 @@ impl<'a> Preparator<'a> {
             .filter(|e| !e.file_type().is_dir())
         {
             let file: DirEntry = file;
-            let relative_path = file.path().to_str().unwrap();
+            let relative_path = file
+                .path()
+                .strip_prefix(&self.install_path)
+                .unwrap()
+                .to_str()
+                .unwrap()
+                .replace('\\', "/");
 
             if !signable_exts.iter().any(|x| relative_path.ends_with(x.as_str())) {
                 continue;
             }
-            to_sign.push(file.path().canonicalize()?)
+            // Reused files are replaced with the (already signed) ones from the previous build later
+            let decision = copy_decision(
+                &self.config.prepare.copy,
+                &self.config.prepare.layout,
+                &relative_path,
+                &self.exclude,
+            );
+            if decision == CopyDecision::Reused {
+                continue;
+            }
+            to_sign.push(file.path().canonicalize()?);
+            to_sign_names.push(relative_path);
         }
         sign(&to_sign, &self.config.prepare.codesign)?;
 
@@ -236,31 +254,63 @@ impl<'a> Preparator<'a> {
         if let Some(codesign_ex) = &self.config.prepare.codesign_extra {
             let to_sign_ex: Vec<PathBuf> = to_sign
                 .iter()
-                .filter(|p| codesign_ex.sign_filter.iter().any(|f| p.to_str().unwrap().contains(f)))
-                .cloned()
+                .zip(to_sign_names.iter())
+                .filter(|(_, name)| codesign_ex.sign_filter.matches(name))
+                .map(|(path, _)| path.clone())
                 .collect();
             sign(&to_sign_ex, codesign_ex)?;
         }
 
-        Ok(())
+        Ok(to_sign.len())
     }
 
-    #[cfg(unix)]
-    fn codesign(&self) -> Result<()> {
-        warn!("Codesigning is not (yet) supported on this platform.");
+    /// Check that all shipped binaries (including ones reused from the previous build) are signed
+    fn verify_signatures(&self) -> Result<()> {
+        let opts = &self.config.prepare.verify_signatures;
+        if opts.thumbprints.is_empty() {
+            return Ok(());
+        }
+
+        info!("Verifying signatures of files in \"{}\"", self.install_path.display());
+        let mut to_verify: Vec<PathBuf> = Vec::new();
+
+        for file in WalkDir::new(&self.install_path)
+            .into_iter()
+            .filter_map(Result::ok)
+            .filter(|e| !e.file_type().is_dir())
+        {
+            let file: DirEntry = file;
+            let relative_path = file.path().strip_prefix(&self.install_path).unwrap().to_str().unwrap();
+            let relative_path_str = String::from(relative_path).replace('\\', "/");
+
+            if !opts.exts.iter().any(|x| relative_path_str.ends_with(x.as_str()))
+                || opts.exclude.matches(&relative_path_str)
+            {
+                continue;
+            }
+            to_verify.push(file.path().to_path_buf())
+        }
+
+        verify(&to_verify, &opts.thumbprints)?;
+        info!("Verified signatures of {} files.", to_verify.len());
+
         Ok(())
     }
 
-    fn code_analysis(&mut self) -> Result<()> {
+    /// Compare code sections of binaries in the specified build directory with the previous build,
+    /// returns the prefixes of files that can be reused.
+    fn code_analysis(&self, build_path: &PathBuf) -> Result<HashSet<String>> {
+        let mut reused = HashSet::new();
         if self.prev_bin_path.is_none() {
-            return Ok(());
+            return Ok(reused);
         }
 
         // Hash code sections
         info!("Hashing new and old code sections...");
         let prev_build_path = self.prev_bin_path.as_ref().unwrap();
-        let in_hashes = get_dir_code_hashes(&self.install_path);
-        let old_hashes = get_dir_code_hashes(prev_build_path);
+        let layout = &self.config.prepare.layout;
+        let in_hashes = get_dir_code_hashes(build_path, layout);
+        let old_hashes = get_dir_code_hashes(prev_build_path, layout);
 
         for (path, file_info) in in_hashes {
             if !old_hashes.contains_key(&path) {
@@ -274,46 +324,198 @@ impl<'a> Preparator<'a> {
                 // version. The trailing "." is included to avoid potential conflicts with files
                 // that share the same prefix but.
                 let (base, _ext) = path.rsplit_once('.').unwrap();
-                self.exclude.insert(format!("{base}."));
+                reused.insert(format!("{base}."));
             }
         }
 
-        info!("Found {} files to exclude based on code sections.", self.exclude.len());
-        Ok(())
+        info!("Found {} files to exclude based on code sections.", reused.len());
+        Ok(reused)
+    }
+
+    /// Determine what preparation would do without writing anything.
+    /// Returns the plan and the files making up the prepared build (relative path => source file).
+    pub fn plan(mut self) -> Result<(PreparePlan, HashMap<String, PathBuf>)> {
+        if self.find_previous().is_err() {
+            warn!("No previous builds found.")
+        }
+
+        let prepare = &self.config.prepare;
+        let mut plan = PreparePlan::default();
+        let mut sources: HashMap<String, PathBuf> = HashMap::new();
+
+        // The new build is copied unmodified, so the code analysis can use the input directly
+        self.exclude = self.code_analysis(&self.input_path)?;
+
+        for name in files_to_copy(&prepare.copy, &prepare.layout, &self.input_path, false, &self.exclude) {
+            sources.insert(name.to_owned(), self.input_path.join(&name));
+            plan.copy.push(name);
+        }
+        for (ins_path, ovr_path) in prepare.copy.overrides.as_slice() {
+            if fs::metadata(ovr_path).is_err() {
+                bail!("Override file \"{}\" does not exist!", ovr_path)
+            }
+            sources.insert(ins_path.to_owned(), PathBuf::from(ovr_path));
+            plan.overrides.push(ins_path.to_owned());
+        }
+
+        // Signing and stripping happens before reused files are copied
+        let mut new_files: Vec<&String> = sources.keys().collect();
+        new_files.sort();
+        if !prepare.codesign.skip_sign {
+            plan.sign = new_files
+                .iter()
+                .filter(|f| prepare.codesign.sign_exts.iter().any(|x| f.ends_with(x.as_str())))
+                .map(|f| f.to_string())
+                .collect();
+            if let Some(codesign_ex) = &prepare.codesign_extra {
+                plan.sign_extra = plan
+                    .sign
+                    .iter()
+                    .filter(|f| codesign_ex.sign_filter.matches(f))
+                    .cloned()
+                    .collect();
+            }
+        }
+        plan.strip_pdbs = new_files
+            .iter()
+            .filter(|f| f.ends_with(".pdb") && !prepare.strip_pdbs.exclude.matches(f))
+            .map(|f| f.to_string())
+            .collect();
+
+        if let Some(prev_bin_path) = &self.prev_bin_path {
+            for name in files_to_copy(&prepare.copy, &prepare.layout, prev_bin_path, true, &self.exclude) {
+                sources.insert(name.to_owned(), prev_bin_path.join(&name));
+                plan.reuse.push(name);
+            }
+        }
+
+        plan.copy.sort();
+        plan.reuse.sort();
+
+        Ok((plan, sources))
     }
 
-    pub fn run(mut self) -> Result<()> {
+    pub fn run(mut self, journal: &mut Journal) -> Result<()> {
         if self.find_previous().is_err() {
             warn!("No previous builds found.")
         }
 
-        self.ensure_output_dir()?;
-        self.copy()?;
-        self.code_analysis()?;
-        self.codesign()?;
-        self.strip_pdbs()?;
-        self.copy_previous()?;
+        let reused: Vec<String> = journal.run_stage(Stage::Copy, || {
+            self.ensure_output_dir()?;
+            self.copy()?;
+            let mut reused: Vec<String> = self.code_analysis(&self.install_path)?.into_iter().collect();
+            reused.sort();
+            Ok(reused)
+        })?;
+        self.exclude = reused.into_iter().collect();
+
+        journal.run_stage(Stage::Codesign, || self.codesign())?;
+        journal.run_stage(Stage::Strip, || {
+            let stripped = self.strip_pdbs()?;
+            self.copy_previous()?;
+            self.verify_signatures()?;
+            Ok(stripped)
+        })?;
 
         Ok(())
     }
 }
 
-fn copy_files(
+/// Find the latest previous version (directory name) that files may be reused from
+pub fn find_previous_version(config: &Config) -> Result<String> {
+    let is_prerelease =
+        config.obs_version.rc > 0 || config.obs_version.beta > 0 || !config.obs_version.commit.is_empty();
+
+    // Iterate over old builds to find the latest one
+    let mut ver_str = String::from("0.0.0");
+    let mut latest_ver: ObsVersion = parse_version(&ver_str)?;
+    for item in fs::read_dir(config.env.previous_dir.join("builds"))?.flatten() {
+        let meta = item.metadata()?;
+        if !meta.is_dir() {
+            continue;
+        }
+        let name = String::from(item.file_name().to_str().unwrap());
+        let ver = parse_version(&name)?;
+
+        // Do not pull files from pre-release builds unless we're doing a pre-release build
+        if !is_prerelease && (ver.beta > 0 || ver.rc > 0 || !ver.commit.is_empty()) {
+            continue;
+        }
+
+        if ver > latest_ver && ver < config.obs_version {
+            latest_ver = ver;
+            ver_str = name;
+        }
+    }
+
+    if latest_ver.version_major == 0 && latest_ver.version_minor == 0 && latest_ver.version_patch == 0 {
+        bail!("No valid previous version found!")
+    }
+
+    Ok(ver_str)
+}
+
+/// Outcome of the copy filters for a file of the new build
+#[derive(Debug, PartialEq, Eq)]
+pub enum CopyDecision {
+    /// Not part of the configured build layout
+    OutsideLayout,
+    /// Matches the specified "never_copy" pattern
+    NeverCopy(String),
+    /// Replaced by the specified override file
+    Override(String),
+    /// Binary with identical code, copied from the previous build instead
+    Reused,
+    /// Matches the specified "always_copy" pattern
+    AlwaysCopy(String),
+    Copy,
+}
+
+/// Decide how a (Unix-style relative) path is handled when copying the new build,
+/// `reused` contains the prefixes of files that are copied from the previous build.
+pub fn copy_decision(
     opts: &CopyOptions,
+    layout: &LayoutOptions,
+    relative_path: &str,
+    reused: &HashSet<String>,
+) -> CopyDecision {
+    if !layout.includes(relative_path) {
+        return CopyDecision::OutsideLayout;
+    }
+    if let Some(pattern) = opts.never_copy.find_match(relative_path) {
+        return CopyDecision::NeverCopy(pattern.to_string());
+    }
+    // Overrides are also excludes
+    if let Some((_, ovr_path)) = opts
+        .overrides
+        .iter()
+        .find(|(obs_path, _)| relative_path.contains(obs_path.as_str()))
+    {
+        return CopyDecision::Override(ovr_path.to_owned());
+    }
+
+    // Include/Exclude filters only apply to binaries except ones that are always copied
+    if let Some(pattern) = opts.always_copy.find_match(relative_path) {
+        return CopyDecision::AlwaysCopy(pattern.to_string());
+    }
+    let is_binary = BINARY_EXTS.iter().any(|e| relative_path.ends_with(e));
+    if is_binary && reused.iter().any(|f| relative_path.starts_with(f)) {
+        return CopyDecision::Reused;
+    }
+
+    CopyDecision::Copy
+}
+
+/// List files (relative paths) of a build that are copied to the output directory,
+/// when copying the old build only reused files are included, otherwise everything else.
+fn files_to_copy(
+    opts: &CopyOptions,
+    layout: &LayoutOptions,
     input: &PathBuf,
-    output: &Path,
     copying_old: bool,
     filter: &HashSet<String>,
-) -> Result<()> {
-    // Non-negotiable excludes
-    let mut always_exclude: HashSet<&String> = HashSet::new();
-    always_exclude.extend(opts.never_copy.iter());
-    // Overrides are also excludes
-    opts.overrides.iter().for_each(|(obs_path, _)| {
-        always_exclude.insert(obs_path);
-    });
-
-    fs::create_dir_all(output)?;
+) -> Vec<String> {
+    let mut files = Vec::new();
 
     for file in WalkDir::new(input)
         .into_iter()
@@ -326,41 +528,36 @@ fn copy_files(
         let relative_path = file.path().strip_prefix(input).unwrap();
         let relative_path_str = String::from(relative_path.to_str().unwrap()).replace('\\', "/");
 
-        // ToDo figure out if this should be configurable
-        if !relative_path.starts_with("bin")
-            && !relative_path.starts_with("data")
-            && !relative_path.starts_with("obs-plugins")
-        {
-            continue;
+        let copy = match copy_decision(opts, layout, &relative_path_str, filter) {
+            CopyDecision::Reused => copying_old,
+            CopyDecision::Copy | CopyDecision::AlwaysCopy(_) => !copying_old,
+            _ => false,
+        };
+        if copy {
+            files.push(relative_path_str);
         }
+    }
 
-        if always_exclude.iter().any(|f| relative_path_str.contains(*f)) {
-            continue;
-        }
+    files
+}
 
-        let is_binary = BINARY_EXTS.iter().any(|e| relative_path_str.ends_with(e));
-        let always_copied = opts.always_copy.iter().any(|e| relative_path_str.contains(e));
-        // Include/Exclude filters only apply to binaries except ones that are always copied
-        if is_binary && !always_copied {
-            // Exclude filtered files when copying new build
-            if !copying_old && !filter.is_empty() && filter.iter().any(|f| relative_path_str.starts_with(f)) {
-                continue;
-            }
-            // Include filtered files when copying old build
-            if copying_old && !filter.iter().any(|f| relative_path_str.starts_with(f)) {
-                continue;
-            }
-        } else if copying_old {
-            // Do not copy old files for anything that doesn't pass the filters
-            continue;
-        }
+fn copy_files(
+    opts: &CopyOptions,
+    layout: &LayoutOptions,
+    input: &PathBuf,
+    output: &Path,
+    copying_old: bool,
+    filter: &HashSet<String>,
+) -> Result<()> {
+    fs::create_dir_all(output)?;
 
-        let file_path = output.join(relative_path);
+    for relative_path in files_to_copy(opts, layout, input, copying_old, filter) {
+        let file_path = output.join(&relative_path);
         // Ensure dir structure exists
         if let Some(_parent) = file_path.parent() {
             fs::create_dir_all(_parent)?;
         }
-        fs::copy(file.path(), file_path)?;
+        fs::copy(input.join(&relative_path), file_path)?;
     }
 
     Ok(())
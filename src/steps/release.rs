@@ -0,0 +1,176 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use log::info;
+use rayon::prelude::*;
+use walkdir::WalkDir;
+
+use crate::models::config::Config;
+use crate::models::release::{Artefact, ArtefactKind, Release};
+use crate::steps::package::Packaging;
+use crate::utils::hash::{hash_file, hash_file_sha256};
+use crate::utils::misc;
+use crate::utils::sign::{signature_path, Signer};
+
+pub const CHECKSUMS_FILE: &str = "SHA256SUMS";
+pub const RELEASE_FILE: &str = "release.json";
+
+/// Files in the output directory that are uploaded, with their kind
+fn find_artefacts(conf: &Config) -> Vec<(ArtefactKind, PathBuf)> {
+    let output_dir = &conf.env.output_dir;
+    let packager = Packaging::init(conf);
+    let plan = packager.plan(true, true);
+
+    let mut files = vec![(ArtefactKind::Installer, packager.installer_path())];
+    files.extend(plan.zips.into_iter().map(|p| (ArtefactKind::Archive, p)));
+    for (kind, path) in [
+        (ArtefactKind::Manifest, packager.manifest_path()),
+        (ArtefactKind::FileIndex, packager.file_index_path()),
+    ] {
+        files.push((ArtefactKind::Signature, signature_path(&path, "sig")));
+        files.push((ArtefactKind::Signature, signature_path(&path, "sigs")));
+        files.push((kind, path));
+    }
+
+    let mut updater_files: Vec<PathBuf> = WalkDir::new(output_dir.join("updater"))
+        .into_iter()
+        .filter_map(Result::ok)
+        .filter(|e| !e.file_type().is_dir())
+        .map(|e| e.into_path())
+        .collect();
+    updater_files.sort();
+    files.extend(updater_files.into_iter().map(|p| (ArtefactKind::UpdaterFile, p)));
+
+    files.retain(|(_, path)| path.is_file());
+    // Group by kind, keeping the order within each kind
+    files.sort_by_key(|(kind, _)| *kind);
+    files
+}
+
+fn hash_artefacts(output_dir: &Path, files: &[(ArtefactKind, PathBuf)]) -> Result<Vec<Artefact>> {
+    files
+        .par_iter()
+        .map(|(kind, path)| {
+            let info = hash_file(path);
+            Ok(Artefact {
+                name: path.strip_prefix(output_dir)?.to_string_lossy().replace('\\', "/"),
+                kind: *kind,
+                size: info.size,
+                sha256: hash_file_sha256(path)?,
+                blake2: info.hash,
+            })
+        })
+        .collect()
+}
+
+/// Write "SHA256SUMS" (signed with the updater keys if enabled) and "release.json" listing all artefacts
+pub fn write_release_files(conf: &Config) -> Result<()> {
+    let output_dir = &conf.env.output_dir;
+    let version = &conf.obs_version;
+    let mut release = Release {
+        version: version.version_str.to_owned(),
+        tag_version: misc::get_filename_version(version, false),
+        short_version: misc::get_filename_version(version, true),
+        major: version.version_major,
+        minor: version.version_minor,
+        patch: version.version_patch,
+        beta: version.beta,
+        rc: version.rc,
+        commit: version.commit.to_owned(),
+        branch: conf.general.branch.to_owned(),
+        artefacts: Vec::new(),
+    };
+
+    let files = find_artefacts(conf);
+    info!(" => Hashing {} files...", files.len());
+    release.artefacts = hash_artefacts(output_dir, &files)?;
+
+    let checksums_path = output_dir.join(CHECKSUMS_FILE);
+    fs::write(&checksums_path, release.sha256sums()).context(format!("Failed writing {checksums_path:?}"))?;
+    let mut checksum_files = vec![(ArtefactKind::Checksums, checksums_path.to_owned())];
+
+    if conf.package.checksums.sign && !conf.package.updater.skip_sign {
+        info!("Signing checksums...");
+        let mut signer = Signer::init(&conf.package.updater.private_keys);
+        signer.sign_file(&checksums_path).context("Signing file failed")?;
+        checksum_files.push((ArtefactKind::Signature, signature_path(&checksums_path, "sig")));
+        checksum_files.push((ArtefactKind::Signature, signature_path(&checksums_path, "sigs")));
+        checksum_files.retain(|(_, path)| path.is_file());
+    }
+
+    // The checksum files can be listed in the release metadata, but not in themselves
+    release.artefacts.extend(hash_artefacts(output_dir, &checksum_files)?);
+    release.to_file(&output_dir.join(RELEASE_FILE), true)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod release_tests {
+    use super::*;
+    use crate::models::config::ArchiveOptions;
+    use crate::utils::misc::TestDir;
+
+    #[test]
+    fn test_release_files() {
+        let dir = TestDir::new("release");
+        let mut conf = Config::default();
+        conf.env.output_dir = dir.to_owned();
+        conf.set_version(&"31.0.0".to_string(), 0, 0).unwrap();
+        conf.package.checksums.sign = false;
+        conf.package.archives.push(ArchiveOptions {
+            name: "OBS-Studio-Portable.zip".to_string(),
+            ..Default::default()
+        });
+
+        let installer = Packaging::init(&conf).installer_path();
+        let installer_name = installer.file_name().unwrap().to_string_lossy().to_string();
+        for file in [
+            "updater/update_studio/stable/core/obs.dll.zst",
+            "manifest.json.sig",
+            "manifest.json",
+            "OBS-Studio-Portable.zip",
+            &installer_name,
+            // Not an artefact
+            "added.txt",
+        ] {
+            let path = dir.join(file);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, "abc").unwrap();
+        }
+
+        write_release_files(&conf).unwrap();
+
+        // Grouped by kind, the checksums file does not list itself
+        let sha256 = "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad";
+        let expected: String = [
+            installer_name.as_str(),
+            "OBS-Studio-Portable.zip",
+            "manifest.json",
+            "manifest.json.sig",
+            "updater/update_studio/stable/core/obs.dll.zst",
+        ]
+        .iter()
+        .map(|name| format!("{sha256}  {name}\n"))
+        .collect();
+        assert_eq!(fs::read_to_string(dir.join(CHECKSUMS_FILE)).unwrap(), expected);
+
+        // The release metadata also lists the checksums file
+        let release: Release = serde_json::from_slice(&fs::read(dir.join(RELEASE_FILE)).unwrap()).unwrap();
+        let kinds: Vec<ArtefactKind> = release.artefacts.iter().map(|a| a.kind).collect();
+        assert_eq!(
+            kinds,
+            [
+                ArtefactKind::Installer,
+                ArtefactKind::Archive,
+                ArtefactKind::Manifest,
+                ArtefactKind::Signature,
+                ArtefactKind::UpdaterFile,
+                ArtefactKind::Checksums,
+            ]
+        );
+        assert_eq!(release.artefacts.last().unwrap().name, CHECKSUMS_FILE);
+        assert_eq!(release.version, "31.0.0");
+    }
+}
@@ -0,0 +1,231 @@
+use std::fs;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use hashbrown::HashMap;
+use log::info;
+use walkdir::WalkDir;
+
+use crate::models::config::Config;
+use crate::models::journal::{Journal, Stage};
+use crate::models::manifest::Manifest;
+use crate::models::report::{Changes, DownloadSize, FileReport, PackageReport, PatchReport, PatchSavings, Report};
+use crate::steps::generate::DOWNLOAD_SIZES_FILE;
+use crate::steps::package::Packaging;
+
+/// Number of entries in the lists of largest files and patches
+const TOP_ENTRIES: usize = 10;
+
+/// Build report from the files in the output directory and the stages recorded in the journal
+pub fn create_report(conf: &Config, journal: &Journal) -> Result<Report> {
+    let output_dir = &conf.env.output_dir;
+    let branch = &conf.general.branch;
+    let mut report = Report {
+        version: conf.obs_version.version_str.to_owned(),
+        branch: branch.to_owned(),
+        changes: read_changes(output_dir),
+        signed: journal.stage_data(Stage::Codesign),
+        pdbs_stripped: journal.stage_data(Stage::Strip),
+        stages: journal.stage_times(),
+        download_sizes: read_download_sizes(output_dir),
+        ..Default::default()
+    };
+
+    let manifest_file = Packaging::init(conf).manifest_path();
+    if !manifest_file.exists() {
+        return Ok(report);
+    }
+    let manifest: Manifest = serde_json::from_reader(BufReader::new(File::open(&manifest_file)?))
+        .context(format!("Failed reading manifest {manifest_file:?}"))?;
+
+    let updater_dir = output_dir.join("updater");
+    let patches = find_patches(&updater_dir.join("patches_studio").join(branch))?;
+    let compressed_size = |package: &str, name: &str| {
+        let mut filename = format!("update_studio/{branch}/{package}/{name}");
+        if conf.generate.compress_files {
+            filename += ".zst";
+        }
+        fs::metadata(updater_dir.join(filename))
+            .map(|m| m.len())
+            .unwrap_or_default()
+    };
+
+    let mut files: Vec<FileReport> = Vec::new();
+    for package in &manifest.packages {
+        let package_files: Vec<FileReport> = package
+            .files
+            .iter()
+            .map(|f| FileReport {
+                name: f.name.to_owned(),
+                package: package.name.to_owned(),
+                size: f.size,
+                compressed_size: compressed_size(&package.name, &f.name),
+            })
+            .collect();
+        let package_patches = patches.iter().filter(|p| p.package == package.name);
+
+        report.packages.push(PackageReport {
+            name: package.name.to_owned(),
+            files: package_files.len(),
+            removed_files: package.removed_files.len(),
+            size: package_files.iter().map(|f| f.size).sum(),
+            compressed_size: package_files.iter().map(|f| f.compressed_size).sum(),
+            patches: package_patches.clone().count(),
+            patch_size: package_patches.map(|p| p.size).sum(),
+        });
+        files.extend(package_files);
+    }
+
+    let mut patches: Vec<PatchReport> = patches
+        .into_iter()
+        .map(|mut p| {
+            p.compressed_size = compressed_size(&p.package, &p.name);
+            p
+        })
+        .collect();
+
+    // Group patches by file to compare their size to the full download
+    let mut savings: HashMap<(&str, &str), PatchSavings> = HashMap::new();
+    for patch in &patches {
+        let entry = savings
+            .entry((&patch.package, &patch.name))
+            .or_insert_with(|| PatchSavings {
+                name: patch.name.to_owned(),
+                package: patch.package.to_owned(),
+                compressed_size: patch.compressed_size,
+                ..Default::default()
+            });
+        entry.patches += 1;
+        // Sum of patch sizes for now, averaged below
+        entry.average_patch_size += patch.size;
+    }
+    report.patch_savings = savings
+        .into_values()
+        .map(|mut s| {
+            s.average_patch_size /= s.patches as u64;
+            s
+        })
+        .collect();
+    report.patch_savings.sort_by(|a, b| a.name.cmp(&b.name));
+
+    files.sort_by(|a, b| b.size.cmp(&a.size).then(a.name.cmp(&b.name)));
+    files.truncate(TOP_ENTRIES);
+    report.largest_files = files;
+    patches.sort_by(|a, b| b.size.cmp(&a.size).then(a.name.cmp(&b.name)));
+    patches.truncate(TOP_ENTRIES);
+    report.largest_patches = patches;
+
+    Ok(report)
+}
+
+/// Count entries of the file lists written by the generator
+fn read_changes(output_dir: &Path) -> Option<Changes> {
+    let count = |name: &str| -> Option<usize> {
+        let data = fs::read_to_string(output_dir.join(name)).ok()?;
+        Some(data.lines().filter(|l| !l.is_empty()).count())
+    };
+
+    Some(Changes {
+        added: count("added.txt")?,
+        changed: count("changed.txt")?,
+        unchanged: count("unchanged.txt")?,
+        removed: count("removed.txt")?,
+    })
+}
+
+/// Load download sizes per previous version written by the generator
+fn read_download_sizes(output_dir: &Path) -> Vec<DownloadSize> {
+    File::open(output_dir.join(DOWNLOAD_SIZES_FILE))
+        .ok()
+        .and_then(|f| serde_json::from_reader(BufReader::new(f)).ok())
+        .unwrap_or_default()
+}
+
+/// List patches in the branch's patch directory ("<package>/<file name>/<old hash>")
+fn find_patches(path: &Path) -> Result<Vec<PatchReport>> {
+    let mut patches = Vec::new();
+    if !path.exists() {
+        return Ok(patches);
+    }
+
+    for entry in WalkDir::new(path)
+        .min_depth(3)
+        .into_iter()
+        .filter_map(Result::ok)
+        .filter(|e| !e.file_type().is_dir())
+    {
+        let relative_path = entry.path().strip_prefix(path)?.to_string_lossy().replace('\\', "/");
+        let Some((package, rest)) = relative_path.split_once('/') else {
+            continue;
+        };
+        let Some((name, old_hash)) = rest.rsplit_once('/') else {
+            continue;
+        };
+
+        patches.push(PatchReport {
+            name: name.to_owned(),
+            package: package.to_owned(),
+            old_hash: old_hash.to_owned(),
+            size: entry.metadata()?.len(),
+            compressed_size: 0,
+        });
+    }
+
+    Ok(patches)
+}
+
+/// Create report and write it to the output directory
+pub fn write_report(conf: &Config, journal: &Journal) -> Result<()> {
+    let report = create_report(conf, journal)?;
+    report.to_files(&conf.env.output_dir)?;
+    info!(
+        "Report written to \"{}\"",
+        conf.env.output_dir.join("report.md").display()
+    );
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod report_tests {
+    use super::*;
+    use crate::utils::misc::TestDir;
+
+    #[test]
+    fn test_find_patches() {
+        let dir = TestDir::new("report");
+        assert!(find_patches(&dir).unwrap().is_empty());
+
+        for (path, size) in [
+            ("core/obs64.exe/abcd", 10),
+            ("core/obs-plugins/64bit/obs-x264.dll/ef01", 20),
+            // Not deep enough to be a patch
+            ("core/stray.txt", 30),
+        ] {
+            let path = dir.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, vec![0u8; size]).unwrap();
+        }
+
+        let mut patches: Vec<(String, String, String, u64)> = find_patches(&dir)
+            .unwrap()
+            .into_iter()
+            .map(|p| (p.package, p.name, p.old_hash, p.size))
+            .collect();
+        patches.sort();
+        assert_eq!(
+            patches,
+            [
+                (
+                    "core".to_string(),
+                    "obs-plugins/64bit/obs-x264.dll".to_string(),
+                    "ef01".to_string(),
+                    20
+                ),
+                ("core".to_string(), "obs64.exe".to_string(), "abcd".to_string(), 10),
+            ]
+        );
+    }
+}
@@ -0,0 +1,159 @@
+use std::fs;
+use std::path::Path;
+
+use anyhow::{bail, Result};
+use hashbrown::HashSet;
+use log::{info, warn};
+use walkdir::WalkDir;
+
+use crate::models::journal::Stage;
+use crate::steps::release::{CHECKSUMS_FILE, RELEASE_FILE};
+use crate::utils::misc::first_difference;
+
+/// Files describing a run rather than its results (e.g. timings), these are expected to differ
+const RUN_METADATA: [&str; 4] = ["journal.json", "report.md", "report.html", "report.json"];
+
+/// List files in an output directory as relative paths, excluding run metadata
+fn list_files(dir: &Path) -> HashSet<String> {
+    WalkDir::new(dir)
+        .into_iter()
+        .filter_map(Result::ok)
+        .filter(|e| !e.file_type().is_dir())
+        .map(|e| e.path().strip_prefix(dir).unwrap().to_string_lossy().replace('\\', "/"))
+        .filter(|f| !RUN_METADATA.contains(&f.as_str()))
+        .collect()
+}
+
+/// Stage that writes a file in the output directory
+fn producing_stage(name: &str) -> Option<Stage> {
+    let ext = name.rsplit_once('.').map(|(_, ext)| ext).unwrap_or_default();
+
+    if let Some(file) = name.strip_prefix("install/") {
+        if file.ends_with(".pdb") {
+            return Some(Stage::Strip);
+        }
+        // Binaries are modified by codesigning
+        return match ext {
+            "exe" | "dll" | "pyd" => Some(Stage::Codesign),
+            _ => Some(Stage::Copy),
+        };
+    }
+
+    if name.starts_with("pdbs/") {
+        Some(Stage::Strip)
+    } else if name.starts_with("updater/update_studio/") {
+        Some(Stage::Generate)
+    } else if name.starts_with("winget/") {
+        Some(Stage::Winget)
+    } else if name.starts_with("updater/patches_studio/") || name.starts_with("updater/plans/") {
+        Some(Stage::Patches)
+    } else if name.contains('/') {
+        None
+    } else if ["added.txt", "changed.txt", "unchanged.txt", "removed.txt"].contains(&name) {
+        Some(Stage::Generate)
+    } else if name.starts_with(CHECKSUMS_FILE) || name == RELEASE_FILE {
+        Some(Stage::Checksums)
+    } else if name == "download_sizes.json" {
+        Some(Stage::Patches)
+    } else if name.starts_with("manifest") || name.starts_with("files") || name.starts_with("notes.") {
+        Some(Stage::Manifest)
+    } else if ext == "exe" {
+        Some(Stage::Installer)
+    } else if ext == "zip" || name.ends_with(".tar.zst") || name.ends_with(".tar.xz") {
+        Some(Stage::Zips)
+    } else {
+        None
+    }
+}
+
+/// Compare two output directories and report every file that is missing or differs
+pub fn verify_repro(dir_a: &Path, dir_b: &Path) -> Result<()> {
+    for dir in [dir_a, dir_b] {
+        if !dir.is_dir() {
+            bail!("Output directory {dir:?} does not exist");
+        }
+    }
+
+    info!(
+        "Comparing output directories \"{}\" and \"{}\"...",
+        dir_a.display(),
+        dir_b.display()
+    );
+    let files_a = list_files(dir_a);
+    let files_b = list_files(dir_b);
+
+    let mut all_files: Vec<&String> = files_a.union(&files_b).collect();
+    all_files.sort();
+
+    let mut differences = 0;
+    for name in all_files {
+        let stage = match producing_stage(name) {
+            Some(stage) => format!("{stage:?}"),
+            None => "unknown".to_string(),
+        };
+
+        if !files_a.contains(name) || !files_b.contains(name) {
+            let missing_in = if files_a.contains(name) { dir_b } else { dir_a };
+            warn!(" - {name}: missing in \"{}\" (stage: {stage})", missing_in.display());
+            differences += 1;
+            continue;
+        }
+
+        let (path_a, path_b) = (dir_a.join(name), dir_b.join(name));
+        if let Some(offset) = first_difference(&path_a, &path_b)? {
+            let (size_a, size_b) = (fs::metadata(&path_a)?.len(), fs::metadata(&path_b)?.len());
+            warn!(" - {name}: differs at offset {offset} (sizes: {size_a}/{size_b}, stage: {stage})");
+            differences += 1;
+        }
+    }
+
+    if differences > 0 {
+        bail!("{differences} file(s) differ between the output directories");
+    }
+    info!("Output directories are identical.");
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod verify_tests {
+    use super::*;
+    use crate::utils::misc::TestDir;
+
+    #[test]
+    fn test_producing_stage() {
+        assert_eq!(producing_stage("install/bin/64bit/obs64.exe"), Some(Stage::Codesign));
+        assert_eq!(producing_stage("install/bin/64bit/obs64.pdb"), Some(Stage::Strip));
+        assert_eq!(producing_stage("install/data/locale.ini"), Some(Stage::Copy));
+        assert_eq!(
+            producing_stage("updater/plans/stable/30.0.0.json"),
+            Some(Stage::Patches)
+        );
+        assert_eq!(producing_stage("OBS-Studio-31.0.zip"), Some(Stage::Zips));
+        assert_eq!(producing_stage("manifest_stable.json"), Some(Stage::Manifest));
+        assert_eq!(producing_stage("other/file.txt"), None);
+    }
+
+    #[test]
+    fn test_verify_repro() {
+        let dir = TestDir::new("verify");
+        let (dir_a, dir_b) = (dir.join("a"), dir.join("b"));
+        for out in [&dir_a, &dir_b] {
+            fs::create_dir_all(out.join("install")).unwrap();
+            fs::write(out.join("install/obs64.exe"), "binary").unwrap();
+            fs::write(out.join("manifest_stable.json"), "{}").unwrap();
+        }
+        // Run metadata is ignored
+        fs::write(dir_a.join("journal.json"), "a").unwrap();
+        fs::write(dir_b.join("journal.json"), "b").unwrap();
+        assert!(verify_repro(&dir_a, &dir_b).is_ok());
+
+        // One file differs and one is missing
+        fs::write(dir_b.join("install/obs64.exe"), "binarY").unwrap();
+        fs::write(dir_a.join("OBS-Studio-31.0.zip"), "zip").unwrap();
+        let err = verify_repro(&dir_a, &dir_b).unwrap_err();
+        assert_eq!(err.to_string(), "2 file(s) differ between the output directories");
+
+        assert!(verify_repro(&dir_a, &dir.join("c")).is_err());
+    }
+}
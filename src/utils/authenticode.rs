@@ -0,0 +1,1091 @@
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::{anyhow, bail, Context, Result};
+use log::debug;
+use object::pe::IMAGE_DIRECTORY_ENTRY_SECURITY;
+use object::read::pe::{PeFile32, PeFile64};
+use object::FileKind;
+use rsa::pkcs8::{DecodePrivateKey, DecodePublicKey, EncodePublicKey};
+use rsa::sha2::{Digest, Sha256, Sha384, Sha512};
+use rsa::{pkcs1::DecodeRsaPrivateKey, Pkcs1v15Sign, RsaPrivateKey, RsaPublicKey};
+use sha1::Sha1;
+
+use crate::models::config::CodesignOptions;
+use crate::utils::der;
+use crate::utils::der::{Tlv, TAG_OCTET_STRING, TAG_SEQUENCE, TAG_SET};
+use crate::utils::http;
+
+const OID_PKCS7_SIGNED_DATA: &str = "1.2.840.113549.1.7.2";
+const OID_RSA_ENCRYPTION: &str = "1.2.840.113549.1.1.1";
+const OID_SHA256_WITH_RSA: &str = "1.2.840.113549.1.1.11";
+const OID_SHA384_WITH_RSA: &str = "1.2.840.113549.1.1.12";
+const OID_SHA512_WITH_RSA: &str = "1.2.840.113549.1.1.13";
+const OID_CONTENT_TYPE: &str = "1.2.840.113549.1.9.3";
+const OID_MESSAGE_DIGEST: &str = "1.2.840.113549.1.9.4";
+const OID_SPC_INDIRECT_DATA: &str = "1.3.6.1.4.1.311.2.1.4";
+const OID_SPC_STATEMENT_TYPE: &str = "1.3.6.1.4.1.311.2.1.11";
+const OID_SPC_SP_OPUS_INFO: &str = "1.3.6.1.4.1.311.2.1.12";
+const OID_SPC_PE_IMAGE_DATA: &str = "1.3.6.1.4.1.311.2.1.15";
+const OID_SPC_INDIVIDUAL_SP_KEY_PURPOSE: &str = "1.3.6.1.4.1.311.2.1.21";
+const OID_SPC_NESTED_SIGNATURE: &str = "1.3.6.1.4.1.311.2.4.1";
+const OID_SPC_RFC3161_TIMESTAMP: &str = "1.3.6.1.4.1.311.3.3.1";
+
+const WIN_CERT_REVISION_2_0: u16 = 0x0200;
+const WIN_CERT_TYPE_PKCS_SIGNED_DATA: u16 = 0x0002;
+// "<<<Obsolete>>>" as UTF-16BE, this is what signtool puts in SpcPeImageData
+const SPC_OBSOLETE: [u8; 28] = [
+    0, 0x3c, 0, 0x3c, 0, 0x3c, 0, 0x4f, 0, 0x62, 0, 0x73, 0, 0x6f, 0, 0x6c, 0, 0x65, 0, 0x74, 0, 0x65, 0, 0x3e, 0,
+    0x3e, 0, 0x3e,
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DigestAlgorithm {
+    Sha256,
+    Sha384,
+    Sha512,
+}
+
+/// Provides the certificate chain and signature for Authenticode signing,
+/// this allows plugging in signers other than a local key file.
+pub trait AuthenticodeSigner: Sync {
+    /// DER-encoded certificates, the first one is the signing certificate
+    fn certificates(&self) -> &[Vec<u8>];
+    /// Create PKCS#1 v1.5 signature over the specified digest
+    fn sign_digest(&self, alg: DigestAlgorithm, digest: &[u8]) -> Result<Vec<u8>>;
+}
+
+/// Signer using a PEM certificate (chain) and RSA private key file
+pub struct KeyFileSigner {
+    certificates: Vec<Vec<u8>>,
+    key: RsaPrivateKey,
+}
+
+/// Signer running an external program (e.g. a KMS or HSM client) that signs the digest,
+/// the digest is written to its standard input and the raw signature read from its standard output
+pub struct CommandSigner {
+    certificates: Vec<Vec<u8>>,
+    command: Vec<String>,
+}
+
+/// Relevant offsets in the PE headers
+pub struct PeLayout {
+    checksum_offset: usize,
+    security_dir_offset: usize,
+}
+
+/// Fields of a X.509 certificate required for signing/verification
+pub struct CertificateInfo<'a> {
+    pub serial: Tlv<'a>,
+    pub issuer: Tlv<'a>,
+    pub public_key: Tlv<'a>,
+}
+
+impl FromStr for DigestAlgorithm {
+    type Err = anyhow::Error;
+
+    fn from_str(input: &str) -> Result<Self> {
+        match input.to_lowercase().replace('-', "").as_str() {
+            "sha256" => Ok(Self::Sha256),
+            "sha384" => Ok(Self::Sha384),
+            "sha512" => Ok(Self::Sha512),
+            _ => Err(anyhow!("Unsupported digest algorithm \"{}\"", input)),
+        }
+    }
+}
+
+impl DigestAlgorithm {
+    pub fn oid(&self) -> &'static str {
+        match self {
+            Self::Sha256 => "2.16.840.1.101.3.4.2.1",
+            Self::Sha384 => "2.16.840.1.101.3.4.2.2",
+            Self::Sha512 => "2.16.840.1.101.3.4.2.3",
+        }
+    }
+
+    pub fn from_oid(oid: &str) -> Result<Self> {
+        match oid {
+            "2.16.840.1.101.3.4.2.1" => Ok(Self::Sha256),
+            "2.16.840.1.101.3.4.2.2" => Ok(Self::Sha384),
+            "2.16.840.1.101.3.4.2.3" => Ok(Self::Sha512),
+            _ => bail!("Unsupported digest algorithm OID {}", oid),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Sha256 => "sha256",
+            Self::Sha384 => "sha384",
+            Self::Sha512 => "sha512",
+        }
+    }
+
+    pub fn digest(&self, parts: &[&[u8]]) -> Vec<u8> {
+        fn run<D: Digest>(parts: &[&[u8]]) -> Vec<u8> {
+            let mut hasher = D::new();
+            parts.iter().for_each(|p| hasher.update(p));
+            hasher.finalize().to_vec()
+        }
+
+        match self {
+            Self::Sha256 => run::<Sha256>(parts),
+            Self::Sha384 => run::<Sha384>(parts),
+            Self::Sha512 => run::<Sha512>(parts),
+        }
+    }
+
+    fn padding(&self) -> Pkcs1v15Sign {
+        match self {
+            Self::Sha256 => Pkcs1v15Sign::new::<Sha256>(),
+            Self::Sha384 => Pkcs1v15Sign::new::<Sha384>(),
+            Self::Sha512 => Pkcs1v15Sign::new::<Sha512>(),
+        }
+    }
+}
+
+impl KeyFileSigner {
+    pub fn from_files(cert_file: &Path, key_file: &Path) -> Result<Self> {
+        let cert_pem = fs::read_to_string(cert_file).with_context(|| format!("Reading {}", cert_file.display()))?;
+        let key_pem = fs::read_to_string(key_file).with_context(|| format!("Reading {}", key_file.display()))?;
+
+        let certificates = der::pem_decode(&cert_pem, "CERTIFICATE")?;
+        if certificates.is_empty() {
+            bail!("No certificates found in \"{}\"", cert_file.display());
+        }
+        let key = if key_pem.contains("RSA PRIVATE KEY") {
+            RsaPrivateKey::from_pkcs1_pem(&key_pem)?
+        } else {
+            RsaPrivateKey::from_pkcs8_pem(&key_pem)?
+        };
+
+        // Make sure the key actually belongs to the signing certificate
+        let cert = parse_certificate(&certificates[0])?;
+        if cert.public_key.raw != key.to_public_key().to_public_key_der()?.as_bytes() {
+            bail!("Private key does not match the signing certificate");
+        }
+
+        Ok(Self { certificates, key })
+    }
+}
+
+impl AuthenticodeSigner for KeyFileSigner {
+    fn certificates(&self) -> &[Vec<u8>] {
+        &self.certificates
+    }
+
+    fn sign_digest(&self, alg: DigestAlgorithm, digest: &[u8]) -> Result<Vec<u8>> {
+        Ok(self.key.sign(alg.padding(), digest)?)
+    }
+}
+
+impl CommandSigner {
+    pub fn new(cert_file: &Path, command: &[String]) -> Result<Self> {
+        let cert_pem = fs::read_to_string(cert_file).with_context(|| format!("Reading {}", cert_file.display()))?;
+        let certificates = der::pem_decode(&cert_pem, "CERTIFICATE")?;
+        if certificates.is_empty() {
+            bail!("No certificates found in \"{}\"", cert_file.display());
+        }
+        if command.is_empty() {
+            bail!("No signing command specified");
+        }
+
+        Ok(Self {
+            certificates,
+            command: command.to_vec(),
+        })
+    }
+}
+
+impl AuthenticodeSigner for CommandSigner {
+    fn certificates(&self) -> &[Vec<u8>] {
+        &self.certificates
+    }
+
+    fn sign_digest(&self, alg: DigestAlgorithm, digest: &[u8]) -> Result<Vec<u8>> {
+        let args = self.command[1..].iter().map(|a| a.replace("{digest}", alg.name()));
+        let mut child = Command::new(&self.command[0])
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::inherit())
+            .spawn()
+            .with_context(|| format!("Failed running \"{}\"", self.command[0]))?;
+        child.stdin.take().unwrap().write_all(digest)?;
+        let output = child.wait_with_output()?;
+        if !output.status.success() {
+            bail!("Signing command returned non-success status: {}", output.status);
+        }
+
+        // Make sure the signature was made with the key of the signing certificate
+        let cert = parse_certificate(&self.certificates[0])?;
+        let public_key = RsaPublicKey::from_public_key_der(cert.public_key.raw)?;
+        if public_key.verify(alg.padding(), digest, &output.stdout).is_err() {
+            bail!("Signature returned by signing command does not match the signing certificate");
+        }
+
+        Ok(output.stdout)
+    }
+}
+
+fn read_u16(data: &[u8], offset: usize) -> Result<u16> {
+    data.get(offset..offset + 2)
+        .map(|b| u16::from_le_bytes([b[0], b[1]]))
+        .ok_or_else(|| anyhow!("PE file truncated"))
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Result<u32> {
+    data.get(offset..offset + 4)
+        .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .ok_or_else(|| anyhow!("PE file truncated"))
+}
+
+impl PeLayout {
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        if data.get(0..2) != Some(b"MZ") {
+            bail!("Not a PE file (missing MZ header)");
+        }
+        let pe_offset = read_u32(data, 0x3c)? as usize;
+        if data.get(pe_offset..pe_offset + 4) != Some(b"PE\0\0") {
+            bail!("Not a PE file (missing PE signature)");
+        }
+
+        let opt_offset = pe_offset + 24;
+        let (num_dirs_offset, dirs_offset) = match read_u16(data, opt_offset)? {
+            0x10b => (opt_offset + 92, opt_offset + 96),
+            0x20b => (opt_offset + 108, opt_offset + 112),
+            magic => bail!("Unknown optional header magic 0x{:x}", magic),
+        };
+        if read_u32(data, num_dirs_offset)? < 5 {
+            bail!("PE file has no security directory entry");
+        }
+
+        Ok(Self {
+            checksum_offset: opt_offset + 64,
+            security_dir_offset: dirs_offset + 4 * 8,
+        })
+    }
+
+    /// Offset and size of the attribute certificate table (if present)
+    pub fn certificate_table(&self, data: &[u8]) -> Result<Option<(usize, usize)>> {
+        let offset = read_u32(data, self.security_dir_offset)? as usize;
+        let size = read_u32(data, self.security_dir_offset + 4)? as usize;
+
+        if offset == 0 || size == 0 {
+            return Ok(None);
+        }
+        if offset + size > data.len() {
+            bail!("Certificate table exceeds file size");
+        }
+
+        Ok(Some((offset, size)))
+    }
+
+    /// Authenticode image hash, skipping checksum, security directory entry, and certificate table
+    pub fn image_hash(&self, data: &[u8], alg: DigestAlgorithm) -> Result<Vec<u8>> {
+        let end = match self.certificate_table(data)? {
+            Some((offset, _)) => offset,
+            None => data.len(),
+        };
+
+        Ok(alg.digest(&[
+            &data[..self.checksum_offset],
+            &data[self.checksum_offset + 4..self.security_dir_offset],
+            &data[self.security_dir_offset + 8..end],
+        ]))
+    }
+
+    fn update_checksum(&self, data: &mut [u8]) {
+        let mut sum: u64 = 0;
+        for (idx, chunk) in data.chunks(2).enumerate() {
+            if idx * 2 == self.checksum_offset || idx * 2 == self.checksum_offset + 2 {
+                continue;
+            }
+            sum += chunk[0] as u64 | (*chunk.get(1).unwrap_or(&0) as u64) << 8;
+            sum = (sum & 0xffff) + (sum >> 16);
+        }
+        sum = (sum & 0xffff) + (sum >> 16);
+        let checksum = (sum as u32).wrapping_add(data.len() as u32);
+
+        data[self.checksum_offset..self.checksum_offset + 4].copy_from_slice(&checksum.to_le_bytes());
+    }
+}
+
+/// Extract the PKCS#7 SignedData blob from a PE file's certificate table (if present)
+pub fn read_signature(data: &[u8]) -> Result<Option<Vec<u8>>> {
+    let layout = PeLayout::parse(data)?;
+    match layout.certificate_table(data)? {
+        Some((offset, size)) => signature_from_table(data, offset, size),
+        None => Ok(None),
+    }
+}
+
+/// Find the first PKCS#7 WIN_CERTIFICATE entry in the certificate table
+fn signature_from_table(data: &[u8], offset: usize, size: usize) -> Result<Option<Vec<u8>>> {
+    let mut pos = offset;
+    while pos + 8 <= offset + size {
+        let length = read_u32(data, pos)? as usize;
+        let cert_type = read_u16(data, pos + 6)?;
+        if length < 8 || pos + length > offset + size {
+            bail!("Invalid WIN_CERTIFICATE entry");
+        }
+        if cert_type == WIN_CERT_TYPE_PKCS_SIGNED_DATA {
+            // Strip padding by parsing the DER element
+            let (element, _) = der::parse(&data[pos + 8..pos + length])?;
+            return Ok(Some(element.raw.to_vec()));
+        }
+        pos += (length + 7) & !7;
+    }
+
+    Ok(None)
+}
+
+/// Offset and size of the certificate table according to the PE security data directory
+fn security_directory(data: &[u8]) -> Result<Option<(usize, usize)>> {
+    let dir = match FileKind::parse(data)? {
+        FileKind::Pe32 => PeFile32::parse(data)?
+            .data_directory(IMAGE_DIRECTORY_ENTRY_SECURITY)
+            .map(|d| d.address_range()),
+        FileKind::Pe64 => PeFile64::parse(data)?
+            .data_directory(IMAGE_DIRECTORY_ENTRY_SECURITY)
+            .map(|d| d.address_range()),
+        _ => bail!("Not a PE file"),
+    };
+
+    match dir {
+        // Unlike other directories, the "virtual address" of the security directory is a file offset
+        Some((offset, size)) if offset != 0 && size != 0 => {
+            if offset as usize + size as usize > data.len() {
+                bail!("Certificate table exceeds file size");
+            }
+            Ok(Some((offset as usize, size as usize)))
+        }
+        _ => Ok(None),
+    }
+}
+
+/// Verify all Authenticode signatures (including nested ones) of a PE file.
+/// Returns the DER-encoded signer certificate of each signature.
+///
+/// Note: This only checks that the signatures are intact and were made by the embedded signer
+/// certificates, certificate chains and timestamps are not validated.
+pub fn verify_signatures(data: &[u8]) -> Result<Vec<Vec<u8>>> {
+    let Some((offset, size)) = security_directory(data)? else {
+        bail!("File is not signed");
+    };
+    let Some(pkcs7) = signature_from_table(data, offset, size)? else {
+        bail!("File has no PKCS#7 signature");
+    };
+
+    let layout = PeLayout::parse(data)?;
+    let mut signers = Vec::new();
+    verify_signed_data(&pkcs7, data, &layout, &mut signers)?;
+
+    Ok(signers)
+}
+
+/// Get a field of a parsed structure, malformed signatures must result in an error rather than a panic
+fn field<'a>(fields: &[Tlv<'a>], index: usize, name: &str) -> Result<Tlv<'a>> {
+    fields
+        .get(index)
+        .copied()
+        .ok_or_else(|| anyhow!("Malformed SignedData: missing {name}"))
+}
+
+fn verify_signed_data(pkcs7: &[u8], data: &[u8], layout: &PeLayout, signers: &mut Vec<Vec<u8>>) -> Result<()> {
+    let (content_info, _) = der::parse(pkcs7)?;
+    let ci_fields = content_info.expect(TAG_SEQUENCE)?.children()?;
+    if ci_fields.len() != 2 || ci_fields[0].oid_string()? != OID_PKCS7_SIGNED_DATA {
+        bail!("Signature is not PKCS#7 SignedData");
+    }
+    // version, digestAlgorithms, contentInfo, [0] certificates, [1] crls, signerInfos
+    let sd_fields = field(&ci_fields[1].children()?, 0, "content")?
+        .expect(TAG_SEQUENCE)?
+        .children()?;
+    if sd_fields.len() < 4 {
+        bail!("Invalid SignedData");
+    }
+
+    // Check image hash in SpcIndirectDataContent
+    let content = sd_fields[2].expect(TAG_SEQUENCE)?.children()?;
+    if content.len() != 2 || content[0].oid_string()? != OID_SPC_INDIRECT_DATA {
+        bail!("Signed content is not SpcIndirectDataContent");
+    }
+    let spc = field(&content[1].children()?, 0, "SpcIndirectDataContent")?.expect(TAG_SEQUENCE)?;
+    let digest_info = field(&spc.children()?, 1, "message digest")?.children()?;
+    let digest_alg = field(
+        &field(&digest_info, 0, "digest algorithm")?.children()?,
+        0,
+        "digest algorithm",
+    )?;
+    let alg = DigestAlgorithm::from_oid(&digest_alg.oid_string()?)?;
+    if layout.image_hash(data, alg)? != field(&digest_info, 1, "image hash")?.expect(TAG_OCTET_STRING)?.content {
+        bail!("Image hash mismatch, file was modified after signing");
+    }
+
+    // Find signer certificate
+    let certificates = match sd_fields.iter().find(|f| f.tag == 0xa0) {
+        Some(certs) => certs.children()?,
+        None => Vec::new(),
+    };
+    let signer_infos = field(&sd_fields, sd_fields.len() - 1, "signer infos")?
+        .expect(TAG_SET)?
+        .children()?;
+    // version, issuerAndSerialNumber, digestAlgorithm, [0] signedAttrs, signatureAlgorithm, signature, [1] unsignedAttrs
+    let si_fields = signer_infos
+        .first()
+        .ok_or_else(|| anyhow!("Signature has no signers"))?
+        .children()?;
+    if si_fields.len() < 6 || si_fields[3].tag != 0xa0 {
+        bail!("Invalid or unsupported SignerInfo");
+    }
+    let sid = si_fields[1].expect(TAG_SEQUENCE)?.children()?;
+    if sid.len() != 2 {
+        bail!("Unsupported signer identifier");
+    }
+    let cert = certificates
+        .iter()
+        .find(|c| parse_certificate(c.raw).is_ok_and(|i| i.issuer.raw == sid[0].raw && i.serial.raw == sid[1].raw))
+        .ok_or_else(|| anyhow!("Signer certificate not included in signature"))?;
+
+    // Check message digest attribute
+    let si_alg =
+        DigestAlgorithm::from_oid(&field(&si_fields[2].children()?, 0, "signer digest algorithm")?.oid_string()?)?;
+    let mut message_digest = None;
+    for attr in si_fields[3].children()? {
+        let attr_fields = attr.children()?;
+        if field(&attr_fields, 0, "attribute type")?.oid_string()? == OID_MESSAGE_DIGEST {
+            let values = field(&attr_fields, 1, "attribute values")?.children()?;
+            message_digest = Some(field(&values, 0, "message digest")?.expect(TAG_OCTET_STRING)?.content);
+        }
+    }
+    if message_digest != Some(si_alg.digest(&[spc.content]).as_slice()) {
+        bail!("Message digest mismatch");
+    }
+
+    // Check signature over signed attributes (encoded as SET OF instead of [0] IMPLICIT)
+    match field(&si_fields[4].children()?, 0, "signature algorithm")?
+        .oid_string()?
+        .as_str()
+    {
+        OID_RSA_ENCRYPTION | OID_SHA256_WITH_RSA | OID_SHA384_WITH_RSA | OID_SHA512_WITH_RSA => {}
+        oid => bail!("Unsupported signature algorithm {}", oid),
+    }
+    let mut signed_attrs = si_fields[3].raw.to_vec();
+    signed_attrs[0] = TAG_SET;
+    let public_key = RsaPublicKey::from_public_key_der(parse_certificate(cert.raw)?.public_key.raw)?;
+    public_key
+        .verify(
+            si_alg.padding(),
+            &si_alg.digest(&[&signed_attrs]),
+            si_fields[5].expect(TAG_OCTET_STRING)?.content,
+        )
+        .map_err(|_| anyhow!("Invalid signature"))?;
+    signers.push(cert.raw.to_vec());
+
+    // Verify nested signatures as well
+    if let Some(unsigned_attrs) = si_fields.iter().find(|f| f.tag == 0xa1) {
+        for attr in unsigned_attrs.children()? {
+            let attr_fields = attr.children()?;
+            if field(&attr_fields, 0, "attribute type")?.oid_string()? != OID_SPC_NESTED_SIGNATURE {
+                continue;
+            }
+            for nested in field(&attr_fields, 1, "nested signatures")?.children()? {
+                verify_signed_data(nested.raw, data, layout, signers)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Parse the fields we need from a DER-encoded X.509 certificate
+pub fn parse_certificate(cert: &[u8]) -> Result<CertificateInfo<'_>> {
+    let (certificate, _) = der::parse(cert)?;
+    let tbs = certificate
+        .expect(TAG_SEQUENCE)?
+        .children()?
+        .first()
+        .copied()
+        .ok_or_else(|| anyhow!("Invalid certificate"))?
+        .expect(TAG_SEQUENCE)?;
+    let mut fields = tbs.children()?;
+    // Skip optional version field
+    if fields.first().is_some_and(|f| f.tag == 0xa0) {
+        fields.remove(0);
+    }
+    if fields.len() < 6 {
+        bail!("Invalid certificate");
+    }
+
+    Ok(CertificateInfo {
+        serial: fields[0],
+        issuer: fields[2],
+        public_key: fields[5],
+    })
+}
+
+/// Check if a DER-encoded certificate matches a hex-encoded SHA-1 (as shown by Windows) or SHA-256 thumbprint
+pub fn matches_thumbprint(cert: &[u8], thumbprint: &str) -> bool {
+    let digest = match thumbprint.len() {
+        40 => Sha1::digest(cert).to_vec(),
+        64 => Sha256::digest(cert).to_vec(),
+        _ => return false,
+    };
+    let hex: String = digest.iter().map(|b| format!("{b:02x}")).collect();
+
+    hex.eq_ignore_ascii_case(thumbprint)
+}
+
+/// Create SpcIndirectDataContent for a PE image hash
+fn spc_indirect_data(alg: DigestAlgorithm, image_hash: &[u8]) -> Vec<u8> {
+    let pe_image_data = der::sequence(&[
+        der::tlv(der::TAG_BIT_STRING, &[0]),
+        der::context(0, &der::context(2, &der::tlv(0x80, &SPC_OBSOLETE))),
+    ]);
+
+    der::sequence(&[
+        der::sequence(&[der::oid(OID_SPC_PE_IMAGE_DATA), pe_image_data]),
+        der::sequence(&[der::algorithm(alg.oid()), der::octet_string(image_hash)]),
+    ])
+}
+
+fn attribute(oid: &str, value: Vec<u8>) -> Vec<u8> {
+    der::sequence(&[der::oid(oid), der::set(&[value])])
+}
+
+/// Request RFC 3161 timestamp token for a signature
+fn request_timestamp(url: &str, alg: DigestAlgorithm, signature: &[u8]) -> Result<Vec<u8>> {
+    let nonce = SystemTime::now().duration_since(UNIX_EPOCH)?.as_nanos() as u64;
+    let request = der::sequence(&[
+        der::small_integer(1),
+        der::sequence(&[der::algorithm(alg.oid()), der::octet_string(&alg.digest(&[signature]))]),
+        der::small_integer(nonce),
+        der::boolean(true),
+    ]);
+
+    let response = http::post(url, "application/timestamp-query", &request)
+        .with_context(|| format!("Timestamp request to \"{url}\" failed"))?;
+
+    let (resp, _) = der::parse(&response)?;
+    let fields = resp.expect(TAG_SEQUENCE)?.children()?;
+    let status = fields
+        .first()
+        .ok_or_else(|| anyhow!("Empty timestamp response"))?
+        .children()?;
+    // 0 = granted, 1 = granted with modifications
+    match status.first().map(|s| s.content) {
+        Some([0]) | Some([1]) => {}
+        _ => bail!("Timestamp server rejected request"),
+    }
+    let token = fields
+        .get(1)
+        .ok_or_else(|| anyhow!("Timestamp response has no token"))?;
+
+    Ok(token.raw.to_vec())
+}
+
+/// Build PKCS#7 SignedData (wrapped in ContentInfo) for a PE image hash
+pub fn build_signature(
+    image_hash: &[u8],
+    alg: DigestAlgorithm,
+    signer: &dyn AuthenticodeSigner,
+    timestamp: Option<(&str, DigestAlgorithm)>,
+) -> Result<Vec<u8>> {
+    let certificates = signer.certificates();
+    let cert = parse_certificate(&certificates[0])?;
+
+    let spc = spc_indirect_data(alg, image_hash);
+    // Authenticode quirk: The message digest is over the content of the SpcIndirectDataContent
+    // sequence, excluding its tag and length
+    let spc_content = der::parse(&spc)?.0.content;
+
+    let signed_attrs = der::set(&[
+        attribute(OID_CONTENT_TYPE, der::oid(OID_SPC_INDIRECT_DATA)),
+        attribute(OID_MESSAGE_DIGEST, der::octet_string(&alg.digest(&[spc_content]))),
+        attribute(OID_SPC_SP_OPUS_INFO, der::sequence(&[])),
+        attribute(
+            OID_SPC_STATEMENT_TYPE,
+            der::sequence(&[der::oid(OID_SPC_INDIVIDUAL_SP_KEY_PURPOSE)]),
+        ),
+    ]);
+    // The signature is over the SET OF encoding, but it is included as [0] IMPLICIT
+    let signature = signer.sign_digest(alg, &alg.digest(&[&signed_attrs]))?;
+    let mut signed_attrs_implicit = signed_attrs.clone();
+    signed_attrs_implicit[0] = 0xa0;
+
+    let mut signer_info = vec![
+        der::small_integer(1),
+        der::sequence(&[cert.issuer.raw.to_vec(), cert.serial.raw.to_vec()]),
+        der::algorithm(alg.oid()),
+        signed_attrs_implicit,
+        der::algorithm(OID_RSA_ENCRYPTION),
+        der::octet_string(&signature),
+    ];
+
+    if let Some((url, ts_alg)) = timestamp {
+        debug!("Requesting timestamp from \"{url}\"");
+        let token = request_timestamp(url, ts_alg, &signature)?;
+        signer_info.push(der::context(1, &attribute(OID_SPC_RFC3161_TIMESTAMP, token)));
+    }
+
+    let signed_data = der::sequence(&[
+        der::small_integer(1),
+        der::set(&[der::algorithm(alg.oid())]),
+        der::sequence(&[der::oid(OID_SPC_INDIRECT_DATA), der::context(0, &spc)]),
+        der::context(0, &certificates.concat()),
+        der::set(&[der::sequence(&signer_info)]),
+    ]);
+
+    Ok(der::sequence(&[
+        der::oid(OID_PKCS7_SIGNED_DATA),
+        der::context(0, &signed_data),
+    ]))
+}
+
+/// Add a signature as nested signature (unauthenticated attribute) to the first signer of an existing one
+fn nest_signature(existing: &[u8], nested: Vec<u8>) -> Result<Vec<u8>> {
+    let (content_info, _) = der::parse(existing)?;
+    let ci_fields = content_info.expect(TAG_SEQUENCE)?.children()?;
+    let signed_data = field(&field(&ci_fields, 1, "content")?.children()?, 0, "content")?;
+    let sd_fields = signed_data.expect(TAG_SEQUENCE)?.children()?;
+    if sd_fields.is_empty() {
+        bail!("Malformed SignedData: missing signer infos");
+    }
+    let signer_infos = field(&sd_fields, sd_fields.len() - 1, "signer infos")?
+        .expect(TAG_SET)?
+        .children()?;
+    let si_fields = signer_infos
+        .first()
+        .ok_or_else(|| anyhow!("Existing signature has no signers"))?
+        .children()?;
+
+    let mut unsigned_attrs: Vec<Vec<u8>> = Vec::new();
+    let mut new_si: Vec<Vec<u8>> = Vec::new();
+    for si_field in &si_fields {
+        if si_field.tag != 0xa1 {
+            new_si.push(si_field.raw.to_vec());
+            continue;
+        }
+        for attr in si_field.children()? {
+            let attr_fields = attr.children()?;
+            let attr_type = field(&attr_fields, 0, "attribute type")?;
+            if attr_type.oid_string()? == OID_SPC_NESTED_SIGNATURE {
+                // Add to existing nested signatures
+                let mut values: Vec<Vec<u8>> = field(&attr_fields, 1, "nested signatures")?
+                    .children()?
+                    .iter()
+                    .map(|v| v.raw.to_vec())
+                    .collect();
+                values.push(nested.clone());
+                unsigned_attrs.push(der::sequence(&[
+                    attr_type.raw.to_vec(),
+                    der::tlv(TAG_SET, &values.concat()),
+                ]));
+            } else {
+                unsigned_attrs.push(attr.raw.to_vec());
+            }
+        }
+    }
+    if !unsigned_attrs.iter().any(|a| {
+        der::parse(a)
+            .and_then(|(t, _)| field(&t.children()?, 0, "attribute type")?.oid_string())
+            .is_ok_and(|oid| oid == OID_SPC_NESTED_SIGNATURE)
+    }) {
+        unsigned_attrs.push(attribute(OID_SPC_NESTED_SIGNATURE, nested));
+    }
+    new_si.push(der::context(1, &unsigned_attrs.concat()));
+
+    let mut new_signer_infos = vec![der::sequence(&new_si)];
+    new_signer_infos.extend(signer_infos[1..].iter().map(|s| s.raw.to_vec()));
+
+    let mut new_sd: Vec<Vec<u8>> = sd_fields[..sd_fields.len() - 1]
+        .iter()
+        .map(|f| f.raw.to_vec())
+        .collect();
+    new_sd.push(der::tlv(TAG_SET, &new_signer_infos.concat()));
+
+    Ok(der::sequence(&[
+        ci_fields[0].raw.to_vec(),
+        der::context(0, &der::sequence(&new_sd)),
+    ]))
+}
+
+/// Sign PE file in-place
+pub fn sign_file(path: &Path, opts: &CodesignOptions, signer: &dyn AuthenticodeSigner) -> Result<()> {
+    let alg = DigestAlgorithm::from_str(&opts.sign_digest)?;
+    let timestamp = if opts.sign_ts_serv.is_empty() {
+        None
+    } else {
+        let ts_alg = match &opts.sign_ts_algo {
+            Some(ts_algo) => DigestAlgorithm::from_str(ts_algo)?,
+            None => alg,
+        };
+        Some((opts.sign_ts_serv.as_str(), ts_alg))
+    };
+
+    let mut data = fs::read(path)?;
+    let layout = PeLayout::parse(&data).with_context(|| format!("Parsing \"{}\" failed", path.display()))?;
+
+    // Remove existing certificate table (keeping the signature if appending)
+    let existing = if opts.sign_append { read_signature(&data)? } else { None };
+    if let Some((offset, size)) = layout.certificate_table(&data)? {
+        // Data after the table is not covered by the signature and would be lost when replacing it
+        if offset + size < data.len() {
+            bail!(
+                "\"{}\" contains data after the certificate table, refusing to sign",
+                path.display()
+            );
+        }
+        data.truncate(offset);
+    }
+    data[layout.security_dir_offset..layout.security_dir_offset + 8].fill(0);
+    // Certificate table must be 8-byte aligned, the padding is part of the image hash
+    data.resize((data.len() + 7) & !7, 0);
+
+    let image_hash = layout.image_hash(&data, alg)?;
+    let mut signature = build_signature(&image_hash, alg, signer, timestamp)?;
+    if let Some(existing) = existing {
+        signature = nest_signature(&existing, signature)?;
+    }
+
+    // Append WIN_CERTIFICATE structure
+    let table_offset = data.len();
+    let table_size = (8 + signature.len() + 7) & !7;
+    data.extend((table_size as u32).to_le_bytes());
+    data.extend(WIN_CERT_REVISION_2_0.to_le_bytes());
+    data.extend(WIN_CERT_TYPE_PKCS_SIGNED_DATA.to_le_bytes());
+    data.extend(signature);
+    data.resize(table_offset + table_size, 0);
+
+    let dir = layout.security_dir_offset;
+    data[dir..dir + 4].copy_from_slice(&(table_offset as u32).to_le_bytes());
+    data[dir + 4..dir + 8].copy_from_slice(&(table_size as u32).to_le_bytes());
+    layout.update_checksum(&mut data);
+
+    fs::write(path, data)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+pub mod authenticode_tests {
+    use super::*;
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+    use std::path::PathBuf;
+    use std::thread;
+
+    /// Create a minimal PE32+ file with a single section
+    pub fn create_test_pe(path: &Path) {
+        let mut data = vec![0u8; 0x400];
+        data[0..2].copy_from_slice(b"MZ");
+        data[0x3c..0x40].copy_from_slice(&0x40u32.to_le_bytes());
+        data[0x40..0x44].copy_from_slice(b"PE\0\0");
+        // COFF header: AMD64, 1 section, optional header size 240, executable
+        data[0x44..0x46].copy_from_slice(&0x8664u16.to_le_bytes());
+        data[0x46..0x48].copy_from_slice(&1u16.to_le_bytes());
+        data[0x54..0x56].copy_from_slice(&240u16.to_le_bytes());
+        data[0x56..0x58].copy_from_slice(&0x22u16.to_le_bytes());
+        // Optional header
+        let opt = 0x58;
+        data[opt..opt + 2].copy_from_slice(&0x20bu16.to_le_bytes());
+        data[opt + 16..opt + 20].copy_from_slice(&0x1000u32.to_le_bytes());
+        data[opt + 24..opt + 32].copy_from_slice(&0x140000000u64.to_le_bytes());
+        data[opt + 32..opt + 36].copy_from_slice(&0x1000u32.to_le_bytes());
+        data[opt + 36..opt + 40].copy_from_slice(&0x200u32.to_le_bytes());
+        data[opt + 40..opt + 42].copy_from_slice(&6u16.to_le_bytes());
+        data[opt + 48..opt + 50].copy_from_slice(&6u16.to_le_bytes());
+        data[opt + 56..opt + 60].copy_from_slice(&0x2000u32.to_le_bytes());
+        data[opt + 60..opt + 64].copy_from_slice(&0x200u32.to_le_bytes());
+        data[opt + 68..opt + 70].copy_from_slice(&3u16.to_le_bytes());
+        data[opt + 108..opt + 112].copy_from_slice(&16u32.to_le_bytes());
+        // Section header
+        let sec = opt + 240;
+        data[sec..sec + 5].copy_from_slice(b".text");
+        data[sec + 8..sec + 12].copy_from_slice(&0x10u32.to_le_bytes());
+        data[sec + 12..sec + 16].copy_from_slice(&0x1000u32.to_le_bytes());
+        data[sec + 16..sec + 20].copy_from_slice(&0x200u32.to_le_bytes());
+        data[sec + 20..sec + 24].copy_from_slice(&0x200u32.to_le_bytes());
+        data[sec + 36..sec + 40].copy_from_slice(&0x60000020u32.to_le_bytes());
+        // Code: xor eax, eax; ret
+        data[0x200..0x203].copy_from_slice(&[0x31, 0xc0, 0xc3]);
+
+        fs::write(path, data).unwrap();
+    }
+
+    /// Local RFC 3161 "server" that returns a dummy token and the received message imprint
+    fn spawn_tsa_stub() -> (String, thread::JoinHandle<Vec<u8>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/tsa", listener.local_addr().unwrap());
+
+        let handle = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = Vec::new();
+            let mut buf = [0u8; 4096];
+            let body_start = loop {
+                let read = stream.read(&mut buf).unwrap();
+                request.extend_from_slice(&buf[..read]);
+                if let Some(pos) = request.windows(4).position(|w| w == b"\r\n\r\n") {
+                    break pos + 4;
+                }
+            };
+            let headers = String::from_utf8_lossy(&request[..body_start]).to_lowercase();
+            let length: usize = headers
+                .lines()
+                .find_map(|l| l.strip_prefix("content-length: "))
+                .unwrap()
+                .trim()
+                .parse()
+                .unwrap();
+            while request.len() < body_start + length {
+                let read = stream.read(&mut buf).unwrap();
+                request.extend_from_slice(&buf[..read]);
+            }
+
+            let (req, _) = der::parse(&request[body_start..]).unwrap();
+            let imprint = req.children().unwrap()[1].children().unwrap()[1].content.to_vec();
+
+            let tst_info = der::sequence(&[der::small_integer(1), der::octet_string(&imprint)]);
+            let token = der::sequence(&[
+                der::oid(OID_PKCS7_SIGNED_DATA),
+                der::context(
+                    0,
+                    &der::sequence(&[
+                        der::small_integer(3),
+                        der::set(&[]),
+                        der::sequence(&[
+                            der::oid("1.2.840.113549.1.9.16.1.4"),
+                            der::context(0, &der::octet_string(&tst_info)),
+                        ]),
+                        der::set(&[]),
+                    ]),
+                ),
+            ]);
+            let response = der::sequence(&[der::sequence(&[der::small_integer(0)]), token]);
+
+            write!(
+                stream,
+                "HTTP/1.0 200 OK\r\nContent-Type: application/timestamp-reply\r\nContent-Length: {}\r\n\r\n",
+                response.len()
+            )
+            .unwrap();
+            stream.write_all(&response).unwrap();
+
+            imprint
+        });
+
+        (url, handle)
+    }
+
+    #[test]
+    fn test_authenticode_sign() {
+        let pe_path = PathBuf::from("extra/test_files/authenticode_test.exe");
+        create_test_pe(&pe_path);
+        let unsigned_hash = PeLayout::parse(&fs::read(&pe_path).unwrap())
+            .unwrap()
+            .image_hash(&fs::read(&pe_path).unwrap(), DigestAlgorithm::Sha256)
+            .unwrap();
+
+        let signer = KeyFileSigner::from_files(
+            Path::new("extra/test_files/codesign_cert.pem"),
+            Path::new("extra/test_files/codesign_key.pem"),
+        )
+        .unwrap();
+        let (url, tsa) = spawn_tsa_stub();
+        let opts = CodesignOptions {
+            sign_digest: "sha256".to_string(),
+            sign_ts_serv: url,
+            ..Default::default()
+        };
+        sign_file(&pe_path, &opts, &signer).expect("Signing failed");
+        let imprint = tsa.join().unwrap();
+
+        let data = fs::read(&pe_path).unwrap();
+        let layout = PeLayout::parse(&data).unwrap();
+        // Signing must not change the image hash
+        assert_eq!(
+            layout.image_hash(&data, DigestAlgorithm::Sha256).unwrap(),
+            unsigned_hash
+        );
+
+        // Walk SignedData: ContentInfo -> SignedData -> SignerInfo
+        let pkcs7 = read_signature(&data).unwrap().expect("No signature found");
+        let (content_info, _) = der::parse(&pkcs7).unwrap();
+        let ci_fields = content_info.children().unwrap();
+        assert_eq!(ci_fields[0].oid_string().unwrap(), OID_PKCS7_SIGNED_DATA);
+        let sd_fields = ci_fields[1].children().unwrap()[0].children().unwrap();
+
+        // Image hash in SpcIndirectDataContent
+        let spc = sd_fields[2].children().unwrap()[1].children().unwrap()[0];
+        let digest_info = spc.children().unwrap()[1].children().unwrap();
+        assert_eq!(digest_info[1].expect(TAG_OCTET_STRING).unwrap().content, unsigned_hash);
+
+        // Verify signature over signed attributes
+        let si_fields = sd_fields[4].children().unwrap()[0].children().unwrap();
+        let mut signed_attrs = si_fields[3].raw.to_vec();
+        signed_attrs[0] = TAG_SET;
+        let signature = si_fields[5].content;
+        let cert_pem = fs::read_to_string("extra/test_files/codesign_cert.pem").unwrap();
+        let cert_der = &der::pem_decode(&cert_pem, "CERTIFICATE").unwrap()[0];
+        let pubkey = RsaPublicKey::from_public_key_der(parse_certificate(cert_der).unwrap().public_key.raw).unwrap();
+        pubkey
+            .verify(
+                DigestAlgorithm::Sha256.padding(),
+                &DigestAlgorithm::Sha256.digest(&[&signed_attrs]),
+                signature,
+            )
+            .expect("Signature verification failed");
+
+        // Timestamp was requested for the signature and added as unsigned attribute
+        assert_eq!(imprint, DigestAlgorithm::Sha256.digest(&[signature]));
+        let unsigned_attrs = si_fields[6].children().unwrap();
+        assert_eq!(
+            unsigned_attrs[0].children().unwrap()[0].oid_string().unwrap(),
+            OID_SPC_RFC3161_TIMESTAMP
+        );
+
+        // Append a second signature without timestamp
+        let opts = CodesignOptions {
+            sign_digest: "sha512".to_string(),
+            sign_append: true,
+            ..Default::default()
+        };
+        sign_file(&pe_path, &opts, &signer).expect("Signing failed");
+        let data = fs::read(&pe_path).unwrap();
+        let pkcs7_nested = read_signature(&data).unwrap().unwrap();
+        let (content_info, _) = der::parse(&pkcs7_nested).unwrap();
+        let sd_fields = content_info.children().unwrap()[1].children().unwrap()[0]
+            .children()
+            .unwrap();
+        let si_fields = sd_fields[4].children().unwrap()[0].children().unwrap();
+        let oids: Vec<String> = si_fields[6]
+            .children()
+            .unwrap()
+            .iter()
+            .map(|a| a.children().unwrap()[0].oid_string().unwrap())
+            .collect();
+        assert_eq!(oids, [OID_SPC_RFC3161_TIMESTAMP, OID_SPC_NESTED_SIGNATURE]);
+    }
+
+    #[test]
+    fn test_authenticode_verify() {
+        let pe_path = PathBuf::from("extra/test_files/authenticode_verify.exe");
+        create_test_pe(&pe_path);
+        assert!(verify_signatures(&fs::read(&pe_path).unwrap()).is_err());
+
+        let signer = KeyFileSigner::from_files(
+            Path::new("extra/test_files/codesign_cert.pem"),
+            Path::new("extra/test_files/codesign_key.pem"),
+        )
+        .unwrap();
+        let opts = CodesignOptions {
+            sign_digest: "sha256".to_string(),
+            ..Default::default()
+        };
+        sign_file(&pe_path, &opts, &signer).expect("Signing failed");
+
+        let signers = verify_signatures(&fs::read(&pe_path).unwrap()).expect("Verification failed");
+        assert_eq!(signers.len(), 1);
+        assert!(matches_thumbprint(
+            &signers[0],
+            "10801E0A7B69D39CEE7E51091F5BFA2DA3085737"
+        ));
+        assert!(!matches_thumbprint(
+            &signers[0],
+            "0000000000000000000000000000000000000000"
+        ));
+
+        // Nested signatures are verified as well
+        let opts = CodesignOptions {
+            sign_digest: "sha384".to_string(),
+            sign_append: true,
+            ..Default::default()
+        };
+        sign_file(&pe_path, &opts, &signer).expect("Signing failed");
+        let signers = verify_signatures(&fs::read(&pe_path).unwrap()).expect("Verification failed");
+        assert_eq!(signers.len(), 2);
+
+        // Modifying the code invalidates the signature
+        let mut data = fs::read(&pe_path).unwrap();
+        data[0x201] ^= 0xff;
+        let err = verify_signatures(&data).unwrap_err();
+        assert!(err.to_string().contains("Image hash mismatch"));
+
+        // Data following the certificate table must not be dropped by re-signing
+        let mut data = fs::read(&pe_path).unwrap();
+        data.extend(b"overlay");
+        fs::write(&pe_path, &data).unwrap();
+        assert!(sign_file(&pe_path, &opts, &signer).is_err());
+        assert_eq!(fs::read(&pe_path).unwrap(), data);
+    }
+
+    #[test]
+    fn test_authenticode_malformed() {
+        let pe_path = PathBuf::from("extra/test_files/authenticode_malformed.exe");
+        create_test_pe(&pe_path);
+        let signer = KeyFileSigner::from_files(
+            Path::new("extra/test_files/codesign_cert.pem"),
+            Path::new("extra/test_files/codesign_key.pem"),
+        )
+        .unwrap();
+        let opts = CodesignOptions {
+            sign_digest: "sha256".to_string(),
+            ..Default::default()
+        };
+        sign_file(&pe_path, &opts, &signer).expect("Signing failed");
+        let data = fs::read(&pe_path).unwrap();
+        let layout = PeLayout::parse(&data).unwrap();
+        let pkcs7 = read_signature(&data).unwrap().unwrap();
+
+        // Truncated signatures are reported as errors instead of panicking
+        for len in 0..pkcs7.len() {
+            assert!(verify_signed_data(&pkcs7[..len], &data, &layout, &mut Vec::new()).is_err());
+        }
+
+        // Well-formed DER with missing fields
+        let (content_info, _) = der::parse(&pkcs7).unwrap();
+        let ci_fields = content_info.children().unwrap();
+        let sd_fields = ci_fields[1].children().unwrap()[0].children().unwrap();
+        let si_fields = sd_fields[4].children().unwrap()[0].children().unwrap();
+        let signed_data = |replace: usize, field: Vec<u8>| {
+            let mut fields: Vec<Vec<u8>> = sd_fields.iter().map(|f| f.raw.to_vec()).collect();
+            fields[replace] = field;
+            der::sequence(&[ci_fields[0].raw.to_vec(), der::context(0, &der::sequence(&fields))])
+        };
+        let mut signer_info: Vec<Vec<u8>> = si_fields.iter().map(|f| f.raw.to_vec()).collect();
+        signer_info[3] = der::context(0, &der::sequence(&[der::oid(OID_MESSAGE_DIGEST)]));
+        let malformed = [
+            der::sequence(&[der::oid(OID_PKCS7_SIGNED_DATA), der::context(0, &[])]),
+            signed_data(
+                2,
+                der::sequence(&[der::oid(OID_SPC_INDIRECT_DATA), der::context(0, &[])]),
+            ),
+            signed_data(4, der::tlv(TAG_SET, &der::sequence(&signer_info))),
+            signed_data(4, der::tlv(TAG_SET, &[])),
+        ];
+        for pkcs7 in &malformed {
+            assert!(verify_signed_data(pkcs7, &data, &layout, &mut Vec::new()).is_err());
+        }
+        assert!(nest_signature(&malformed[0], Vec::new()).is_err());
+        assert!(nest_signature(&malformed[3], Vec::new()).is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_command_signer() {
+        let cert = Path::new("extra/test_files/codesign_cert.pem");
+        let digest = DigestAlgorithm::Sha256.digest(&[b"data"]);
+
+        // Output that is not a signature by the certificate's key is rejected
+        let signer = CommandSigner::new(cert, &["cat".to_string()]).unwrap();
+        let err = signer.sign_digest(DigestAlgorithm::Sha256, &digest).unwrap_err();
+        assert!(err.to_string().contains("does not match"));
+
+        let signer = CommandSigner::new(cert, &["false".to_string()]).unwrap();
+        assert!(signer.sign_digest(DigestAlgorithm::Sha256, &digest).is_err());
+    }
+}
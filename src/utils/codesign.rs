@@ -1,25 +1,200 @@
-#![allow(unused_variables)]
-
+#[cfg(windows)]
 use std::ffi::OsString;
+use std::fs;
 use std::io::Write;
 use std::path::{Path, PathBuf};
 use std::process::Command;
+use std::thread;
+use std::time::Duration;
 
-use anyhow::{anyhow, Context, Result};
-use log::{debug, error, info, warn};
+#[cfg(windows)]
+use anyhow::Context;
+use anyhow::{anyhow, bail, Result};
+#[cfg(windows)]
+use log::debug;
+use log::{error, info, warn};
+use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
 #[cfg(windows)]
 use winreg::enums::{HKEY_LOCAL_MACHINE, KEY_READ, KEY_WOW64_32KEY};
 #[cfg(windows)]
 use winreg::RegKey;
 
-use crate::models::config::CodesignOptions;
+use crate::models::config::{CodesignBackend, CodesignOptions};
+use crate::utils::authenticode;
+use crate::utils::authenticode::{AuthenticodeSigner, CommandSigner, KeyFileSigner};
+use crate::utils::progress::Task;
+
+/// Sign files using the configured backend
+pub fn sign(files: &[PathBuf], opts: &CodesignOptions) -> Result<()> {
+    if files.is_empty() {
+        return Ok(());
+    }
+
+    match opts.sign_backend {
+        CodesignBackend::Signtool => sign_signtool(files, opts),
+        CodesignBackend::Native => sign_native(files, opts),
+        CodesignBackend::Command => sign_command(files, opts),
+    }
+}
+
+/// Run signing command on batches of files, failed batches are retried with exponential backoff
+fn run_batches<F>(name: &str, files: &[PathBuf], opts: &CodesignOptions, make_command: F) -> Result<()>
+where
+    F: Fn(&[PathBuf]) -> Command,
+{
+    let batches = files.len().div_ceil(opts.sign_batch_size);
+
+    for (idx, chunk) in files.chunks(opts.sign_batch_size).enumerate() {
+        info!(" => Running {name} ({}/{batches})...", idx + 1);
+
+        let mut attempt = 0;
+        loop {
+            let output = make_command(chunk).output()?;
+            if output.status.success() {
+                break;
+            }
+
+            // std::process::Output's status is returned as an i32, but on Windows it's a u32,
+            // so accept both the signed and unsigned representation
+            let code = output.status.code();
+            if code.is_some_and(|c| {
+                opts.sign_ignore_exit_codes
+                    .iter()
+                    .any(|i| *i == c as i64 || *i == c as u32 as i64)
+            }) {
+                warn!("{name} returned ignored non-success status: {}", output.status);
+                break;
+            }
 
-const MAX_FILES: usize = 5;
-// std::process::Output's status is returned as an i32, but on Windows it's a u32
-const IGNORE_STATUS: i32 = 0xc0000374u32 as i32;
+            if attempt < opts.sign_retries {
+                let delay = opts
+                    .sign_retry_delay
+                    .saturating_mul(1u64.checked_shl(attempt).unwrap_or(u64::MAX));
+                attempt += 1;
+                warn!(
+                    "{name} returned non-success status: {}, retrying in {delay} seconds ({attempt}/{})...",
+                    output.status, opts.sign_retries
+                );
+                thread::sleep(Duration::from_secs(delay));
+                continue;
+            }
+
+            error!("{name} returned non-success status: {}", output.status);
+            std::io::stdout().write_all(&output.stdout)?;
+            std::io::stderr().write_all(&output.stderr)?;
+
+            return Err(anyhow!("{name} failed (see stdout/stderr for details)"));
+        }
+    }
+
+    Ok(())
+}
+
+/// Sign files using the user-defined command template
+fn sign_command(files: &[PathBuf], opts: &CodesignOptions) -> Result<()> {
+    let Some((program, _)) = opts.sign_command.split_first() else {
+        bail!("No codesigning command specified")
+    };
+    let name = Path::new(program).file_name().unwrap_or_default().to_string_lossy();
+
+    run_batches(&name, files, opts, |chunk| {
+        let mut command = Command::new(program);
+        command.args(expand_command_args(&opts.sign_command[1..], chunk, opts));
+        command
+    })
+}
+
+/// Replace placeholders in command arguments, "{files}" is expanded to one argument per file
+fn expand_command_args(template: &[String], files: &[PathBuf], opts: &CodesignOptions) -> Vec<String> {
+    let mut args = Vec::new();
+
+    for arg in template {
+        if arg == "{files}" {
+            args.extend(files.iter().map(|f| f.to_string_lossy().into_owned()));
+            continue;
+        }
+
+        args.push(
+            arg.replace("{digest}", &opts.sign_digest)
+                .replace("{timestamp_url}", &opts.sign_ts_serv)
+                .replace(
+                    "{timestamp_digest}",
+                    opts.sign_ts_algo.as_deref().unwrap_or(&opts.sign_digest),
+                )
+                .replace("{cert}", opts.sign_cert_file.as_deref().unwrap_or_default())
+                .replace("{key}", opts.sign_key_file.as_deref().unwrap_or_default())
+                .replace("{name}", opts.sign_name.as_deref().unwrap_or_default()),
+        );
+    }
+
+    args
+}
+
+/// Sign files using the built-in Authenticode implementation
+fn sign_native(files: &[PathBuf], opts: &CodesignOptions) -> Result<()> {
+    let Some(cert_file) = &opts.sign_cert_file else {
+        bail!("Native codesigning requires \"sign_cert_file\"")
+    };
+    let signer: Box<dyn AuthenticodeSigner> = match &opts.sign_key_file {
+        Some(key_file) if !key_file.is_empty() => {
+            Box::new(KeyFileSigner::from_files(Path::new(cert_file), Path::new(key_file))?)
+        }
+        _ => Box::new(CommandSigner::new(Path::new(cert_file), &opts.sign_key_command)?),
+    };
+
+    let task = Task::start("sign", files.len() as u64);
+
+    files.par_iter().try_for_each(|file| {
+        authenticode::sign_file(file, opts, signer.as_ref())
+            .map_err(|e| e.context(format!("Signing \"{}\" failed", file.display())))?;
+        task.inc();
+        Ok(())
+    })
+}
+
+/// Verify that all files carry a valid Authenticode signature from one of the specified certificates
+pub fn verify(files: &[PathBuf], thumbprints: &[String]) -> Result<()> {
+    let task = Task::start("verify", files.len() as u64);
+
+    let failed = files
+        .par_iter()
+        .filter(|file| {
+            let result = fs::read(file).map_err(anyhow::Error::from).and_then(|data| {
+                let signers = authenticode::verify_signatures(&data)?;
+                if !signers
+                    .iter()
+                    .any(|cert| thumbprints.iter().any(|t| authenticode::matches_thumbprint(cert, t)))
+                {
+                    bail!("Not signed by any of the configured certificates")
+                }
+                Ok(())
+            });
+
+            task.inc();
+            match result {
+                Ok(_) => false,
+                Err(e) => {
+                    error!("Signature verification of \"{}\" failed: {}", file.display(), e);
+                    true
+                }
+            }
+        })
+        .count();
+
+    if failed > 0 {
+        bail!("{} of {} files are not signed correctly!", failed, files.len())
+    }
+
+    Ok(())
+}
+
+#[cfg(not(windows))]
+fn sign_signtool(_files: &[PathBuf], _opts: &CodesignOptions) -> Result<()> {
+    bail!("The signtool backend is only supported on Windows, use the native backend instead.")
+}
 
 #[cfg(windows)]
-pub fn sign(files: &[PathBuf], opts: &CodesignOptions) -> Result<()> {
+fn sign_signtool(files: &[PathBuf], opts: &CodesignOptions) -> Result<()> {
     let signtool = locate_signtool()?;
     debug!("Signtool found at {:?}", signtool);
 
@@ -51,42 +226,11 @@ pub fn sign(files: &[PathBuf], opts: &CodesignOptions) -> Result<()> {
         args.push(kms_id.into());
     }
 
-    let slices = files.len().div_ceil(MAX_FILES);
-    let mut ctr = 0;
-
-    for chunk in files.chunks(MAX_FILES) {
-        ctr += 1;
-        let mut chunk_args = args.to_owned();
-        for x in chunk {
-            chunk_args.push(x.to_owned().into_os_string())
-        }
-
-        info!(" => Running signtool ({ctr}/{slices})...");
-        let output = Command::new(&signtool).args(chunk_args).output()?;
-
-        if !output.status.success() {
-            // Annoying error code that seems to only happen *after* successfully signing...
-            if output.status.code().unwrap() == IGNORE_STATUS {
-                warn!("signtool returned ignored non-success status: {}", output.status);
-                continue;
-            }
-            error!("signtool returned non-success status: {}", output.status);
-            std::io::stdout().write_all(&output.stdout)?;
-            std::io::stderr().write_all(&output.stderr)?;
-
-            return Err(anyhow!("signtool failed (see stdout/stderr for details)"));
-        }
-    }
-
-    Ok(())
-}
-
-#[cfg(unix)]
-pub fn sign(files: Vec<PathBuf>, opts: &CodesignOptions) -> Result<()> {
-    use log::warn;
-    warn!("Codesigning is not (yet) supported on this platform.");
-
-    Ok(())
+    run_batches("signtool", files, opts, |chunk| {
+        let mut command = Command::new(&signtool);
+        command.args(&args).args(chunk);
+        command
+    })
 }
 
 // Based on https://github.com/forbjok/rust-codesign/blob/master/lib/src/signtool.rs (Apache-2/MIT)
@@ -149,3 +293,45 @@ fn locate_signtool() -> Result<PathBuf> {
         None => Err(anyhow!("Signtool was not found!")),
     }
 }
+
+#[cfg(test)]
+mod codesign_tests {
+    use super::*;
+
+    #[test]
+    fn test_expand_command() {
+        let opts = CodesignOptions {
+            sign_backend: CodesignBackend::Command,
+            sign_digest: "sha256".to_string(),
+            sign_ts_serv: "http://timestamp.example.com".to_string(),
+            sign_cert_file: Some("chain.pem".to_string()),
+            sign_command: [
+                "jsign",
+                "--alg",
+                "{digest}",
+                "--tsaurl={timestamp_url}",
+                "--certfile",
+                "{cert}",
+                "{files}",
+            ]
+            .map(String::from)
+            .to_vec(),
+            ..Default::default()
+        };
+        let files = [PathBuf::from("a.exe"), PathBuf::from("b.dll")];
+
+        let args = expand_command_args(&opts.sign_command[1..], &files, &opts);
+        assert_eq!(
+            args,
+            [
+                "--alg",
+                "sha256",
+                "--tsaurl=http://timestamp.example.com",
+                "--certfile",
+                "chain.pem",
+                "a.exe",
+                "b.dll"
+            ]
+        );
+    }
+}
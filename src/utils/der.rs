@@ -0,0 +1,241 @@
+// Minimal DER encoder/parser, just enough to build and inspect the
+// PKCS#7/CMS structures used for Authenticode signatures and timestamps.
+
+use anyhow::{bail, Result};
+
+pub const TAG_INTEGER: u8 = 0x02;
+pub const TAG_BIT_STRING: u8 = 0x03;
+pub const TAG_OCTET_STRING: u8 = 0x04;
+pub const TAG_NULL: u8 = 0x05;
+pub const TAG_OID: u8 = 0x06;
+pub const TAG_SEQUENCE: u8 = 0x30;
+pub const TAG_SET: u8 = 0x31;
+
+/// Parsed DER element referencing the input buffer
+#[derive(Debug, Clone, Copy)]
+pub struct Tlv<'a> {
+    pub tag: u8,
+    /// Content octets (without tag and length)
+    pub content: &'a [u8],
+    /// Complete encoding (including tag and length)
+    pub raw: &'a [u8],
+}
+
+fn encode_length(len: usize) -> Vec<u8> {
+    if len < 0x80 {
+        return vec![len as u8];
+    }
+
+    let bytes: Vec<u8> = len.to_be_bytes().into_iter().skip_while(|b| *b == 0).collect();
+    let mut out = vec![0x80 | bytes.len() as u8];
+    out.extend(bytes);
+    out
+}
+
+pub fn tlv(tag: u8, content: &[u8]) -> Vec<u8> {
+    let mut out = vec![tag];
+    out.extend(encode_length(content.len()));
+    out.extend_from_slice(content);
+    out
+}
+
+pub fn sequence(parts: &[Vec<u8>]) -> Vec<u8> {
+    tlv(TAG_SEQUENCE, &parts.concat())
+}
+
+/// SET OF, elements are sorted by their encoding as required by DER
+pub fn set(parts: &[Vec<u8>]) -> Vec<u8> {
+    let mut sorted = parts.to_vec();
+    sorted.sort();
+    tlv(TAG_SET, &sorted.concat())
+}
+
+/// Context-specific tag, always constructed (i.e. EXPLICIT or IMPLICIT of a constructed type)
+pub fn context(num: u8, content: &[u8]) -> Vec<u8> {
+    tlv(0xa0 | num, content)
+}
+
+pub fn octet_string(content: &[u8]) -> Vec<u8> {
+    tlv(TAG_OCTET_STRING, content)
+}
+
+pub fn null() -> Vec<u8> {
+    tlv(TAG_NULL, &[])
+}
+
+pub fn boolean(value: bool) -> Vec<u8> {
+    tlv(0x01, &[if value { 0xff } else { 0x00 }])
+}
+
+/// Encode unsigned big-endian integer
+pub fn integer(value: &[u8]) -> Vec<u8> {
+    let mut bytes: Vec<u8> = value.iter().copied().skip_while(|b| *b == 0).collect();
+    if bytes.is_empty() || bytes[0] & 0x80 != 0 {
+        bytes.insert(0, 0);
+    }
+    tlv(TAG_INTEGER, &bytes)
+}
+
+pub fn small_integer(value: u64) -> Vec<u8> {
+    integer(&value.to_be_bytes())
+}
+
+/// Encode object identifier from dotted string (e.g. "1.2.840.113549.1.7.2")
+pub fn oid(dotted: &str) -> Vec<u8> {
+    let arcs: Vec<u64> = dotted.split('.').map(|a| a.parse().expect("Invalid OID")).collect();
+    let mut content = vec![(arcs[0] * 40 + arcs[1]) as u8];
+
+    for arc in &arcs[2..] {
+        let mut chunk = vec![(arc & 0x7f) as u8];
+        let mut rest = arc >> 7;
+        while rest > 0 {
+            chunk.insert(0, 0x80 | (rest & 0x7f) as u8);
+            rest >>= 7;
+        }
+        content.extend(chunk);
+    }
+
+    tlv(TAG_OID, &content)
+}
+
+/// AlgorithmIdentifier with NULL parameters
+pub fn algorithm(dotted: &str) -> Vec<u8> {
+    sequence(&[oid(dotted), null()])
+}
+
+/// Parse a single element, returning it and the remaining data
+pub fn parse(data: &[u8]) -> Result<(Tlv<'_>, &[u8])> {
+    if data.len() < 2 {
+        bail!("DER data truncated");
+    }
+
+    let tag = data[0];
+    if tag & 0x1f == 0x1f {
+        bail!("Multi-byte DER tags are not supported");
+    }
+
+    let (len, header_len) = if data[1] < 0x80 {
+        (data[1] as usize, 2)
+    } else {
+        let num = (data[1] & 0x7f) as usize;
+        if num == 0 || num > 8 || data.len() < 2 + num {
+            bail!("Invalid DER length");
+        }
+        let len = data[2..2 + num].iter().fold(0usize, |acc, b| (acc << 8) | *b as usize);
+        (len, 2 + num)
+    };
+
+    if data.len() - header_len < len {
+        bail!("DER data truncated");
+    }
+
+    let end = header_len + len;
+    Ok((
+        Tlv {
+            tag,
+            content: &data[header_len..end],
+            raw: &data[..end],
+        },
+        &data[end..],
+    ))
+}
+
+/// Parse all elements in a buffer (e.g. contents of a SEQUENCE)
+pub fn parse_all(mut data: &[u8]) -> Result<Vec<Tlv<'_>>> {
+    let mut elements = Vec::new();
+    while !data.is_empty() {
+        let (element, rest) = parse(data)?;
+        elements.push(element);
+        data = rest;
+    }
+    Ok(elements)
+}
+
+impl<'a> Tlv<'a> {
+    pub fn children(&self) -> Result<Vec<Tlv<'a>>> {
+        parse_all(self.content)
+    }
+
+    pub fn expect(self, tag: u8) -> Result<Self> {
+        if self.tag != tag {
+            bail!("Unexpected DER tag 0x{:02x} (expected 0x{:02x})", self.tag, tag);
+        }
+        Ok(self)
+    }
+
+    /// Decode object identifier to dotted string
+    pub fn oid_string(&self) -> Result<String> {
+        if self.tag != TAG_OID || self.content.is_empty() {
+            bail!("Element is not an OID");
+        }
+
+        let mut arcs = vec![(self.content[0] / 40) as u64, (self.content[0] % 40) as u64];
+        let mut value = 0u64;
+        for byte in &self.content[1..] {
+            value = (value << 7) | (byte & 0x7f) as u64;
+            if byte & 0x80 == 0 {
+                arcs.push(value);
+                value = 0;
+            }
+        }
+
+        Ok(arcs.iter().map(|a| a.to_string()).collect::<Vec<_>>().join("."))
+    }
+}
+
+/// Decode PEM file contents with the specified label (e.g. "CERTIFICATE") to a list of DER blobs
+pub fn pem_decode(pem: &str, label: &str) -> Result<Vec<Vec<u8>>> {
+    use base64::prelude::*;
+
+    let begin = format!("-----BEGIN {label}-----");
+    let end = format!("-----END {label}-----");
+    let mut blobs = Vec::new();
+    let mut current: Option<String> = None;
+
+    for line in pem.lines().map(str::trim) {
+        if line == begin {
+            current = Some(String::new());
+        } else if line == end {
+            if let Some(b64) = current.take() {
+                blobs.push(BASE64_STANDARD.decode(b64)?);
+            }
+        } else if let Some(b64) = current.as_mut() {
+            b64.push_str(line);
+        }
+    }
+
+    Ok(blobs)
+}
+
+#[cfg(test)]
+mod der_tests {
+    use super::*;
+
+    #[test]
+    fn test_der_roundtrip() {
+        // rsaEncryption
+        let encoded = oid("1.2.840.113549.1.1.1");
+        assert_eq!(
+            encoded,
+            [0x06, 0x09, 0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x01, 0x01]
+        );
+        let (parsed, rest) = parse(&encoded).unwrap();
+        assert!(rest.is_empty());
+        assert_eq!(parsed.oid_string().unwrap(), "1.2.840.113549.1.1.1");
+
+        // Long-form length and nested parsing
+        let long = octet_string(&[0xaa; 300]);
+        assert_eq!(&long[..4], &[0x04, 0x82, 0x01, 0x2c]);
+        let seq = sequence(&[small_integer(128), long.clone()]);
+        let children = parse(&seq).unwrap().0.children().unwrap();
+        assert_eq!(children.len(), 2);
+        assert_eq!(children[0].content, &[0x00, 0x80]);
+        assert_eq!(children[1].raw, long.as_slice());
+
+        // SET OF is sorted
+        assert_eq!(
+            set(&[small_integer(2), small_integer(1)]),
+            [0x31, 0x06, 2, 1, 1, 2, 1, 2]
+        );
+    }
+}
@@ -0,0 +1,106 @@
+use std::fs;
+use std::path::Path;
+
+use anyhow::Result;
+use hashbrown::HashSet;
+
+// Content-defined chunking parameters (average chunk size of ~4 KiB)
+const MIN_CHUNK: usize = 512;
+const MAX_CHUNK: usize = 65536;
+const CHUNK_MASK: u64 = 0xfff << 52;
+const ZSTD_LEVEL: i32 = 3;
+
+const GEAR: [u64; 256] = gear_table();
+
+/// Generate pseudo-random table for the gear rolling hash (splitmix64)
+const fn gear_table() -> [u64; 256] {
+    let mut table = [0u64; 256];
+    let mut state: u64 = 0;
+    let mut i = 0;
+    while i < 256 {
+        state = state.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        table[i] = z ^ (z >> 31);
+        i += 1;
+    }
+    table
+}
+
+/// Split data into chunks at content-defined boundaries, so that insertions
+/// or removals only affect the chunks around them.
+fn chunks(data: &[u8]) -> Vec<&[u8]> {
+    let mut chunks = Vec::new();
+    let mut start = 0;
+    let mut hash: u64 = 0;
+
+    for (pos, byte) in data.iter().enumerate() {
+        hash = (hash << 1).wrapping_add(GEAR[*byte as usize]);
+        let len = pos + 1 - start;
+        if (len >= MIN_CHUNK && hash & CHUNK_MASK == 0) || len >= MAX_CHUNK {
+            chunks.push(&data[start..=pos]);
+            start = pos + 1;
+            hash = 0;
+        }
+    }
+    if start < data.len() {
+        chunks.push(&data[start..]);
+    }
+
+    chunks
+}
+
+/// Estimate the size of a delta patch without creating it.
+/// Data of the new file that does not appear in the old one is compressed, the result is
+/// usually larger than the actual patch, but in the same order of magnitude.
+pub fn estimate_patch_size(old: &[u8], new: &[u8]) -> Result<u64> {
+    let known: HashSet<&[u8]> = chunks(old).into_iter().collect();
+    let mut changed: Vec<u8> = Vec::new();
+    for chunk in chunks(new) {
+        if !known.contains(chunk) {
+            changed.extend_from_slice(chunk);
+        }
+    }
+
+    Ok(zstd::bulk::compress(&changed, ZSTD_LEVEL)?.len() as u64)
+}
+
+pub fn estimate_patch_size_files(old: &Path, new: &Path) -> Result<u64> {
+    estimate_patch_size(&fs::read(old)?, &fs::read(new)?)
+}
+
+#[cfg(test)]
+mod estimate_tests {
+    use super::*;
+
+    fn random_data(len: usize, seed: u64) -> Vec<u8> {
+        let mut state = seed;
+        (0..len)
+            .map(|_| {
+                state = state
+                    .wrapping_mul(6364136223846793005)
+                    .wrapping_add(1442695040888963407);
+                (state >> 56) as u8
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_estimate() {
+        let old = random_data(1 << 20, 1);
+        let unrelated = random_data(1 << 20, 2);
+        // Insert and modify some data, shifting everything after it
+        let mut new = old.clone();
+        new.splice(1000..1000, random_data(100, 3));
+        new[500_000..500_050].copy_from_slice(&random_data(50, 4));
+
+        let identical = estimate_patch_size(&old, &old).unwrap();
+        let modified = estimate_patch_size(&old, &new).unwrap();
+        let replaced = estimate_patch_size(&old, &unrelated).unwrap();
+
+        assert!(identical < 100);
+        assert!(modified < 64 * 1024);
+        assert!(replaced > 1 << 19);
+    }
+}
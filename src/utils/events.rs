@@ -0,0 +1,201 @@
+use std::fs::File;
+use std::io::{LineWriter, Write};
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+use anyhow::{Context, Result};
+use hashbrown::HashMap;
+use serde::Serialize;
+
+use crate::models::journal::Stage;
+use crate::utils::progress::{Event, ProgressHandler};
+
+/// Milliseconds since the Unix epoch, used as timestamp for JSON logs and events
+pub fn timestamp_ms() -> u128 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis()
+}
+
+/// Serialise event to a single line of JSON with the current timestamp
+pub fn json_line<T: Serialize>(event: T) -> Option<String> {
+    #[derive(Serialize)]
+    struct Line<T: Serialize> {
+        timestamp: u128,
+        #[serde(flatten)]
+        event: T,
+    }
+
+    serde_json::to_string(&Line {
+        timestamp: timestamp_ms(),
+        event,
+    })
+    .ok()
+}
+
+#[derive(Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+enum ProgressEvent<'a> {
+    StageStarted {
+        stage: Stage,
+    },
+    StageFinished {
+        stage: Stage,
+        duration_ms: Option<u128>,
+    },
+    TaskStarted {
+        task: &'a str,
+        total: u64,
+    },
+    TaskFinished {
+        task: &'a str,
+        processed: u64,
+        duration_ms: u128,
+    },
+}
+
+/// Writes progress and events as JSON objects, one per line
+pub struct JsonEvents {
+    out: Mutex<Box<dyn Write + Send>>,
+    /// Whether to include logged warnings/errors (not needed if logs are written to the same stream)
+    include_logs: bool,
+    stages: Mutex<HashMap<Stage, Instant>>,
+    tasks: Mutex<HashMap<String, (Instant, u64)>>,
+}
+
+impl JsonEvents {
+    pub fn new(out: Box<dyn Write + Send>, include_logs: bool) -> Self {
+        Self {
+            out: Mutex::new(out),
+            include_logs,
+            stages: Mutex::new(HashMap::new()),
+            tasks: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn to_file(path: &Path) -> Result<Self> {
+        let f = File::create(path).context(format!("Failed creating events file {path:?}"))?;
+        // Flush every line so the file can be followed while the run is in progress
+        Ok(Self::new(Box::new(LineWriter::new(f)), true))
+    }
+
+    /// Write events to stderr, alongside JSON-formatted logs
+    pub fn stderr() -> Self {
+        Self::new(Box::new(std::io::stderr()), false)
+    }
+
+    fn write<T: Serialize>(&self, event: T) {
+        // Failing to write events should not fail the run
+        if let Some(line) = json_line(event) {
+            let _ = writeln!(self.out.lock().unwrap(), "{line}");
+        }
+    }
+}
+
+impl ProgressHandler for JsonEvents {
+    fn task_started(&self, task: &str, total: u64) {
+        self.tasks.lock().unwrap().insert(task.to_string(), (Instant::now(), 0));
+        self.write(ProgressEvent::TaskStarted { task, total });
+    }
+
+    fn task_progress(&self, task: &str, position: u64, _total: u64) {
+        if let Some((_, pos)) = self.tasks.lock().unwrap().get_mut(task) {
+            *pos = position.max(*pos);
+        }
+    }
+
+    fn task_finished(&self, task: &str) {
+        let Some((start, processed)) = self.tasks.lock().unwrap().remove(task) else {
+            return;
+        };
+        self.write(ProgressEvent::TaskFinished {
+            task,
+            processed,
+            duration_ms: start.elapsed().as_millis(),
+        });
+    }
+
+    fn stage_started(&self, stage: Stage) {
+        self.stages.lock().unwrap().insert(stage, Instant::now());
+        self.write(ProgressEvent::StageStarted { stage });
+    }
+
+    fn stage_finished(&self, stage: Stage) {
+        let duration_ms = self
+            .stages
+            .lock()
+            .unwrap()
+            .remove(&stage)
+            .map(|s| s.elapsed().as_millis());
+        self.write(ProgressEvent::StageFinished { stage, duration_ms });
+    }
+
+    fn event(&self, event: &Event) {
+        if !self.include_logs && matches!(event, Event::Log { .. }) {
+            return;
+        }
+        self.write(event);
+    }
+}
+
+#[cfg(test)]
+mod events_tests {
+    use super::*;
+    use std::sync::Arc;
+
+    #[derive(Clone, Default)]
+    struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Buffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_events() {
+        let buffer = Buffer::default();
+        let events = JsonEvents::new(Box::new(buffer.clone()), false);
+
+        events.stage_started(Stage::Patches);
+        events.task_started("patch", 2);
+        events.task_progress("patch", 2, 2);
+        events.task_finished("patch");
+        events.event(&Event::Log {
+            level: "warn",
+            message: "skipped",
+        });
+        events.event(&Event::PatchCreated {
+            name: "obs64.exe",
+            package: "core",
+            old_hash: "abc",
+            old_size: 100,
+            new_size: 120,
+            patch_size: 10,
+        });
+        events.stage_finished(Stage::Patches);
+
+        let data = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+        let lines: Vec<serde_json::Value> = data.lines().map(|l| serde_json::from_str(l).unwrap()).collect();
+        let names: Vec<&str> = lines.iter().map(|l| l["event"].as_str().unwrap()).collect();
+        assert_eq!(
+            names,
+            [
+                "stage_started",
+                "task_started",
+                "task_finished",
+                "patch_created",
+                "stage_finished"
+            ]
+        );
+        assert_eq!(lines[0]["stage"], "patches");
+        assert_eq!(lines[2]["processed"], 2);
+        assert_eq!(lines[3]["patch_size"], 10);
+        assert!(lines[4]["duration_ms"].is_u64());
+    }
+}
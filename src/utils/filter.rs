@@ -0,0 +1,147 @@
+use anyhow::{anyhow, Result};
+use globset::{Glob, GlobMatcher};
+use regex::Regex;
+use serde::Deserialize;
+
+/// List of path patterns used by config filters.
+///
+/// Patterns are plain strings matched as substrings, or glob/regular expressions when prefixed
+/// with "glob:" or "re:". Prefixing a pattern with "!" negates it. A path matches the filter if it
+/// matches at least one pattern and none of the negated ones.
+#[derive(Deserialize, Default, Clone, Debug)]
+#[serde(from = "Vec<String>")]
+pub struct PathFilter {
+    rules: Vec<Rule>,
+    /// Error encountered when compiling patterns, reported by `validate()`
+    error: Option<String>,
+}
+
+#[derive(Clone, Debug)]
+struct Rule {
+    input: String,
+    pattern: Pattern,
+    negated: bool,
+}
+
+#[derive(Clone, Debug)]
+enum Pattern {
+    Substring(String),
+    Glob(GlobMatcher),
+    Regex(Regex),
+}
+
+impl Rule {
+    fn parse(input: &str) -> Result<Self> {
+        let (negated, pattern) = match input.strip_prefix('!') {
+            Some(rest) => (true, rest),
+            None => (false, input),
+        };
+
+        let pattern = if let Some(glob) = pattern.strip_prefix("glob:") {
+            Pattern::Glob(Glob::new(glob)?.compile_matcher())
+        } else if let Some(re) = pattern.strip_prefix("re:") {
+            Pattern::Regex(Regex::new(re)?)
+        } else {
+            Pattern::Substring(pattern.to_string())
+        };
+
+        Ok(Self {
+            input: input.to_string(),
+            pattern,
+            negated,
+        })
+    }
+
+    fn matches(&self, path: &str) -> bool {
+        match &self.pattern {
+            Pattern::Substring(s) => path.contains(s.as_str()),
+            Pattern::Glob(glob) => glob.is_match(path),
+            Pattern::Regex(re) => re.is_match(path),
+        }
+    }
+}
+
+impl From<Vec<String>> for PathFilter {
+    fn from(patterns: Vec<String>) -> Self {
+        let mut filter = Self::default();
+        filter.extend(patterns);
+        filter
+    }
+}
+
+impl PathFilter {
+    pub fn validate(&self) -> Result<()> {
+        match &self.error {
+            Some(e) => Err(anyhow!("{}", e)),
+            None => Ok(()),
+        }
+    }
+
+    /// Add patterns to filter
+    pub fn extend(&mut self, patterns: Vec<String>) {
+        for pattern in patterns {
+            match Rule::parse(&pattern) {
+                Ok(rule) => self.rules.push(rule),
+                Err(e) => {
+                    if self.error.is_none() {
+                        self.error = Some(format!("Invalid pattern \"{pattern}\": {e}"));
+                    }
+                }
+            }
+        }
+    }
+
+    /// Check if (Unix-style relative) path matches the filter
+    pub fn matches(&self, path: &str) -> bool {
+        self.find_match(path).is_some()
+    }
+
+    /// Return the first pattern matching the path (if the path matches the filter)
+    pub fn find_match(&self, path: &str) -> Option<&str> {
+        let mut matched = None;
+        for rule in &self.rules {
+            if rule.matches(path) {
+                if rule.negated {
+                    return None;
+                }
+                matched = matched.or(Some(rule.input.as_str()));
+            }
+        }
+        matched
+    }
+}
+
+#[cfg(test)]
+mod filter_tests {
+    use super::*;
+
+    fn create_filter(patterns: &[&str]) -> Result<PathFilter> {
+        let filter = PathFilter::from(patterns.iter().map(|p| p.to_string()).collect::<Vec<_>>());
+        filter.validate()?;
+        Ok(filter)
+    }
+
+    #[test]
+    fn test_filter() {
+        // Plain strings are substring matches
+        let filter = create_filter(&["obs.dll"]).unwrap();
+        assert!(filter.matches("bin/64bit/obs.dll"));
+        assert!(filter.matches("bin/64bit/libobs.dll.bak"));
+
+        let filter = create_filter(&["glob:**/obs.dll", "re:^data/.*\\.effect$", "!re:^data/libobs/"]).unwrap();
+        assert!(filter.matches("bin/64bit/obs.dll"));
+        assert!(!filter.matches("bin/64bit/libobs.dll"));
+        assert!(filter.matches("data/obs-plugins/obs-filters/blur.effect"));
+        assert!(!filter.matches("data/libobs/default.effect"));
+        assert_eq!(filter.find_match("data/foo.effect"), Some("re:^data/.*\\.effect$"));
+
+        // Negated patterns alone never match
+        let filter = create_filter(&["!foo"]).unwrap();
+        assert!(!filter.matches("bar"));
+        assert!(!PathFilter::default().matches("bar"));
+
+        // Invalid patterns are reported by validation
+        assert!(create_filter(&["re:("]).is_err());
+        assert!(create_filter(&["glob:a[b"]).is_err());
+    }
+}
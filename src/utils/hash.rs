@@ -8,16 +8,20 @@ use std::os::unix::fs::MetadataExt;
 #[cfg(windows)]
 use std::os::windows::fs::MetadataExt;
 
+use anyhow::Result;
 use blake2::digest::{Update, VariableOutput};
 use blake2::Blake2bVar;
 use hashbrown::HashMap;
-use indicatif::{ParallelProgressIterator, ProgressBar, ProgressFinish, ProgressStyle};
 use log::{info, warn};
 use object::{Object, ObjectSection};
-use rayon::iter::{IntoParallelRefMutIterator, ParallelIterator};
+use rayon::iter::{IntoParallelRefIterator, IntoParallelRefMutIterator, ParallelIterator};
+use rsa::sha2::{Digest, Sha256};
 use serde::{Deserialize, Serialize};
 use walkdir::{DirEntry, WalkDir};
 
+use crate::models::config::LayoutOptions;
+use crate::utils::progress::Task;
+
 const BLAKE2_HASH_SIZE: usize = 20;
 const READ_BUFSIZE: usize = usize::pow(2, 16);
 const BINARY_EXTS: [&str; 3] = ["exe", "pyd", "dll"];
@@ -73,11 +77,40 @@ pub fn hash_file(path: &Path) -> FileInfo {
     create_file_info(s, &file)
 }
 
-pub fn get_dir_hashes(path: &PathBuf, cache: Option<HashMap<String, FileInfo>>) -> HashMap<String, FileInfo> {
+/// Create hex-encoded SHA-256 digest of file
+pub fn hash_file_sha256(path: &Path) -> Result<String> {
+    let mut file = File::open(path)?;
+    let mut hasher = Sha256::new();
+
+    let mut read_buf = [0u8; READ_BUFSIZE];
+    loop {
+        let read = file.read(&mut read_buf)?;
+        if read == 0 {
+            break;
+        }
+        Digest::update(&mut hasher, &read_buf[0..read]);
+    }
+
+    let mut s = String::with_capacity(64);
+    for byte in hasher.finalize() {
+        write!(s, "{byte:02x}").unwrap();
+    }
+
+    Ok(s)
+}
+
+/// Create a list of file hashes in a directory.
+/// Only files that are part of the build layout are included, if no layout is specified
+/// all files in subdirectories are (e.g. for the directory containing all old builds).
+pub fn get_dir_hashes(
+    path: &PathBuf,
+    cache: Option<HashMap<String, FileInfo>>,
+    layout: Option<&LayoutOptions>,
+) -> HashMap<String, FileInfo> {
     let mut hashes: HashMap<String, FileInfo> = HashMap::new();
 
     for file in WalkDir::new(path)
-        .min_depth(2)
+        .min_depth(if layout.is_some() { 1 } else { 2 })
         .into_iter()
         .filter_map(Result::ok)
         .filter(|e| !e.file_type().is_dir())
@@ -88,6 +121,10 @@ pub fn get_dir_hashes(path: &PathBuf, cache: Option<HashMap<String, FileInfo>>)
         // Internally we always use Unix-style paths, so adjust this here
         let relative_path_str = String::from(relative_path).replace('\\', "/");
 
+        if layout.is_some_and(|l| !l.includes(&relative_path_str)) {
+            continue;
+        }
+
         if let Some(_cache_entry) = cache.as_ref().and_then(|_cache| _cache.get(&relative_path_str)) {
             hashes.insert(relative_path_str, _cache_entry.to_owned());
         } else {
@@ -105,25 +142,37 @@ pub fn get_dir_hashes(path: &PathBuf, cache: Option<HashMap<String, FileInfo>>)
     }
 
     info!(" => Hashing {num} files.");
-    let style = ProgressStyle::with_template("[{elapsed_precise}] {bar:40.cyan/blue} {pos:>7}/{len:7} {msg}").unwrap();
-    let pbar = ProgressBar::new(num)
-        .with_style(style)
-        .with_finish(ProgressFinish::AndLeave);
+    let task = Task::start("hash", num);
     hashes
         .par_iter_mut()
         .filter(|(_, v)| v.hash.is_empty())
-        .progress_with(pbar)
         .for_each(|(f_path, fileinfo)| {
             *fileinfo = hash_file(path.join(Path::new(f_path)).as_path());
+            task.inc();
         });
 
     hashes
 }
 
+/// Create a list of hashes for files (relative path => file path) that may be located in different directories
+pub fn get_file_hashes(files: &HashMap<String, PathBuf>) -> HashMap<String, FileInfo> {
+    info!(" => Hashing {} files.", files.len());
+    let task = Task::start("hash", files.len() as u64);
+
+    files
+        .par_iter()
+        .map(|(name, path)| {
+            let info = hash_file(path);
+            task.inc();
+            (name.to_owned(), info)
+        })
+        .collect()
+}
+
 /// Create a list of file hashes in a directory, loading existing results from a
-/// "cache.json" file inside that directory (if it exists)
+/// "cache.json" file inside that directory (if it exists), and updating it if requested.
 /// Error reading/writing a cache file are ignored.
-pub fn get_dir_hashes_cache(path: &PathBuf) -> HashMap<String, FileInfo> {
+pub fn get_dir_hashes_cache(path: &PathBuf, update_cache: bool) -> HashMap<String, FileInfo> {
     let cache_file = path.join("cache.json");
 
     let cache: Option<HashMap<String, FileInfo>> = File::open(cache_file.as_path()).ok().and_then(|f| {
@@ -135,7 +184,10 @@ pub fn get_dir_hashes_cache(path: &PathBuf) -> HashMap<String, FileInfo> {
         info!("No cache found.");
     }
 
-    let hashes = get_dir_hashes(path, cache);
+    let hashes = get_dir_hashes(path, cache, None);
+    if !update_cache {
+        return hashes;
+    }
 
     let file_written = serde_json::to_string_pretty(&hashes).ok().and_then(|j| {
         File::create(cache_file.as_path())
@@ -150,8 +202,58 @@ pub fn get_dir_hashes_cache(path: &PathBuf) -> HashMap<String, FileInfo> {
     hashes
 }
 
+/// Create hex-encoded BLAKE2 digest of a string
+pub fn hash_string(data: &str) -> String {
+    let mut hasher = Blake2bVar::new(BLAKE2_HASH_SIZE).unwrap();
+    hasher.update(data.as_bytes());
+
+    let mut buf = [0u8; BLAKE2_HASH_SIZE];
+    hasher.finalize_variable(&mut buf).unwrap();
+
+    let mut s = String::with_capacity(2 * BLAKE2_HASH_SIZE);
+    for byte in buf {
+        write!(s, "{byte:02x}").unwrap();
+    }
+    s
+}
+
+/// Create a fingerprint of a file or directory based on file names, sizes, and modification times
+/// (without reading any file contents). Top-level entries listed in `skip` are ignored.
+pub fn get_path_fingerprint(path: &Path, skip: &[&str]) -> String {
+    let mut listing = String::new();
+
+    for entry in WalkDir::new(path)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_entry(|e| e.depth() != 1 || !skip.iter().any(|s| e.file_name() == *s))
+        .filter_map(Result::ok)
+        .filter(|e| !e.file_type().is_dir())
+    {
+        let relative_path = entry
+            .path()
+            .strip_prefix(path)
+            .unwrap()
+            .to_string_lossy()
+            .replace('\\', "/");
+        let (size, mtime) = match entry.metadata() {
+            Ok(meta) => (
+                meta.len(),
+                meta.modified()
+                    .ok()
+                    .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+                    .unwrap_or_default()
+                    .as_nanos(),
+            ),
+            Err(_) => (0, 0),
+        };
+        writeln!(listing, "{relative_path}:{size}:{mtime}").unwrap();
+    }
+
+    hash_string(&listing)
+}
+
 // ToDo make all of this stuff return results
-fn hash_file_code(path: &Path) -> FileInfo {
+pub fn hash_file_code(path: &Path) -> FileInfo {
     let mut file = File::open(path).expect("Unable to open file");
     let mut buf = Vec::new();
     let mut hash_buf = [0u8; 20];
@@ -175,11 +277,10 @@ fn hash_file_code(path: &Path) -> FileInfo {
     create_file_info(s, &file)
 }
 
-pub fn get_dir_code_hashes(path: &PathBuf) -> HashMap<String, FileInfo> {
+pub fn get_dir_code_hashes(path: &PathBuf, layout: &LayoutOptions) -> HashMap<String, FileInfo> {
     let mut hashes: HashMap<String, FileInfo> = HashMap::new();
 
     for file in WalkDir::new(path)
-        .min_depth(2)
         .into_iter()
         .filter_map(Result::ok)
         .filter(|e| !e.file_type().is_dir())
@@ -190,7 +291,7 @@ pub fn get_dir_code_hashes(path: &PathBuf) -> HashMap<String, FileInfo> {
         // Internally we always use Unix-style paths, so adjust this here
         let relative_path_str = String::from(relative_path).replace('\\', "/");
 
-        if !BINARY_EXTS.iter().any(|ext| relative_path_str.ends_with(ext)) {
+        if !layout.includes(&relative_path_str) || !BINARY_EXTS.iter().any(|ext| relative_path_str.ends_with(ext)) {
             continue;
         }
 
@@ -200,16 +301,13 @@ pub fn get_dir_code_hashes(path: &PathBuf) -> HashMap<String, FileInfo> {
     let num = hashes.iter().filter(|(_, v)| v.hash.is_empty()).count() as u64;
 
     info!(" => Hashing {num} files' code sections...");
-    let style = ProgressStyle::with_template("[{elapsed_precise}] {bar:40.cyan/blue} {pos:>7}/{len:7} {msg}").unwrap();
-    let pbar = ProgressBar::new(num)
-        .with_style(style)
-        .with_finish(ProgressFinish::AndLeave);
+    let task = Task::start("hash_code", num);
     hashes
         .par_iter_mut()
         .filter(|(_, v)| v.hash.is_empty())
-        .progress_with(pbar)
         .for_each(|(f_path, fileinfo)| {
             *fileinfo = hash_file_code(path.join(Path::new(f_path)).as_path());
+            task.inc();
         });
 
     hashes
@@ -224,4 +322,25 @@ mod hash_tests {
         let finfo = hash_file(Path::new("extra/test_files/in.txt"));
         assert_eq!(finfo.hash, "ea08af20e468ff39054c5832b26ee2d80f467045");
     }
+
+    #[test]
+    fn test_sha256() {
+        let hash = hash_file_sha256(Path::new("extra/test_files/in.txt")).unwrap();
+        assert_eq!(hash, "f29bc64a9d3732b4b9035125fdb3285f5b6455778edca72414671e0ca3b2e0de");
+    }
+
+    #[test]
+    fn test_layout() {
+        let mut layout = LayoutOptions::default();
+        assert!(layout.includes("bin/64bit/obs64.exe"));
+        assert!(layout.includes("obs-plugins/64bit/obs-browser.dll"));
+        assert!(!layout.includes("binaries/foo.dll"));
+        assert!(!layout.includes("licenses/LICENSE"));
+        assert!(!layout.includes("README.txt"));
+
+        layout.roots.push("licenses".to_string());
+        layout.top_level_files = true;
+        assert!(layout.includes("licenses/LICENSE"));
+        assert!(layout.includes("README.txt"));
+    }
 }
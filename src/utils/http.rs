@@ -0,0 +1,55 @@
+use std::io::{Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+use anyhow::{anyhow, bail, Context, Result};
+
+const TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Minimal HTTP/1.0 POST request, only plain HTTP is supported.
+/// (This is used for RFC 3161 timestamp servers, which are usually not using TLS anyway)
+pub fn post(url: &str, content_type: &str, body: &[u8]) -> Result<Vec<u8>> {
+    let Some(location) = url.strip_prefix("http://") else {
+        bail!("Unsupported URL \"{}\" (only http:// is supported)", url);
+    };
+    let (host, path) = match location.find('/') {
+        Some(idx) => (&location[..idx], &location[idx..]),
+        None => (location, "/"),
+    };
+    let addr = if host.contains(':') {
+        host.to_string()
+    } else {
+        format!("{host}:80")
+    };
+
+    let socket_addr = addr
+        .to_socket_addrs()?
+        .next()
+        .ok_or_else(|| anyhow!("Could not resolve \"{}\"", host))?;
+    let mut stream =
+        TcpStream::connect_timeout(&socket_addr, TIMEOUT).with_context(|| format!("Connecting to {url}"))?;
+    stream.set_read_timeout(Some(TIMEOUT))?;
+    stream.set_write_timeout(Some(TIMEOUT))?;
+
+    let header = format!(
+        "POST {path} HTTP/1.0\r\nHost: {host}\r\nUser-Agent: bouf\r\nContent-Type: {content_type}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        body.len()
+    );
+    stream.write_all(header.as_bytes())?;
+    stream.write_all(body)?;
+
+    let mut response = Vec::new();
+    stream.read_to_end(&mut response)?;
+
+    let header_end = response
+        .windows(4)
+        .position(|w| w == b"\r\n\r\n")
+        .ok_or_else(|| anyhow!("Invalid HTTP response"))?;
+    let headers = String::from_utf8_lossy(&response[..header_end]);
+    let status = headers.split_whitespace().nth(1).unwrap_or_default();
+    if status != "200" {
+        bail!("Server returned HTTP status {}", status);
+    }
+
+    Ok(response[header_end + 4..].to_vec())
+}
@@ -1,20 +1,44 @@
 use env_logger::{Builder, Env};
+use log::Level;
 use std::io::Write;
 
-pub fn init_logger(level: &str) {
+use crate::models::args::LogFormat;
+use crate::utils::events::json_line;
+use crate::utils::progress::{emit, Event};
+
+pub fn init_logger(level: &str, format: LogFormat) {
     let env = Env::default()
         .filter_or("BOUF_LOG_LEVEL", level)
         .write_style_or("BOUF_LOG_STYLE", "always");
 
     Builder::from_env(env)
-        .format(|buf, record| {
-            writeln!(
-                buf,
-                "[{}] {}: {}",
-                buf.timestamp(),
-                buf.default_styled_level(record.level()),
-                record.args()
-            )
+        .format(move |buf, record| {
+            let message = record.args().to_string();
+            let level = record.level().as_str().to_lowercase();
+            // Forward warnings and errors to the progress handler (e.g. for the events file)
+            if record.level() <= Level::Warn {
+                emit(Event::Log {
+                    level: &level,
+                    message: &message,
+                });
+            }
+
+            match format {
+                LogFormat::Text => writeln!(
+                    buf,
+                    "[{}] {}: {}",
+                    buf.timestamp(),
+                    buf.default_styled_level(record.level()),
+                    message
+                ),
+                LogFormat::Json => {
+                    let line = json_line(Event::Log {
+                        level: &level,
+                        message: &message,
+                    });
+                    writeln!(buf, "{}", line.unwrap_or_default())
+                }
+            }
         })
         .init();
 }
@@ -1,11 +1,31 @@
 use std::fs;
+use std::fs::File;
+use std::io::{BufReader, Read};
 use std::path::{Component, Path, PathBuf};
 use std::process::Command;
 
 use anyhow::{bail, Context, Result};
+use walkdir::WalkDir;
 
 use crate::models::config::ObsVersion;
 
+/// List all files in a directory as names relative to it (with "/" separators) and full paths
+pub fn list_dir(dir: &Path) -> Result<Vec<(String, PathBuf)>> {
+    if !dir.is_dir() {
+        bail!("Directory {dir:?} does not exist");
+    }
+
+    Ok(WalkDir::new(dir)
+        .into_iter()
+        .filter_map(Result::ok)
+        .filter(|e| !e.file_type().is_dir())
+        .map(|e| {
+            let name = e.path().strip_prefix(dir).unwrap().to_string_lossy().replace('\\', "/");
+            (name, e.into_path())
+        })
+        .collect())
+}
+
 /// Parses a version string such as "28.0.0-rc1" to version struct
 pub fn parse_version(version_string: &String) -> Result<ObsVersion> {
     let parts: Vec<&str> = version_string.split('-').collect();
@@ -33,6 +53,30 @@ pub fn parse_version(version_string: &String) -> Result<ObsVersion> {
     Ok(version)
 }
 
+/// Format size in bytes using binary units (e.g. "1.5 MiB")
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KiB", "MiB", "GiB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+
+    if unit == 0 {
+        format!("{bytes} B")
+    } else {
+        format!("{size:.1} {}", UNITS[unit])
+    }
+}
+
+pub fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
 /// Get the version string used in filenames, optionally as a short version
 /// (dropping the patch part if it is "0")
 pub fn get_filename_version(version: &ObsVersion, short: bool) -> String {
@@ -125,6 +169,75 @@ pub fn check_binary_path(path: &mut PathBuf) -> Result<()> {
     Ok(())
 }
 
+/// Find the offset of the first byte that differs between two files (or where the shorter one ends),
+/// returns `None` if both are identical
+pub fn first_difference(a: &Path, b: &Path) -> Result<Option<u64>> {
+    let mut reader_a = BufReader::new(File::open(a).context(format!("Failed opening {a:?}"))?);
+    let mut reader_b = BufReader::new(File::open(b).context(format!("Failed opening {b:?}"))?);
+    let mut buf_a = vec![0u8; 65536];
+    let mut buf_b = vec![0u8; 65536];
+    let mut offset: u64 = 0;
+
+    loop {
+        let read_a = read_full(&mut reader_a, &mut buf_a)?;
+        let read_b = read_full(&mut reader_b, &mut buf_b)?;
+        if let Some(pos) = buf_a[..read_a].iter().zip(&buf_b[..read_b]).position(|(x, y)| x != y) {
+            return Ok(Some(offset + pos as u64));
+        }
+        if read_a != read_b {
+            return Ok(Some(offset + read_a.min(read_b) as u64));
+        }
+        if read_a == 0 {
+            return Ok(None);
+        }
+        offset += read_a as u64;
+    }
+}
+
+/// Read until the buffer is full or the end of the file is reached
+fn read_full(reader: &mut impl Read, buf: &mut [u8]) -> Result<usize> {
+    let mut total = 0;
+    while total < buf.len() {
+        let read = reader.read(&mut buf[total..])?;
+        if read == 0 {
+            break;
+        }
+        total += read;
+    }
+    Ok(total)
+}
+
+/// Temporary directory for tests, removed together with its contents when dropped
+#[cfg(test)]
+pub struct TestDir(PathBuf);
+
+#[cfg(test)]
+impl TestDir {
+    /// Create an empty directory, the name must be unique among tests as they run in parallel
+    pub fn new(name: &str) -> Self {
+        let path = std::env::temp_dir().join(format!("bouf_{name}_test_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&path);
+        fs::create_dir_all(&path).unwrap();
+        Self(path)
+    }
+}
+
+#[cfg(test)]
+impl std::ops::Deref for TestDir {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+impl Drop for TestDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
 #[cfg(test)]
 mod misc_tests {
     use super::*;
@@ -179,4 +292,26 @@ mod misc_tests {
         let ver_short = get_filename_version(&version, false);
         assert_eq!(ver_short, "28.1.0-gabcdef12");
     }
+
+    #[test]
+    fn test_first_difference() {
+        let dir = TestDir::new("diff");
+        let data: Vec<u8> = (0..200_000u32).map(|i| i as u8).collect();
+        let mut changed = data.clone();
+        changed[100_000] ^= 0xff;
+
+        let write = |name: &str, data: &[u8]| {
+            let path = dir.join(name);
+            fs::write(&path, data).unwrap();
+            path
+        };
+        let original = write("original", &data);
+        let same = write("same", &data);
+        let modified = write("modified", &changed);
+        let truncated = write("truncated", &data[..70_000]);
+
+        assert_eq!(first_difference(&original, &same).unwrap(), None);
+        assert_eq!(first_difference(&original, &modified).unwrap(), Some(100_000));
+        assert_eq!(first_difference(&original, &truncated).unwrap(), Some(70_000));
+    }
 }
@@ -1,9 +1,19 @@
+pub mod authenticode;
 pub mod bsdiff;
+pub mod codesign;
+pub mod der;
+pub mod estimate;
+pub mod events;
+pub mod filter;
 pub mod hash;
+pub mod http;
 pub mod logging;
 pub mod misc;
+pub mod notes;
+pub mod progress;
 pub mod sign;
+pub mod tar;
+pub mod template;
+pub mod winget;
+pub mod zip;
 pub mod zstd;
-
-#[cfg(windows)]
-pub mod codesign;
@@ -0,0 +1,889 @@
+use hashbrown::HashMap;
+
+use crate::models::config::NotesFormat;
+use crate::utils::misc::escape_html;
+
+/// Block-level elements shared by the Markdown and RST parsers (text is unparsed inline markup)
+enum Block {
+    Heading(usize, String),
+    Paragraph(String),
+    List {
+        ordered: bool,
+        tight: bool,
+        items: Vec<Vec<Block>>,
+    },
+    Code(String),
+    Quote(Vec<Block>),
+    Rule,
+}
+
+/// Convert release notes to HTML (Markdown is treated as CommonMark, RST only supports a common subset)
+pub fn to_html(text: &str, format: NotesFormat) -> String {
+    let lines: Vec<String> = text
+        .replace("\r\n", "\n")
+        .replace('\t', "    ")
+        .lines()
+        .map(|l| l.trim_end().to_string())
+        .collect();
+
+    let mut html = String::new();
+    if format == NotesFormat::Markdown {
+        render(&parse_markdown(&lines), &markdown_inline, &mut html);
+    } else {
+        let mut parser = RstParser::default();
+        let blocks = parser.parse(&lines);
+        render(&blocks, &|text| parser.inline(text), &mut html);
+    }
+
+    html
+}
+
+fn indent(line: &str) -> usize {
+    line.len() - line.trim_start_matches(' ').len()
+}
+
+/// Remove up to `n` leading spaces
+fn dedent(line: &str, n: usize) -> String {
+    line[indent(line).min(n)..].to_string()
+}
+
+/// Check for a list item marker, returns whether the list is ordered and the content's indentation
+fn list_marker(line: &str, rst: bool) -> Option<(bool, usize)> {
+    let ind = indent(line);
+    let rest = &line[ind..];
+    let bytes = rest.as_bytes();
+
+    let (ordered, marker_len) = match bytes.first()? {
+        b'-' | b'*' | b'+' => (false, 1),
+        b'#' if rst && bytes.get(1) == Some(&b'.') => (true, 2),
+        _ => {
+            let digits = bytes.iter().take_while(|b| b.is_ascii_digit()).count();
+            if digits == 0 || digits > 9 || !matches!(bytes.get(digits), Some(b'.') | Some(b')')) {
+                return None;
+            }
+            (true, digits + 1)
+        }
+    };
+
+    let after = &rest[marker_len..];
+    if after.is_empty() {
+        return Some((ordered, ind + marker_len + 1));
+    }
+    let spaces = indent(after);
+    match spaces {
+        0 => None,
+        1..=4 => Some((ordered, ind + marker_len + spaces)),
+        // Content starting with more than four spaces is indented code, the item starts after one space
+        _ => Some((ordered, ind + marker_len + 1)),
+    }
+}
+
+/// Parse list starting at line `i` (whose items are parsed with `parse`), `lazy` allows unindented
+/// paragraph continuation lines as in Markdown
+fn parse_list(lines: &[String], i: &mut usize, lazy: bool, mut parse: impl FnMut(&[String]) -> Vec<Block>) -> Block {
+    let (ordered, _) = list_marker(&lines[*i], !lazy).unwrap();
+    let list_indent = indent(&lines[*i]);
+    let mut items = Vec::new();
+    let mut tight = true;
+
+    while *i < lines.len() {
+        let line = &lines[*i];
+        let Some((item_ordered, offset)) = list_marker(line, !lazy) else {
+            break;
+        };
+        if item_ordered != ordered || indent(line) != list_indent {
+            break;
+        }
+
+        let mut item_lines = vec![line.get(offset..).unwrap_or_default().to_string()];
+        let mut blank = false;
+        *i += 1;
+        while *i < lines.len() {
+            let line = &lines[*i];
+            if line.is_empty() {
+                blank = true;
+                item_lines.push(String::new());
+            } else if indent(line) >= offset {
+                blank = false;
+                item_lines.push(dedent(line, offset));
+            } else if lazy && !blank && list_marker(line, false).is_none() && !line.starts_with('#') {
+                item_lines.push(line.trim_start().to_string());
+            } else {
+                break;
+            }
+            *i += 1;
+        }
+
+        while item_lines.last().is_some_and(|l| l.is_empty()) {
+            item_lines.pop();
+        }
+        // Blank lines between items or between blocks of an item make the list loose (paragraphs in items)
+        if blank && *i < lines.len() && list_marker(&lines[*i], !lazy).is_some_and(|(o, _)| o == ordered) {
+            tight = false;
+        }
+        if item_lines.iter().any(|l| l.is_empty()) {
+            tight = false;
+        }
+        items.push(parse(&item_lines));
+    }
+
+    Block::List { ordered, tight, items }
+}
+
+fn render(blocks: &[Block], inline: &dyn Fn(&str) -> String, html: &mut String) {
+    for block in blocks {
+        match block {
+            Block::Heading(level, text) => {
+                html.push_str(&format!("<h{level}>{}</h{level}>\n", inline(text)));
+            }
+            Block::Paragraph(text) => html.push_str(&format!("<p>{}</p>\n", inline(text))),
+            Block::List { ordered, tight, items } => {
+                let tag = if *ordered { "ol" } else { "ul" };
+                html.push_str(&format!("<{tag}>\n"));
+                for item in items {
+                    let mut content = String::new();
+                    if *tight {
+                        // Paragraphs of tight lists are not wrapped in <p>
+                        for block in item {
+                            if !content.is_empty() {
+                                content.push('\n');
+                            }
+                            match block {
+                                Block::Paragraph(text) => content.push_str(&inline(text)),
+                                _ => render(std::slice::from_ref(block), inline, &mut content),
+                            }
+                        }
+                    } else {
+                        render(item, inline, &mut content);
+                    }
+                    html.push_str(&format!("<li>{}</li>\n", content.trim_end()));
+                }
+                html.push_str(&format!("</{tag}>\n"));
+            }
+            Block::Code(text) => html.push_str(&format!("<pre><code>{}</code></pre>\n", escape_html(text))),
+            Block::Quote(blocks) => {
+                html.push_str("<blockquote>\n");
+                render(blocks, inline, html);
+                html.push_str("</blockquote>\n");
+            }
+            Block::Rule => html.push_str("<hr />\n"),
+        }
+    }
+}
+
+/// Code block content, without trailing blank lines
+fn code_block(lines: &[String]) -> String {
+    let mut lines = lines.to_vec();
+    while lines.last().is_some_and(|l| l.is_empty()) {
+        lines.pop();
+    }
+    lines.join("\n")
+}
+
+/* Markdown */
+
+fn is_md_rule(line: &str) -> bool {
+    let chars: Vec<char> = line.chars().filter(|c| *c != ' ').collect();
+    indent(line) < 4 && chars.len() >= 3 && ['-', '*', '_'].contains(&chars[0]) && chars.iter().all(|c| *c == chars[0])
+}
+
+fn md_heading(line: &str) -> Option<(usize, String)> {
+    let trimmed = line.trim_start();
+    let level = trimmed.chars().take_while(|c| *c == '#').count();
+    if indent(line) >= 4 || level == 0 || level > 6 {
+        return None;
+    }
+
+    let text = &trimmed[level..];
+    if !text.is_empty() && !text.starts_with(' ') {
+        return None;
+    }
+    // Optional closing sequence
+    let text = text.trim();
+    let text = match text.trim_end_matches('#') {
+        t if t.is_empty() || t.ends_with(' ') => t.trim_end(),
+        _ => text,
+    };
+    Some((level, text.to_string()))
+}
+
+fn md_fence(line: &str) -> Option<&str> {
+    let trimmed = line.trim_start();
+    if indent(line) < 4 && (trimmed.starts_with("```") || trimmed.starts_with("~~~")) {
+        Some(&trimmed[..3])
+    } else {
+        None
+    }
+}
+
+/// Whether a line starts a block that interrupts a paragraph
+fn md_interrupts(line: &str) -> bool {
+    md_heading(line).is_some()
+        || md_fence(line).is_some()
+        || is_md_rule(line)
+        || line.trim_start().starts_with('>')
+        || list_marker(line, false).is_some_and(|(_, offset)| line.len() > offset)
+}
+
+fn parse_markdown(lines: &[String]) -> Vec<Block> {
+    let mut blocks = Vec::new();
+    let mut i = 0;
+
+    while i < lines.len() {
+        let line = &lines[i];
+        if line.is_empty() {
+            i += 1;
+            continue;
+        }
+
+        if let Some(fence) = md_fence(line) {
+            let fence_indent = indent(line);
+            let start = i + 1;
+            i = start;
+            while i < lines.len() && !lines[i].trim_start().starts_with(fence) {
+                i += 1;
+            }
+            let content: Vec<String> = lines[start..i].iter().map(|l| dedent(l, fence_indent)).collect();
+            blocks.push(Block::Code(content.join("\n")));
+            i += 1;
+        } else if indent(line) >= 4 {
+            let start = i;
+            while i < lines.len() && (lines[i].is_empty() || indent(&lines[i]) >= 4) {
+                i += 1;
+            }
+            let content: Vec<String> = lines[start..i].iter().map(|l| dedent(l, 4)).collect();
+            blocks.push(Block::Code(code_block(&content)));
+        } else if let Some((level, text)) = md_heading(line) {
+            blocks.push(Block::Heading(level, text));
+            i += 1;
+        } else if is_md_rule(line) {
+            blocks.push(Block::Rule);
+            i += 1;
+        } else if line.trim_start().starts_with('>') {
+            let mut content = Vec::new();
+            while i < lines.len() && !lines[i].is_empty() {
+                let trimmed = lines[i].trim_start();
+                let quoted = trimmed.strip_prefix('>').map(|l| l.strip_prefix(' ').unwrap_or(l));
+                content.push(quoted.unwrap_or(trimmed).to_string());
+                i += 1;
+            }
+            blocks.push(Block::Quote(parse_markdown(&content)));
+        } else if list_marker(line, false).is_some() {
+            blocks.push(parse_list(lines, &mut i, true, parse_markdown));
+        } else {
+            let mut text = vec![line.trim_start()];
+            i += 1;
+            let mut heading = None;
+            while i < lines.len() && !lines[i].is_empty() {
+                // Setext heading underline
+                let trimmed = lines[i].trim();
+                if indent(&lines[i]) < 4 && !trimmed.is_empty() {
+                    if trimmed.chars().all(|c| c == '=') {
+                        heading = Some(1);
+                    } else if trimmed.chars().all(|c| c == '-') {
+                        heading = Some(2);
+                    }
+                }
+                if heading.is_some() {
+                    i += 1;
+                    break;
+                }
+                if md_interrupts(&lines[i]) {
+                    break;
+                }
+                text.push(lines[i].trim_start());
+                i += 1;
+            }
+
+            let text = text.join("\n");
+            match heading {
+                Some(level) => blocks.push(Block::Heading(level, text)),
+                None => blocks.push(Block::Paragraph(text)),
+            }
+        }
+    }
+
+    blocks
+}
+
+fn run_length(chars: &[char], start: usize, c: char) -> usize {
+    chars[start..].iter().take_while(|&&x| x == c).count()
+}
+
+/// Find end of code span starting with a run of `n` backticks
+fn find_code_end(chars: &[char], start: usize, n: usize) -> Option<usize> {
+    let mut j = start;
+    while j < chars.len() {
+        if chars[j] == '`' {
+            let len = run_length(chars, j, '`');
+            if len == n {
+                return Some(j);
+            }
+            j += len;
+        } else {
+            j += 1;
+        }
+    }
+    None
+}
+
+/// Find closing emphasis delimiter run of exactly `n` characters
+fn find_emphasis_end(chars: &[char], start: usize, c: char, n: usize) -> Option<usize> {
+    let mut j = start;
+    while j < chars.len() {
+        match chars[j] {
+            '\\' => j += 2,
+            '`' => {
+                let len = run_length(chars, j, '`');
+                j = find_code_end(chars, j + len, len).map_or(j + len, |end| end + len);
+            }
+            x if x == c => {
+                let len = run_length(chars, j, c);
+                let intraword = c == '_' && chars.get(j + len).is_some_and(|x| x.is_alphanumeric());
+                if len == n && j > start && !chars[j - 1].is_whitespace() && !intraword {
+                    return Some(j);
+                }
+                j += len;
+            }
+            _ => j += 1,
+        }
+    }
+    None
+}
+
+/// Find closing bracket or parenthesis, taking nesting and escapes into account
+fn find_closing(chars: &[char], start: usize, open: char, close: char) -> Option<usize> {
+    let mut depth = 0;
+    let mut j = start;
+    while j < chars.len() {
+        match chars[j] {
+            '\\' => j += 1,
+            x if x == open => depth += 1,
+            x if x == close && depth == 0 => return Some(j),
+            x if x == close => depth -= 1,
+            _ => {}
+        }
+        j += 1;
+    }
+    None
+}
+
+fn push_escaped(html: &mut String, c: char) {
+    match c {
+        '&' => html.push_str("&amp;"),
+        '<' => html.push_str("&lt;"),
+        '>' => html.push_str("&gt;"),
+        '"' => html.push_str("&quot;"),
+        _ => html.push(c),
+    }
+}
+
+/// Line break within a paragraph, two trailing spaces (already trimmed from the text) or a backslash
+/// make it a hard break
+fn push_newline(html: &mut String, hard: bool) {
+    let trimmed = html.trim_end_matches(' ').len();
+    let hard = hard || html.len() - trimmed >= 2;
+    html.truncate(trimmed);
+    html.push_str(if hard { "<br />\n" } else { "\n" });
+}
+
+/// Parse link destination and optional title, e.g. `<url> "title"`
+fn link_destination(dest: &str) -> (String, Option<String>) {
+    let dest = dest.trim();
+    let (url, title) = match dest.split_once(char::is_whitespace) {
+        Some((url, title)) => (
+            url,
+            Some(title.trim().trim_matches(|c| c == '"' || c == '\'').to_string()),
+        ),
+        None => (dest, None),
+    };
+    let url = url.strip_prefix('<').and_then(|u| u.strip_suffix('>')).unwrap_or(url);
+    (url.to_string(), title)
+}
+
+fn markdown_inline(text: &str) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    let mut html = String::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        match c {
+            '\\' if chars.get(i + 1).is_some_and(|x| x.is_ascii_punctuation()) => {
+                push_escaped(&mut html, chars[i + 1]);
+                i += 2;
+            }
+            '\\' if chars.get(i + 1) == Some(&'\n') => {
+                push_newline(&mut html, true);
+                i += 2;
+            }
+            '\n' => {
+                push_newline(&mut html, false);
+                i += 1;
+            }
+            '`' => {
+                let n = run_length(&chars, i, '`');
+                match find_code_end(&chars, i + n, n) {
+                    Some(end) => {
+                        let code: String = chars[i + n..end].iter().collect();
+                        let code = code.replace('\n', " ");
+                        let code = match code.strip_prefix(' ').and_then(|c| c.strip_suffix(' ')) {
+                            Some(stripped) if !stripped.trim().is_empty() => stripped.to_string(),
+                            _ => code,
+                        };
+                        html.push_str(&format!("<code>{}</code>", escape_html(&code)));
+                        i = end + n;
+                    }
+                    None => {
+                        html.push_str(&"`".repeat(n));
+                        i += n;
+                    }
+                }
+            }
+            '*' | '_' => {
+                let n = run_length(&chars, i, c);
+                let intraword = c == '_' && i > 0 && chars[i - 1].is_alphanumeric();
+                let opens = chars.get(i + n).is_some_and(|x| !x.is_whitespace()) && !intraword;
+                let delim = n.min(2);
+                match opens.then(|| find_emphasis_end(&chars, i + delim, c, delim)).flatten() {
+                    Some(end) => {
+                        let inner: String = chars[i + delim..end].iter().collect();
+                        let tag = if delim == 2 { "strong" } else { "em" };
+                        html.push_str(&format!("<{tag}>{}</{tag}>", markdown_inline(&inner)));
+                        i = end + delim;
+                    }
+                    None => {
+                        html.push_str(&c.to_string().repeat(n));
+                        i += n;
+                    }
+                }
+            }
+            '!' | '[' => {
+                let image = c == '!';
+                let open = if image { i + 1 } else { i };
+                let link = (chars.get(open) == Some(&'['))
+                    .then(|| find_closing(&chars, open + 1, '[', ']'))
+                    .flatten()
+                    .filter(|&close| chars.get(close + 1) == Some(&'('))
+                    .and_then(|close| Some((close, find_closing(&chars, close + 2, '(', ')')?)));
+
+                match link {
+                    Some((close, end)) => {
+                        let label: String = chars[open + 1..close].iter().collect();
+                        let dest: String = chars[close + 2..end].iter().collect();
+                        let (url, title) = link_destination(&dest);
+                        let title = title.map_or(String::new(), |t| format!(" title=\"{}\"", escape_html(&t)));
+                        if image {
+                            html.push_str(&format!(
+                                "<img src=\"{}\" alt=\"{}\"{title} />",
+                                escape_html(&url),
+                                escape_html(&label)
+                            ));
+                        } else {
+                            html.push_str(&format!(
+                                "<a href=\"{}\"{title}>{}</a>",
+                                escape_html(&url),
+                                markdown_inline(&label)
+                            ));
+                        }
+                        i = end + 1;
+                    }
+                    None => {
+                        html.push(c);
+                        i += 1;
+                    }
+                }
+            }
+            '<' => {
+                // Autolink
+                let end = chars[i..]
+                    .iter()
+                    .position(|&x| x == '>' || x.is_whitespace())
+                    .map(|p| p + i);
+                let url: Option<String> = end
+                    .filter(|&end| chars[end] == '>')
+                    .map(|end| chars[i + 1..end].iter().collect())
+                    .filter(|url: &String| url.contains("://") || url.starts_with("mailto:"));
+                match url {
+                    Some(url) => {
+                        i += url.chars().count() + 2;
+                        let url = escape_html(&url);
+                        html.push_str(&format!("<a href=\"{url}\">{}</a>", url.trim_start_matches("mailto:")));
+                    }
+                    None => {
+                        html.push_str("&lt;");
+                        i += 1;
+                    }
+                }
+            }
+            _ => {
+                push_escaped(&mut html, c);
+                i += 1;
+            }
+        }
+    }
+
+    html
+}
+
+/* reStructuredText */
+
+const RST_ADORNMENT_CHARS: &str = "=-`:'\"~^_*+#<>.";
+const RST_ADMONITIONS: [&str; 9] = [
+    "attention",
+    "caution",
+    "danger",
+    "error",
+    "hint",
+    "important",
+    "note",
+    "tip",
+    "warning",
+];
+
+fn is_rst_adornment(line: &str) -> bool {
+    let Some(first) = line.chars().next() else {
+        return false;
+    };
+    RST_ADORNMENT_CHARS.contains(first) && line.chars().all(|c| c == first)
+}
+
+/// Normalise reference name (case-insensitive, whitespace-insensitive)
+fn rst_ref_name(name: &str) -> String {
+    name.split_whitespace().collect::<Vec<_>>().join(" ").to_lowercase()
+}
+
+#[derive(Default)]
+struct RstParser {
+    /// Heading styles (adornment character and whether there is an overline) in order of appearance
+    heading_styles: Vec<(char, bool)>,
+    /// Hyperlink targets (`.. _name: url`)
+    targets: HashMap<String, String>,
+}
+
+impl RstParser {
+    fn heading_level(&mut self, style: (char, bool)) -> usize {
+        let pos = match self.heading_styles.iter().position(|s| *s == style) {
+            Some(pos) => pos,
+            None => {
+                self.heading_styles.push(style);
+                self.heading_styles.len() - 1
+            }
+        };
+        (pos + 1).min(6)
+    }
+
+    /// Collect the indented block following line `i` (including blank lines in between), dedented
+    fn indented_block(lines: &[String], i: &mut usize) -> Vec<String> {
+        let start = *i;
+        while *i < lines.len() && (lines[*i].is_empty() || indent(&lines[*i]) > 0) {
+            *i += 1;
+        }
+        let block = &lines[start..*i];
+        let min_indent = block
+            .iter()
+            .filter(|l| !l.is_empty())
+            .map(|l| indent(l))
+            .min()
+            .unwrap_or(0);
+        let mut block: Vec<String> = block.iter().map(|l| dedent(l, min_indent)).collect();
+        while block.first().is_some_and(|l| l.is_empty()) {
+            block.remove(0);
+        }
+        while block.last().is_some_and(|l| l.is_empty()) {
+            block.pop();
+        }
+        block
+    }
+
+    /// Parse explicit markup (directive, hyperlink target, or comment) starting at line `i`
+    fn parse_explicit(&mut self, lines: &[String], i: &mut usize, blocks: &mut Vec<Block>) {
+        let text = lines[*i].get(3..).unwrap_or_default().trim();
+        *i += 1;
+        let content = Self::indented_block(lines, i);
+
+        if let Some(target) = text.strip_prefix('_') {
+            let target = target.trim_start_matches('`');
+            if let Some((name, url)) = target.split_once(':') {
+                let url = url.trim().to_string() + &content.join("");
+                self.targets.insert(rst_ref_name(name.trim_end_matches('`')), url);
+            }
+            return;
+        }
+
+        let Some((directive, argument)) = text.split_once("::") else {
+            // Comment
+            return;
+        };
+        match directive.trim() {
+            "code" | "code-block" | "sourcecode" => {
+                // Skip directive options
+                let code: Vec<String> = content.into_iter().skip_while(|l| l.starts_with(':')).collect();
+                blocks.push(Block::Code(code_block(&code)));
+            }
+            name if RST_ADMONITIONS.contains(&name) => {
+                let mut content_lines = Vec::new();
+                if !argument.trim().is_empty() {
+                    content_lines.push(argument.trim().to_string());
+                }
+                content_lines.extend(content);
+                blocks.push(Block::Quote(self.parse(&content_lines)));
+            }
+            _ => {}
+        }
+    }
+
+    fn parse(&mut self, lines: &[String]) -> Vec<Block> {
+        let mut blocks = Vec::new();
+        let mut i = 0;
+
+        while i < lines.len() {
+            let line = &lines[i];
+            if line.is_empty() {
+                i += 1;
+                continue;
+            }
+
+            if indent(line) > 0 {
+                let content = Self::indented_block(lines, &mut i);
+                blocks.push(Block::Quote(self.parse(&content)));
+            } else if line.starts_with(".. ") || line == ".." {
+                self.parse_explicit(lines, &mut i, &mut blocks);
+            } else if is_rst_adornment(line) && i + 2 < lines.len() && !lines[i + 1].is_empty() && lines[i + 2] == *line
+            {
+                // Heading with overline
+                let level = self.heading_level((line.chars().next().unwrap(), true));
+                blocks.push(Block::Heading(level, lines[i + 1].trim().to_string()));
+                i += 3;
+            } else if i + 1 < lines.len()
+                && is_rst_adornment(&lines[i + 1])
+                && (lines[i + 1].chars().count() >= line.chars().count() || lines[i + 1].len() >= 4)
+            {
+                let level = self.heading_level((lines[i + 1].chars().next().unwrap(), false));
+                blocks.push(Block::Heading(level, line.to_string()));
+                i += 2;
+            } else if is_rst_adornment(line) && line.len() >= 4 {
+                blocks.push(Block::Rule);
+                i += 1;
+            } else if list_marker(line, true).is_some() {
+                blocks.push(parse_list(lines, &mut i, false, |item| self.parse(item)));
+            } else {
+                let start = i;
+                while i < lines.len() && !lines[i].is_empty() {
+                    i += 1;
+                }
+                let mut text = lines[start..i].join("\n");
+
+                // Paragraph ending with "::" introduces a literal block
+                if text.ends_with("::") {
+                    text.truncate(text.len() - 1);
+                    if text == ":" {
+                        text.clear();
+                    } else if text.ends_with(" :") {
+                        text.truncate(text.len() - 2);
+                    }
+                    if !text.is_empty() {
+                        blocks.push(Block::Paragraph(text));
+                    }
+                    while i < lines.len() && lines[i].is_empty() {
+                        i += 1;
+                    }
+                    let code = Self::indented_block(lines, &mut i);
+                    blocks.push(Block::Code(code.join("\n")));
+                } else {
+                    blocks.push(Block::Paragraph(text));
+                }
+            }
+        }
+
+        blocks
+    }
+
+    fn link(&self, url: &str, text: &str) -> String {
+        format!("<a href=\"{}\">{}</a>", escape_html(url), escape_html(text))
+    }
+
+    /// Whether inline markup can start at position `i` (preceded by whitespace or punctuation,
+    /// followed by non-whitespace)
+    fn inline_start(chars: &[char], i: usize, len: usize) -> bool {
+        let before = i == 0 || chars[i - 1].is_whitespace() || "-:/'\"<([{".contains(chars[i - 1]);
+        let after = chars.get(i + len).is_some_and(|c| !c.is_whitespace());
+        before && after
+    }
+
+    /// Find end of inline markup (preceded by non-whitespace, followed by whitespace or punctuation)
+    fn inline_end(chars: &[char], start: usize, delim: &str) -> Option<usize> {
+        let delim: Vec<char> = delim.chars().collect();
+        (start + 1..chars.len()).find(|&j| {
+            chars[j..].starts_with(&delim)
+                && !chars[j - 1].is_whitespace()
+                && chars[j - 1] != '\\'
+                && chars
+                    .get(j + delim.len())
+                    .is_none_or(|c| c.is_whitespace() || "-.,:;!?\\/'\")]}>_".contains(*c))
+        })
+    }
+
+    fn inline(&self, text: &str) -> String {
+        let chars: Vec<char> = text.chars().collect();
+        let mut html = String::new();
+        let mut i = 0;
+
+        while i < chars.len() {
+            let rest: String = chars[i..].iter().take(8).collect();
+            let c = chars[i];
+
+            if c == '\\' && i + 1 < chars.len() {
+                if !chars[i + 1].is_whitespace() {
+                    push_escaped(&mut html, chars[i + 1]);
+                }
+                i += 2;
+                continue;
+            }
+
+            let delims = [("``", "code"), ("**", "strong"), ("*", "em")];
+            if let Some((delim, tag, end)) = delims.iter().find_map(|(delim, tag)| {
+                if !rest.starts_with(delim) || !Self::inline_start(&chars, i, delim.len()) {
+                    return None;
+                }
+                Some((*delim, *tag, Self::inline_end(&chars, i + delim.len(), delim)?))
+            }) {
+                let inner: String = chars[i + delim.len()..end].iter().collect();
+                let inner = if tag == "code" { inner } else { inner.replace('\\', "") };
+                html.push_str(&format!("<{tag}>{}</{tag}>", escape_html(&inner)));
+                i = end + delim.len();
+                continue;
+            }
+
+            // Interpreted text with an optional role (":role:`text`") or hyperlink reference ("`text`_")
+            let role = (c == ':' && Self::inline_start(&chars, i, 1))
+                .then(|| {
+                    let end = chars[i + 1..].iter().position(|&x| x == ':' || x.is_whitespace())? + i + 1;
+                    (chars[end] == ':' && chars.get(end + 1) == Some(&'`')).then_some(end)
+                })
+                .flatten();
+            let tick = role.map_or(i, |end| end + 1);
+            if chars.get(tick) == Some(&'`') && (role.is_some() || Self::inline_start(&chars, i, 1)) {
+                if let Some(end) = Self::inline_end(&chars, tick + 1, "`") {
+                    let inner: String = chars[tick + 1..end].iter().collect();
+                    let role: Option<String> = role.map(|r| chars[i + 1..r].iter().collect());
+                    let mut next = end + 1;
+                    let is_reference = role.is_none() && chars.get(next) == Some(&'_');
+                    if is_reference {
+                        next += run_length(&chars, next, '_').min(2);
+                    }
+
+                    if is_reference {
+                        // Embedded URI ("text <url>") or reference to a target
+                        let embedded = inner
+                            .strip_suffix('>')
+                            .and_then(|s| s.rsplit_once('<'))
+                            .map(|(text, url)| (text.trim().to_string(), url.to_string()));
+                        match embedded {
+                            Some((text, url)) if text.is_empty() => html.push_str(&self.link(&url, &url)),
+                            Some((text, url)) => html.push_str(&self.link(&url, &text)),
+                            None => match self.targets.get(&rst_ref_name(&inner)) {
+                                Some(url) => html.push_str(&self.link(url, &inner)),
+                                None => html.push_str(&escape_html(&inner)),
+                            },
+                        }
+                    } else if matches!(role.as_deref(), Some("code" | "literal")) {
+                        html.push_str(&format!("<code>{}</code>", escape_html(&inner)));
+                    } else if matches!(role.as_deref(), Some("strong")) {
+                        html.push_str(&format!("<strong>{}</strong>", escape_html(&inner)));
+                    } else {
+                        // Default role and unknown roles: just the text
+                        html.push_str(&escape_html(&inner));
+                    }
+                    i = next;
+                    continue;
+                }
+            }
+
+            // Standalone URL
+            if (rest.starts_with("http://") || rest.starts_with("https://")) && Self::inline_start(&chars, i, 1) {
+                let mut end = chars[i..]
+                    .iter()
+                    .position(|c| c.is_whitespace() || *c == '<')
+                    .map_or(chars.len(), |p| p + i);
+                while end > i && ".,;:!?)'\"".contains(chars[end - 1]) {
+                    end -= 1;
+                }
+                let url: String = chars[i..end].iter().collect();
+                html.push_str(&self.link(&url, &url));
+                i = end;
+                continue;
+            }
+
+            // Simple reference ("name_")
+            if c.is_alphanumeric() && (i == 0 || !chars[i - 1].is_alphanumeric()) {
+                let len = chars[i..]
+                    .iter()
+                    .take_while(|c| c.is_alphanumeric() || **c == '-')
+                    .count();
+                let word: String = chars[i..i + len].iter().collect();
+                let is_reference = chars.get(i + len) == Some(&'_')
+                    && chars.get(i + len + 1).is_none_or(|c| !c.is_alphanumeric() && *c != '_');
+                if let Some(url) = is_reference.then(|| self.targets.get(&rst_ref_name(&word))).flatten() {
+                    html.push_str(&self.link(url, &word));
+                    i += len + 1;
+                    continue;
+                }
+                html.push_str(&escape_html(&word));
+                i += len;
+                continue;
+            }
+
+            push_escaped(&mut html, c);
+            i += 1;
+        }
+
+        html
+    }
+}
+
+#[cfg(test)]
+mod notes_tests {
+    use super::*;
+
+    #[test]
+    fn test_markdown() {
+        let md = "# Release *Notes*\n\nSome text with `code`, **bold** and a [link](https://obsproject.com \"OBS\").\n\
+                  Second line\n\n- Item 1\n- Item 2\n  continued\n  - Nested <3\n\nOther\n---\n\n```\nlet a = 1 < 2;\n```\n";
+        assert_eq!(
+            to_html(md, NotesFormat::Markdown),
+            "<h1>Release <em>Notes</em></h1>\n\
+             <p>Some text with <code>code</code>, <strong>bold</strong> and a \
+             <a href=\"https://obsproject.com\" title=\"OBS\">link</a>.\nSecond line</p>\n\
+             <ul>\n<li>Item 1</li>\n<li>Item 2\ncontinued\n<ul>\n<li>Nested &lt;3</li>\n</ul></li>\n</ul>\n\
+             <h2>Other</h2>\n<pre><code>let a = 1 &lt; 2;</code></pre>\n"
+        );
+        assert_eq!(
+            to_html("1. a\n\n2. b_c_ *d **e** f*", NotesFormat::Markdown),
+            "<ol>\n<li><p>a</p></li>\n<li><p>b_c_ <em>d <strong>e</strong> f</em></p></li>\n</ol>\n"
+        );
+    }
+
+    #[test]
+    fn test_rst() {
+        let rst = "31.0 Release Notes\n==================\n\nNew Features\n------------\n\n\
+                   - Added ``literal``, *emphasis*, and **strong** text [#1234]\n\
+                   - Links to `OBS <https://obsproject.com>`_ and docs_\n\n  - Nested item\n\n\
+                   Example::\n\n    code here\n\n.. _docs: https://docs.obsproject.com\n\
+                   .. note:: Some note\n";
+        assert_eq!(
+            to_html(rst, NotesFormat::Rst),
+            "<h1>31.0 Release Notes</h1>\n<h2>New Features</h2>\n\
+             <ul>\n<li><p>Added <code>literal</code>, <em>emphasis</em>, and <strong>strong</strong> text [#1234]</p></li>\n\
+             <li><p>Links to <a href=\"https://obsproject.com\">OBS</a> and \
+             <a href=\"https://docs.obsproject.com\">docs</a></p>\n<ul>\n<li>Nested item</li>\n</ul></li>\n</ul>\n\
+             <p>Example:</p>\n<pre><code>code here</code></pre>\n<blockquote>\n<p>Some note</p>\n</blockquote>\n"
+        );
+
+        // Empty comments and targets without a URL are not rendered
+        assert_eq!(
+            to_html("Text\n\n..\n\nMore\n\n.. _anchor:\n\nEnd\n", NotesFormat::Rst),
+            "<p>Text</p>\n<p>More</p>\n<p>End</p>\n"
+        );
+    }
+}
@@ -0,0 +1,191 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+
+use hashbrown::HashMap;
+use indicatif::{ProgressBar, ProgressFinish, ProgressStyle};
+use serde::Serialize;
+
+use crate::models::journal::Stage;
+
+/// Receiver of progress events, e.g. to display progress bars or forward them to another application.
+/// Task events may be sent from multiple threads.
+pub trait ProgressHandler: Send + Sync {
+    /// A task processing `total` items (e.g. hashing files) has started
+    fn task_started(&self, task: &str, total: u64);
+    /// `position` of `total` items of a task have been processed
+    fn task_progress(&self, task: &str, position: u64, total: u64);
+    fn task_finished(&self, task: &str);
+
+    fn stage_started(&self, _stage: Stage) {}
+    fn stage_finished(&self, _stage: Stage) {}
+    /// Other noteworthy events (results, warnings, errors)
+    fn event(&self, _event: &Event) {}
+}
+
+#[derive(Serialize, Debug)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event<'a> {
+    /// Result of comparing the new build to the previous ones
+    Changes {
+        added: usize,
+        changed: usize,
+        unchanged: usize,
+        removed: usize,
+        patches: usize,
+    },
+    PatchCreated {
+        name: &'a str,
+        package: &'a str,
+        /// Hash of the old file the patch applies to
+        old_hash: &'a str,
+        old_size: u64,
+        new_size: u64,
+        patch_size: u64,
+    },
+    /// Warning or error logged during the run
+    Log { level: &'a str, message: &'a str },
+    /// Error the run failed with, including its causes (outermost first)
+    Error { message: String, causes: Vec<String> },
+}
+
+static HANDLER: RwLock<Option<Arc<dyn ProgressHandler>>> = RwLock::new(None);
+
+/// Set handler receiving progress events, by default no progress is reported
+pub fn set_progress_handler(handler: Arc<dyn ProgressHandler>) {
+    *HANDLER.write().unwrap() = Some(handler);
+}
+
+fn handler() -> Option<Arc<dyn ProgressHandler>> {
+    HANDLER.read().unwrap().clone()
+}
+
+pub fn stage_started(stage: Stage) {
+    if let Some(handler) = handler() {
+        handler.stage_started(stage);
+    }
+}
+
+pub fn stage_finished(stage: Stage) {
+    if let Some(handler) = handler() {
+        handler.stage_finished(stage);
+    }
+}
+
+pub fn emit(event: Event) {
+    if let Some(handler) = handler() {
+        handler.event(&event);
+    }
+}
+
+/// A running task, reports it as finished to the handler when dropped
+pub struct Task {
+    name: &'static str,
+    total: u64,
+    position: AtomicU64,
+    handler: Option<Arc<dyn ProgressHandler>>,
+}
+
+impl Task {
+    pub fn start(name: &'static str, total: u64) -> Self {
+        let handler = handler();
+        if let Some(handler) = &handler {
+            handler.task_started(name, total);
+        }
+
+        Self {
+            name,
+            total,
+            position: AtomicU64::new(0),
+            handler,
+        }
+    }
+
+    /// Mark one item as processed
+    pub fn inc(&self) {
+        let position = self.position.fetch_add(1, Ordering::Relaxed) + 1;
+        if let Some(handler) = &self.handler {
+            handler.task_progress(self.name, position, self.total);
+        }
+    }
+}
+
+impl Drop for Task {
+    fn drop(&mut self) {
+        if let Some(handler) = &self.handler {
+            handler.task_finished(self.name);
+        }
+    }
+}
+
+/// Progress bars on the terminal
+#[derive(Default)]
+pub struct ConsoleProgress {
+    bars: Mutex<HashMap<String, ProgressBar>>,
+}
+
+impl ProgressHandler for ConsoleProgress {
+    fn task_started(&self, task: &str, total: u64) {
+        let style =
+            ProgressStyle::with_template("[{elapsed_precise}] {bar:40.cyan/blue} {pos:>7}/{len:7} {msg}").unwrap();
+        let bar = ProgressBar::new(total)
+            .with_style(style)
+            .with_finish(ProgressFinish::AndLeave);
+        self.bars.lock().unwrap().insert(task.to_string(), bar);
+    }
+
+    fn task_progress(&self, task: &str, position: u64, _total: u64) {
+        if let Some(bar) = self.bars.lock().unwrap().get(task) {
+            bar.set_position(position);
+        }
+    }
+
+    fn task_finished(&self, task: &str) {
+        if let Some(bar) = self.bars.lock().unwrap().remove(task) {
+            bar.finish();
+        }
+    }
+}
+
+#[cfg(test)]
+mod progress_tests {
+    use super::*;
+
+    #[derive(Default)]
+    struct Recorder {
+        events: Mutex<Vec<String>>,
+    }
+
+    impl ProgressHandler for Recorder {
+        fn task_started(&self, task: &str, total: u64) {
+            self.events.lock().unwrap().push(format!("{task} started {total}"));
+        }
+        fn task_progress(&self, task: &str, position: u64, total: u64) {
+            self.events.lock().unwrap().push(format!("{task} {position}/{total}"));
+        }
+        fn task_finished(&self, task: &str) {
+            self.events.lock().unwrap().push(format!("{task} finished"));
+        }
+    }
+
+    #[test]
+    fn test_task() {
+        let recorder = Arc::new(Recorder::default());
+        set_progress_handler(recorder.clone());
+
+        let task = Task::start("test", 2);
+        task.inc();
+        task.inc();
+        drop(task);
+
+        // Other tests may report progress at the same time
+        let events: Vec<String> = recorder
+            .events
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|e| e.starts_with("test "))
+            .cloned()
+            .collect();
+        assert_eq!(events, ["test started 2", "test 1/2", "test 2/2", "test finished"]);
+    }
+}
@@ -1,73 +1,211 @@
-use std::path::PathBuf;
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
 use std::{env, fs};
 
-use anyhow::Result;
+use anyhow::{bail, Context, Result};
 use base64::prelude::*;
-use rsa::sha2::{Digest, Sha512};
+use ed25519_dalek::Signer as _;
+use log::{debug, warn};
+use rsa::pkcs8::EncodePublicKey;
+use rsa::sha2::{Digest, Sha256, Sha512};
 use rsa::{pkcs1::DecodeRsaPrivateKey, pkcs8::DecodePrivateKey, Pkcs1v15Sign, RsaPrivateKey};
+use serde::{Deserialize, Serialize};
+
+/// Identifier and version of the multi-signature file format
+const MULTISIG_FORMAT: &str = "bouf-multisig";
+const MULTISIG_VERSION: u32 = 1;
+/// Number of bytes of the public key's SHA-256 digest used as key ID
+const KEY_ID_SIZE: usize = 8;
+
+pub enum SigningKey {
+    Rsa(RsaPrivateKey),
+    Ed25519(ed25519_dalek::SigningKey),
+}
+
+/// A multi-signature file (`<file>.sigs`) containing one entry per signing key
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct MultiSignature {
+    pub format: String,
+    pub version: u32,
+    pub signatures: Vec<SignatureEntry>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct SignatureEntry {
+    pub key_id: String,
+    pub algorithm: String,
+    /// Base64-encoded signature
+    pub signature: String,
+}
 
 #[derive(Default)]
 pub struct Signer<'a> {
-    key_file: Option<&'a PathBuf>,
-    private_key: Option<RsaPrivateKey>,
+    key_files: &'a [PathBuf],
+    keys: Vec<SigningKey>,
+}
+
+impl SigningKey {
+    /// Load RSA (PKCS#1/PKCS#8) or Ed25519 (PKCS#8) key from PEM string
+    pub fn from_pem(pem: &str) -> Result<Self> {
+        if pem.contains("RSA PRIVATE KEY") {
+            return Ok(Self::Rsa(RsaPrivateKey::from_pkcs1_pem(pem)?));
+        }
+
+        if let Ok(key) = RsaPrivateKey::from_pkcs8_pem(pem) {
+            return Ok(Self::Rsa(key));
+        }
+        match ed25519_dalek::SigningKey::from_pkcs8_pem(pem) {
+            Ok(key) => Ok(Self::Ed25519(key)),
+            Err(e) => bail!("Key is neither a valid RSA nor Ed25519 private key: {}", e),
+        }
+    }
+
+    pub fn algorithm(&self) -> &'static str {
+        match self {
+            Self::Rsa(_) => "rsa-pkcs1v15-sha512",
+            Self::Ed25519(_) => "ed25519",
+        }
+    }
+
+    /// Key ID is the hex-encoded truncated SHA-256 digest of the DER-encoded public key (SPKI)
+    pub fn key_id(&self) -> Result<String> {
+        let der = match self {
+            Self::Rsa(key) => key.to_public_key().to_public_key_der()?,
+            Self::Ed25519(key) => key.verifying_key().to_public_key_der()?,
+        };
+        let digest = Sha256::digest(der.as_bytes());
+
+        let mut s = String::with_capacity(2 * KEY_ID_SIZE);
+        for byte in &digest[..KEY_ID_SIZE] {
+            write!(s, "{byte:02x}").unwrap();
+        }
+        Ok(s)
+    }
+
+    pub fn sign(&self, data: &[u8]) -> Result<Vec<u8>> {
+        match self {
+            Self::Rsa(key) => {
+                let digest = Sha512::digest(data);
+                let pad = Pkcs1v15Sign::new::<Sha512>();
+                Ok(key.sign(pad, &digest)?)
+            }
+            Self::Ed25519(key) => Ok(key.sign(data).to_bytes().to_vec()),
+        }
+    }
 }
 
 impl<'a> Signer<'a> {
-    pub fn init(key_file: Option<&'a PathBuf>) -> Self {
+    /// Create signer for the specified key files,
+    /// if the list is empty the "UPDATER_PRIVATE_KEY" environment variable will be used instead.
+    pub fn init(key_files: &'a [PathBuf]) -> Self {
         Self {
-            key_file,
+            key_files,
             ..Default::default()
         }
     }
 
-    fn load_key(&mut self) -> Result<()> {
-        let pem: String;
+    fn load_keys(&mut self) -> Result<()> {
+        let mut pems: Vec<String> = Vec::new();
 
-        if let Some(_path) = &self.key_file {
-            pem = fs::read_to_string(_path)?;
+        if !self.key_files.is_empty() {
+            for path in self.key_files {
+                pems.push(fs::read_to_string(path).with_context(|| format!("Reading {} failed", path.display()))?);
+            }
         } else {
-            let b64key = env::var("UPDATER_PRIVATE_KEY")?;
-            let decoded = BASE64_STANDARD.decode(b64key)?;
-            pem = String::from_utf8(decoded)?;
+            // The env var may contain multiple comma-separated keys
+            let b64keys = env::var("UPDATER_PRIVATE_KEY")?;
+            for b64key in b64keys.split(',').map(str::trim).filter(|k| !k.is_empty()) {
+                let decoded = BASE64_STANDARD.decode(b64key)?;
+                pems.push(String::from_utf8(decoded)?);
+            }
         }
 
-        let pkey: RsaPrivateKey = if pem.contains("RSA PRIVATE KEY") {
-            RsaPrivateKey::from_pkcs1_pem(pem.as_str())?
-        } else {
-            RsaPrivateKey::from_pkcs8_pem(pem.as_str())?
-        };
-        self.private_key = Some(pkey);
+        if pems.is_empty() {
+            bail!("No private keys specified")
+        }
+
+        self.keys = pems
+            .iter()
+            .map(|pem| SigningKey::from_pem(pem))
+            .collect::<Result<Vec<_>>>()?;
 
         Ok(())
     }
 
+    pub fn key_ids(&mut self) -> Result<Vec<String>> {
+        if self.keys.is_empty() {
+            self.load_keys()?
+        }
+
+        self.keys.iter().map(SigningKey::key_id).collect()
+    }
+
+    /// Sign file with all keys.
+    /// Writes the legacy "<file>.sig" (raw signature of the first RSA key) that is checked by the
+    /// OBS updater as well as "<file>.sigs" containing the signatures of all keys.
     pub fn sign_file(&mut self, path: &PathBuf) -> Result<()> {
-        if self.private_key.is_none() {
-            self.load_key()?
+        if self.keys.is_empty() {
+            self.load_keys()?
         }
 
-        // Create digest
         let data = fs::read(path)?;
-        let mut hasher = Sha512::new();
-        hasher.update(data);
-        let res = hasher.finalize();
-        let pad = Pkcs1v15Sign::new::<Sha512>();
-        let signature = self.private_key.as_ref().unwrap().sign(pad, &res)?;
+        // Remove legacy signature of a previous run, it would not match if no RSA key is used now
+        let legacy_path = signature_path(path, "sig");
+        if legacy_path.exists() {
+            fs::remove_file(&legacy_path)?;
+        }
+        let mut multisig = MultiSignature {
+            format: MULTISIG_FORMAT.to_string(),
+            version: MULTISIG_VERSION,
+            ..Default::default()
+        };
+        let mut legacy_written = false;
+
+        for key in &self.keys {
+            let signature = key.sign(&data)?;
+            let key_id = key.key_id()?;
+            debug!("Signed \"{}\" with {} key {}", path.display(), key.algorithm(), key_id);
 
-        let new_ext = format!("{}.sig", path.extension().unwrap().to_str().unwrap());
-        let signature_file = path.with_extension(new_ext);
-        fs::write(signature_file, signature)?;
+            if !legacy_written && matches!(key, SigningKey::Rsa(_)) {
+                fs::write(&legacy_path, &signature)?;
+                legacy_written = true;
+            }
+
+            multisig.signatures.push(SignatureEntry {
+                key_id,
+                algorithm: key.algorithm().to_string(),
+                signature: BASE64_STANDARD.encode(signature),
+            });
+        }
+
+        fs::write(signature_path(path, "sigs"), serde_json::to_string_pretty(&multisig)?)?;
+        if !legacy_written {
+            warn!(
+                "No RSA key specified, \"{}\" cannot be verified by the OBS updater",
+                path.display()
+            );
+        }
 
         Ok(())
     }
 
-    pub fn check_key(key_file: Option<&'a PathBuf>) -> Result<()> {
-        let mut signer = Self {
-            key_file,
-            ..Default::default()
-        };
+    /// Check that the keys can be loaded and include an RSA key for the legacy signature
+    pub fn check_key(key_files: &'a [PathBuf]) -> Result<()> {
+        let mut signer = Self::init(key_files);
+        signer.load_keys()?;
+        if !signer.keys.iter().any(|k| matches!(k, SigningKey::Rsa(_))) {
+            bail!("No RSA key specified, the OBS updater only verifies signatures made with an RSA key")
+        }
+
+        Ok(())
+    }
+}
 
-        signer.load_key()
+/// Appends signature extension to existing extension (e.g. "manifest.json" => "manifest.json.sig")
+pub fn signature_path(path: &Path, sig_ext: &str) -> PathBuf {
+    match path.extension() {
+        Some(ext) => path.with_extension(format!("{}.{sig_ext}", ext.to_str().unwrap())),
+        None => path.with_extension(sig_ext),
     }
 }
 
@@ -75,6 +213,10 @@ impl<'a> Signer<'a> {
 mod rsa_tests {
     use super::*;
     use crate::utils::hash::hash_file;
+    use crate::utils::misc::TestDir;
+    use ed25519_dalek::Verifier;
+    use rsa::pkcs8::DecodePublicKey;
+    use rsa::RsaPublicKey;
     use std::env;
     use std::path::PathBuf;
 
@@ -85,18 +227,85 @@ mod rsa_tests {
         let signature_path = PathBuf::from("extra/test_files/in.txt.sig");
 
         // Try with key file
-        let mut signer = Signer::init(Some(&key_path));
+        let key_files = vec![key_path.clone()];
+        let mut signer = Signer::init(&key_files);
         signer.sign_file(&file_path).expect("Signing failed");
         let finfo = hash_file(&signature_path);
         assert_eq!(finfo.hash, "4aae469c5a90903a40f1757c7b50d38c5ddfb364");
 
         // Try with env var
-        let b64_key = base64::encode(fs::read(key_path).unwrap());
+        let b64_key = BASE64_STANDARD.encode(fs::read(key_path).unwrap());
         env::set_var("UPDATER_PRIVATE_KEY", b64_key);
 
-        let mut signer = Signer::init(None);
+        let mut signer = Signer::init(&[]);
         signer.sign_file(&file_path).expect("Signing failed");
         let finfo = hash_file(&signature_path);
         assert_eq!(finfo.hash, "4aae469c5a90903a40f1757c7b50d38c5ddfb364");
     }
+
+    #[test]
+    fn test_multi_sign() {
+        let key_files = vec![
+            PathBuf::from("extra/test_files/ed25519_privatekey.pem"),
+            PathBuf::from("extra/test_files/privatekey.pem"),
+        ];
+        let file_path = PathBuf::from("extra/test_files/out.txt");
+        let data = fs::read(&file_path).unwrap();
+
+        let mut signer = Signer::init(&key_files);
+        signer.sign_file(&file_path).expect("Signing failed");
+
+        // Legacy signature is written for the RSA key even if it is not the first one
+        let finfo = hash_file(Path::new("extra/test_files/out.txt.sig"));
+        assert_eq!(finfo.size, 512);
+
+        let multisig: MultiSignature =
+            serde_json::from_str(&fs::read_to_string("extra/test_files/out.txt.sigs").unwrap()).unwrap();
+        assert_eq!(multisig.format, MULTISIG_FORMAT);
+        assert_eq!(multisig.signatures.len(), 2);
+        assert_eq!(multisig.signatures[0].algorithm, "ed25519");
+        assert_eq!(multisig.signatures[1].algorithm, "rsa-pkcs1v15-sha512");
+
+        let key_ids = signer.key_ids().unwrap();
+        assert_eq!(multisig.signatures[0].key_id, key_ids[0]);
+        assert_eq!(multisig.signatures[1].key_id, key_ids[1]);
+        assert_ne!(key_ids[0], key_ids[1]);
+        assert!(Signer::check_key(&key_files).is_ok());
+
+        // Verify Ed25519 signature
+        let SigningKey::Ed25519(ed_key) = &signer.keys[0] else {
+            panic!("First key is not Ed25519")
+        };
+        let ed_sig = BASE64_STANDARD.decode(&multisig.signatures[0].signature).unwrap();
+        let ed_sig = ed25519_dalek::Signature::from_slice(&ed_sig).unwrap();
+        ed_key
+            .verifying_key()
+            .verify(&data, &ed_sig)
+            .expect("Ed25519 verification failed");
+
+        // Verify RSA signature with public key
+        let pubkey = RsaPublicKey::from_public_key_pem(&fs::read_to_string("extra/test_files/pubkey.pem").unwrap());
+        let rsa_sig = BASE64_STANDARD.decode(&multisig.signatures[1].signature).unwrap();
+        pubkey
+            .unwrap()
+            .verify(Pkcs1v15Sign::new::<Sha512>(), &Sha512::digest(&data), &rsa_sig)
+            .expect("RSA verification failed");
+    }
+
+    #[test]
+    fn test_ed25519_only() {
+        let key_files = vec![PathBuf::from("extra/test_files/ed25519_privatekey.pem")];
+        assert!(Signer::check_key(&key_files).is_err());
+
+        // Stale legacy signature of a previous run is removed
+        let dir = TestDir::new("sign");
+        let file_path = dir.join("manifest.json");
+        fs::write(&file_path, "{}").unwrap();
+        fs::write(dir.join("manifest.json.sig"), "stale").unwrap();
+
+        let mut signer = Signer::init(&key_files);
+        signer.sign_file(&file_path).expect("Signing failed");
+        assert!(!dir.join("manifest.json.sig").exists());
+        assert!(dir.join("manifest.json.sigs").exists());
+    }
 }
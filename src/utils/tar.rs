@@ -0,0 +1,211 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context, Result};
+use xz2::stream::{Check, MtStreamBuilder};
+use xz2::write::XzEncoder;
+
+use crate::models::config::ArchiveFormat;
+use crate::utils::progress::Task;
+
+const BLOCK_SIZE: usize = 512;
+/// Largest size that fits into the 11 octal digits of the header's size field
+const MAX_FILE_SIZE: u64 = 0o77777777777;
+const TYPE_FILE: u8 = b'0';
+const TYPE_PAX_HEADER: u8 = b'x';
+
+const DEFAULT_XZ_LEVEL: u32 = 6;
+
+/// Write value as zero-terminated octal number filling the field
+fn write_octal(field: &mut [u8], value: u64) {
+    let digits = format!("{:0width$o}", value, width = field.len() - 1);
+    field[..digits.len()].copy_from_slice(digits.as_bytes());
+    field[digits.len()] = 0;
+}
+
+/// Split a name into ustar prefix and name (if it does not fit into the name field)
+fn split_name(name: &str) -> Option<(&str, &str)> {
+    if name.len() <= 100 {
+        return Some(("", name));
+    }
+
+    name.match_indices('/')
+        .map(|(pos, _)| (&name[..pos], &name[pos + 1..]))
+        .find(|(prefix, rest)| prefix.len() <= 155 && !rest.is_empty() && rest.len() <= 100)
+}
+
+/// Create ustar header block. Owner, group, and modification time are always zero
+fn header(prefix: &str, name: &str, size: u64, mode: u32, typeflag: u8) -> [u8; BLOCK_SIZE] {
+    let mut block = [0u8; BLOCK_SIZE];
+    block[..name.len().min(100)].copy_from_slice(&name.as_bytes()[..name.len().min(100)]);
+    write_octal(&mut block[100..108], mode as u64);
+    write_octal(&mut block[108..116], 0);
+    write_octal(&mut block[116..124], 0);
+    write_octal(&mut block[124..136], size);
+    write_octal(&mut block[136..148], 0);
+    block[156] = typeflag;
+    block[257..263].copy_from_slice(b"ustar\0");
+    block[263..265].copy_from_slice(b"00");
+    block[345..345 + prefix.len()].copy_from_slice(prefix.as_bytes());
+
+    // Checksum is calculated with the checksum field set to spaces
+    block[148..156].fill(b' ');
+    let checksum: u32 = block.iter().map(|&b| b as u32).sum();
+    write_octal(&mut block[148..155], checksum as u64);
+
+    block
+}
+
+/// PAX extended header record ("<length> path=<name>\n", the length includes itself)
+fn pax_record(key: &str, value: &str) -> String {
+    let record_len = key.len() + value.len() + 3;
+    let mut len = record_len + record_len.to_string().len();
+    if len.to_string().len() != record_len.to_string().len() {
+        len += 1;
+    }
+    format!("{len} {key}={value}\n")
+}
+
+fn write_padding(writer: &mut impl Write, size: u64) -> Result<()> {
+    let remainder = (size % BLOCK_SIZE as u64) as usize;
+    if remainder != 0 {
+        writer.write_all(&[0u8; BLOCK_SIZE][..BLOCK_SIZE - remainder])?;
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn file_mode(metadata: &std::fs::Metadata) -> u32 {
+    use std::os::unix::fs::PermissionsExt;
+    if metadata.permissions().mode() & 0o111 != 0 {
+        0o755
+    } else {
+        0o644
+    }
+}
+
+#[cfg(not(unix))]
+fn file_mode(_metadata: &std::fs::Metadata) -> u32 {
+    0o644
+}
+
+/// Write tar stream of the files to writer, names that do not fit into a ustar header use a PAX header
+fn write_tar<W: Write>(files: &[&(String, PathBuf)], mut writer: W) -> Result<W> {
+    let task = Task::start("tar", files.len() as u64);
+    for (name, path) in files {
+        let mut file = File::open(path).context(format!("Failed reading {path:?}"))?;
+        let metadata = file.metadata()?;
+        let size = metadata.len();
+        if size > MAX_FILE_SIZE {
+            bail!("File {path:?} is too large for a tar archive");
+        }
+
+        let (prefix, short_name) = match split_name(name) {
+            Some(split) => split,
+            None => {
+                let record = pax_record("path", name);
+                writer.write_all(&header(
+                    "",
+                    "././@PaxHeader",
+                    record.len() as u64,
+                    0o644,
+                    TYPE_PAX_HEADER,
+                ))?;
+                writer.write_all(record.as_bytes())?;
+                write_padding(&mut writer, record.len() as u64)?;
+                ("", name.as_str())
+            }
+        };
+
+        writer.write_all(&header(prefix, short_name, size, file_mode(&metadata), TYPE_FILE))?;
+        let copied = std::io::copy(&mut file, &mut writer)?;
+        if copied != size {
+            bail!("File {path:?} changed while creating archive");
+        }
+        write_padding(&mut writer, size)?;
+        task.inc();
+    }
+
+    // End of archive: two empty blocks
+    writer.write_all(&[0u8; BLOCK_SIZE * 2])?;
+    Ok(writer)
+}
+
+/// Create compressed tar archive from a list of files (name in the archive and path).
+/// Entries are sorted by name and use a fixed timestamp and owner so that the same inputs
+/// always produce the same archive.
+pub fn create_tar(files: &[(String, PathBuf)], output: &Path, format: ArchiveFormat, level: Option<u32>) -> Result<()> {
+    let mut files: Vec<&(String, PathBuf)> = files.iter().collect();
+    files.sort_by(|a, b| a.0.cmp(&b.0));
+
+    let writer = BufWriter::new(File::create(output).context(format!("Failed creating {output:?}"))?);
+    let mut writer = match format {
+        ArchiveFormat::TarZst => {
+            let level = level.map_or(zstd::DEFAULT_COMPRESSION_LEVEL, |l| l as i32);
+            write_tar(&files, zstd::Encoder::new(writer, level)?)?.finish()?
+        }
+        ArchiveFormat::TarXz => {
+            // Output of the multithreaded encoder does not depend on the number of threads
+            let stream = MtStreamBuilder::new()
+                .threads(rayon::current_num_threads() as u32)
+                .preset(level.unwrap_or(DEFAULT_XZ_LEVEL))
+                .check(Check::Crc64)
+                .encoder()?;
+            write_tar(&files, XzEncoder::new_stream(writer, stream))?.finish()?
+        }
+        ArchiveFormat::Zip => bail!("ZIP is not a tar format"),
+    };
+    writer.flush()?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tar_tests {
+    use super::*;
+    use crate::utils::misc::TestDir;
+    use std::fs;
+    use std::io::Read;
+
+    #[test]
+    fn test_tar() {
+        assert_eq!(pax_record("path", "a"), "9 path=a\n");
+        assert_eq!(pax_record("path", "abcd"), "13 path=abcd\n");
+        let long_name = format!("{}/{}", "d".repeat(120), "f".repeat(50));
+        assert_eq!(
+            split_name(&long_name),
+            Some(("d".repeat(120).as_str(), "f".repeat(50).as_str()))
+        );
+        assert_eq!(split_name(&"f".repeat(120)), None);
+
+        let dir = TestDir::new("tar");
+        fs::write(dir.join("a.txt"), "hello").unwrap();
+        let files = vec![
+            ("z.txt".to_string(), dir.join("a.txt")),
+            ("a.txt".to_string(), dir.join("a.txt")),
+        ];
+
+        let output = dir.join("test.tar.zst");
+        create_tar(&files, &output, ArchiveFormat::TarZst, None).unwrap();
+        let mut data = Vec::new();
+        zstd::Decoder::new(File::open(&output).unwrap())
+            .unwrap()
+            .read_to_end(&mut data)
+            .unwrap();
+
+        // Two entries with one data block each, and two end blocks
+        assert_eq!(data.len(), BLOCK_SIZE * 6);
+        assert_eq!(&data[..6], b"a.txt\0");
+        assert_eq!(&data[257..263], b"ustar\0");
+        assert_eq!(&data[124..136], b"00000000005\0");
+        let checksum: u32 = data[..BLOCK_SIZE]
+            .iter()
+            .enumerate()
+            .map(|(i, &b)| if (148..156).contains(&i) { b' ' as u32 } else { b as u32 })
+            .sum();
+        assert_eq!(&data[148..155], format!("{checksum:06o}\0").as_bytes());
+        assert_eq!(&data[BLOCK_SIZE..BLOCK_SIZE + 5], b"hello");
+        assert_eq!(&data[BLOCK_SIZE * 2..BLOCK_SIZE * 2 + 6], b"z.txt\0");
+    }
+}
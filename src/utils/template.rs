@@ -0,0 +1,144 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::{bail, Result};
+
+use crate::models::config::Config;
+use crate::utils::misc;
+
+/// Variables that can be used in output name templates (e.g. "OBS-Studio-{version}.zip")
+pub const VARIABLES: [&str; 7] = [
+    "version",
+    "short_version",
+    "tag_version",
+    "branch",
+    "commit",
+    "arch",
+    "date",
+];
+
+pub struct Templates {
+    values: Vec<(&'static str, String)>,
+}
+
+/// Convert days since the Unix epoch to a "YYYY-MM-DD" date (proleptic Gregorian calendar)
+fn format_date(days: i64) -> String {
+    // See http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!("{year:04}-{month:02}-{day:02}")
+}
+
+/// Current date, or the date of `SOURCE_DATE_EPOCH` if set (for reproducible builds)
+fn build_date() -> String {
+    let timestamp = std::env::var("SOURCE_DATE_EPOCH")
+        .ok()
+        .and_then(|t| t.parse::<i64>().ok())
+        .unwrap_or_else(|| {
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs() as i64)
+                .unwrap_or_default()
+        });
+
+    format_date(timestamp.div_euclid(86400))
+}
+
+/// Check that a template only uses known variables
+pub fn check(template: &str) -> Result<()> {
+    check_with(template, &[])
+}
+
+/// Check that a template only uses known variables or the additional ones
+pub fn check_with(template: &str, extra: &[&str]) -> Result<()> {
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        let Some(len) = rest[start..].find('}') else {
+            bail!("Unclosed variable in \"{template}\"");
+        };
+        let name = &rest[start + 1..start + len];
+        if !VARIABLES.contains(&name) && !extra.contains(&name) {
+            let available: Vec<String> = VARIABLES.iter().chain(extra).map(|v| format!("{{{v}}}")).collect();
+            bail!(
+                "Unknown variable \"{{{name}}}\" in \"{template}\" (available: {})",
+                available.join(", ")
+            );
+        }
+        rest = &rest[start + len + 1..];
+    }
+
+    Ok(())
+}
+
+impl Templates {
+    pub fn new(conf: &Config) -> Self {
+        let short_version = misc::get_filename_version(&conf.obs_version, true);
+        let values = vec![
+            // "{version}" has always been the short version, kept for existing configs
+            ("version", short_version.to_owned()),
+            ("short_version", short_version),
+            ("tag_version", misc::get_filename_version(&conf.obs_version, false)),
+            ("branch", conf.general.branch.to_owned()),
+            ("commit", conf.obs_version.commit.to_owned()),
+            ("arch", conf.general.arch.to_owned()),
+            ("date", build_date()),
+        ];
+
+        Self { values }
+    }
+
+    /// Replace variables in template (templates are checked when the config is validated)
+    pub fn render(&self, template: &str) -> String {
+        let mut result = template.to_string();
+        for (name, value) in &self.values {
+            result = result.replace(&format!("{{{name}}}"), value);
+        }
+        result
+    }
+
+    /// Replace variables in template, including additional ones only available in this template
+    pub fn render_with(&self, template: &str, extra: &[(&str, &str)]) -> String {
+        let mut result = self.render(template);
+        for (name, value) in extra {
+            result = result.replace(&format!("{{{name}}}"), value);
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod template_tests {
+    use super::*;
+
+    #[test]
+    fn test_template() {
+        assert_eq!(format_date(0), "1970-01-01");
+        assert_eq!(format_date(20379), "2025-10-18");
+        assert_eq!(format_date(-1), "1969-12-31");
+
+        assert!(check("OBS-Studio-{short_version}-{arch}.zip").is_ok());
+        assert!(check("OBS-Studio-{foo}.zip").is_err());
+        assert!(check("OBS-Studio-{version.zip").is_err());
+        assert!(check("https://example.com/{installer}").is_err());
+        assert!(check_with("https://example.com/{tag_version}/{installer}", &["installer"]).is_ok());
+
+        let templates = Templates {
+            values: vec![("version", "31.0".to_string()), ("arch", "x64".to_string())],
+        };
+        assert_eq!(
+            templates.render("OBS-Studio-{version}-{arch}.exe"),
+            "OBS-Studio-31.0-x64.exe"
+        );
+        assert_eq!(
+            templates.render_with("https://example.com/{version}/{installer}", &[("installer", "obs.exe")]),
+            "https://example.com/31.0/obs.exe"
+        );
+    }
+}
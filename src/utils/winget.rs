@@ -0,0 +1,257 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context, Result};
+
+use crate::models::config::Config;
+use crate::utils::hash::hash_file_sha256;
+use crate::utils::misc;
+use crate::utils::template::Templates;
+
+/// Supported manifest schema versions
+pub const SCHEMA_VERSIONS: [&str; 3] = ["1.6.0", "1.9.0", "1.10.0"];
+
+/// Manifest contents, maps keep the order of their fields
+enum Node {
+    Str(String),
+    List(Vec<Node>),
+    Map(Vec<(&'static str, Node)>),
+}
+
+impl Node {
+    fn str(text: &str) -> Self {
+        Self::Str(text.to_string())
+    }
+}
+
+/// Quote strings that YAML would not read back as the same string
+fn yaml_string(text: &str) -> String {
+    let reserved = ["true", "false", "yes", "no", "on", "off", "null", "~"];
+    let plain = text.starts_with(|c: char| c.is_ascii_alphanumeric())
+        && text
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || " ._-/:+(),@=?".contains(c))
+        && !text.ends_with([' ', ':'])
+        && !text.contains(": ")
+        && !reserved.contains(&text.to_lowercase().as_str())
+        && text.parse::<f64>().is_err();
+
+    if plain {
+        text.to_string()
+    } else {
+        // JSON strings are valid double-quoted YAML strings
+        serde_json::Value::String(text.to_string()).to_string()
+    }
+}
+
+fn write_fields(fields: &[(&str, Node)], indent: usize, out: &mut String) {
+    let pad = " ".repeat(indent);
+    for (key, value) in fields {
+        match value {
+            Node::Str(text) => out.push_str(&format!("{pad}{key}: {}\n", yaml_string(text))),
+            Node::Map(inner) => {
+                out.push_str(&format!("{pad}{key}:\n"));
+                write_fields(inner, indent + 2, out);
+            }
+            Node::List(items) => {
+                out.push_str(&format!("{pad}{key}:\n"));
+                for item in items {
+                    match item {
+                        Node::Str(text) => out.push_str(&format!("{pad}- {}\n", yaml_string(text))),
+                        Node::Map(inner) => {
+                            // First field goes on the line of the list item marker
+                            let mut entry = String::new();
+                            write_fields(inner, indent + 2, &mut entry);
+                            entry.replace_range(indent..indent + 2, "- ");
+                            out.push_str(&entry);
+                        }
+                        Node::List(_) => unreachable!("Nested lists are not used in manifests"),
+                    }
+                }
+            }
+        }
+    }
+}
+
+pub struct WingetManifest {
+    /// Type as used in the schema names ("version", "installer", or "defaultLocale")
+    pub manifest_type: &'static str,
+    pub file_name: String,
+    fields: Vec<(&'static str, Node)>,
+}
+
+impl WingetManifest {
+    pub fn to_yaml(&self, schema_version: &str) -> String {
+        let mut out = format!(
+            "# Created with bouf {}\n# yaml-language-server: $schema=https://aka.ms/winget-manifest.{}.{}.schema.json\n\n",
+            env!("CARGO_PKG_VERSION"),
+            self.manifest_type,
+            schema_version
+        );
+        write_fields(&self.fields, 0, &mut out);
+        out
+    }
+}
+
+/// Create version, installer, and default locale manifests for an installer
+fn manifests(conf: &Config, installer_name: &str, installer_sha256: &str) -> Vec<WingetManifest> {
+    let winget = &conf.package.winget;
+    let templates = Templates::new(conf);
+    let id = winget.package_identifier.as_str();
+    let version = misc::get_filename_version(&conf.obs_version, false);
+
+    let manifest = |manifest_type: &'static str, file_name: String, fields: Vec<(&'static str, Node)>| {
+        let mut all_fields = vec![
+            ("PackageIdentifier", Node::str(id)),
+            ("PackageVersion", Node::str(&version)),
+        ];
+        all_fields.extend(fields);
+        all_fields.push(("ManifestType", Node::str(manifest_type)));
+        all_fields.push(("ManifestVersion", Node::str(&winget.schema_version)));
+        WingetManifest {
+            manifest_type,
+            file_name,
+            fields: all_fields,
+        }
+    };
+
+    let installer = Node::Map(vec![
+        ("Architecture", Node::str(&conf.general.arch)),
+        (
+            "InstallerUrl",
+            Node::Str(templates.render_with(&winget.installer_url, &[("installer", installer_name)])),
+        ),
+        ("InstallerSha256", Node::Str(installer_sha256.to_uppercase())),
+    ]);
+
+    let mut locale_fields = vec![
+        ("PackageLocale", Node::str(&winget.locale)),
+        ("Publisher", Node::str(&winget.publisher)),
+    ];
+    if !winget.publisher_url.is_empty() {
+        locale_fields.push(("PublisherUrl", Node::str(&winget.publisher_url)));
+    }
+    locale_fields.extend([
+        ("PackageName", Node::str(&winget.package_name)),
+        ("License", Node::str(&winget.license)),
+        ("ShortDescription", Node::str(&winget.short_description)),
+    ]);
+    if !winget.release_notes_url.is_empty() {
+        locale_fields.push((
+            "ReleaseNotesUrl",
+            Node::Str(templates.render(&winget.release_notes_url)),
+        ));
+    }
+
+    vec![
+        manifest(
+            "version",
+            format!("{id}.yaml"),
+            vec![("DefaultLocale", Node::str(&winget.locale))],
+        ),
+        manifest(
+            "installer",
+            format!("{id}.installer.yaml"),
+            vec![
+                ("InstallerType", Node::str("nullsoft")),
+                ("Scope", Node::str("machine")),
+                ("UpgradeBehavior", Node::str("install")),
+                ("ReleaseDate", Node::Str(templates.render("{date}"))),
+                ("Installers", Node::List(vec![installer])),
+            ],
+        ),
+        manifest(
+            "defaultLocale",
+            format!("{id}.locale.{}.yaml", winget.locale),
+            locale_fields,
+        ),
+    ]
+}
+
+/// Check that the configured schema version is supported
+pub fn check(conf: &Config) -> Result<()> {
+    let schema_version = &conf.package.winget.schema_version;
+    if !SCHEMA_VERSIONS.contains(&schema_version.as_str()) {
+        bail!(
+            "Unsupported schema version \"{schema_version}\" (supported: {})",
+            SCHEMA_VERSIONS.join(", ")
+        )
+    }
+
+    Ok(())
+}
+
+/// Write manifests for the installer to the output directory, returns the paths of the written files
+pub fn write_manifests(conf: &Config, installer: &Path, output_dir: &Path) -> Result<Vec<PathBuf>> {
+    let schema_version = &conf.package.winget.schema_version;
+    let installer_name = installer.file_name().unwrap_or_default().to_string_lossy();
+    let sha256 = hash_file_sha256(installer).context(format!("Failed hashing {installer:?}"))?;
+
+    fs::create_dir_all(output_dir)?;
+    let mut paths = Vec::new();
+    for manifest in manifests(conf, &installer_name, &sha256) {
+        let path = output_dir.join(&manifest.file_name);
+        fs::write(&path, manifest.to_yaml(schema_version)).context(format!("Failed writing {path:?}"))?;
+        paths.push(path);
+    }
+
+    Ok(paths)
+}
+
+#[cfg(test)]
+mod winget_tests {
+    use super::*;
+
+    #[test]
+    fn test_yaml() {
+        assert_eq!(yaml_string("OBS Studio"), "OBS Studio");
+        assert_eq!(yaml_string("https://obsproject.com"), "https://obsproject.com");
+        assert_eq!(yaml_string("31.0"), "\"31.0\"");
+        assert_eq!(yaml_string("yes"), "\"yes\"");
+        assert_eq!(yaml_string("Free: \"open\" source"), "\"Free: \\\"open\\\" source\"");
+
+        let fields = vec![
+            ("PackageIdentifier", Node::str("OBSProject.OBSStudio")),
+            (
+                "Installers",
+                Node::List(vec![Node::Map(vec![
+                    ("Architecture", Node::str("x64")),
+                    ("InstallerSha256", Node::str("ABCD")),
+                ])]),
+            ),
+            ("ManifestType", Node::str("installer")),
+        ];
+        let mut out = String::new();
+        write_fields(&fields, 0, &mut out);
+        assert_eq!(
+            out,
+            "PackageIdentifier: OBSProject.OBSStudio\nInstallers:\n- Architecture: x64\n  InstallerSha256: ABCD\nManifestType: installer\n"
+        );
+    }
+
+    #[test]
+    fn test_manifests() {
+        let mut conf = Config::default();
+        conf.set_version(&"31.0.0".to_string(), 2, 0).unwrap();
+        for version in SCHEMA_VERSIONS {
+            conf.package.winget.schema_version = version.to_string();
+            assert!(check(&conf).is_ok());
+        }
+
+        let files: Vec<String> = manifests(&conf, "obs.exe", &"a".repeat(64))
+            .into_iter()
+            .map(|m| m.file_name)
+            .collect();
+        assert_eq!(
+            files,
+            [
+                "OBSProject.OBSStudio.yaml",
+                "OBSProject.OBSStudio.installer.yaml",
+                "OBSProject.OBSStudio.locale.en-US.yaml"
+            ]
+        );
+
+        conf.package.winget.schema_version = "1.0.0".to_string();
+        assert!(check(&conf).is_err());
+    }
+}
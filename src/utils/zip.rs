@@ -0,0 +1,277 @@
+use std::fs;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use flate2::write::DeflateEncoder;
+use rayon::prelude::*;
+
+use crate::models::config::ZipCompression;
+use crate::utils::progress::Task;
+
+const LOCAL_HEADER_SIG: u32 = 0x04034b50;
+const CENTRAL_HEADER_SIG: u32 = 0x02014b50;
+const END_OF_CENTRAL_DIR_SIG: u32 = 0x06054b50;
+const ZIP64_END_OF_CENTRAL_DIR_SIG: u32 = 0x06064b50;
+const ZIP64_LOCATOR_SIG: u32 = 0x07064b50;
+const ZIP64_EXTRA_ID: u16 = 0x0001;
+
+const METHOD_DEFLATE: u16 = 8;
+const METHOD_ZSTD: u16 = 93;
+/// General purpose flag: file names are UTF-8
+const FLAG_UTF8: u16 = 1 << 11;
+/// Fixed modification time for reproducible archives (MS-DOS format: 1980-01-01 00:00:00)
+const DOS_TIME: u16 = 0;
+const DOS_DATE: u16 = (1 << 5) | 1;
+
+/// Files compressed in parallel before being written, limits memory usage
+const BATCH_SIZE: usize = 64;
+
+struct Entry {
+    name: String,
+    method: u16,
+    crc32: u32,
+    compressed_size: u64,
+    size: u64,
+    offset: u64,
+}
+
+impl Entry {
+    fn is_zip64(&self) -> bool {
+        self.compressed_size >= u32::MAX as u64 || self.size >= u32::MAX as u64 || self.offset >= u32::MAX as u64
+    }
+
+    fn version_needed(&self) -> u16 {
+        match (self.method, self.is_zip64()) {
+            (METHOD_ZSTD, _) => 63,
+            (_, true) => 45,
+            _ => 20,
+        }
+    }
+
+    /// ZIP64 extra field with sizes and offset (only written if any of them exceed 32 bits)
+    fn zip64_extra(&self, include_offset: bool) -> Vec<u8> {
+        if !self.is_zip64() {
+            return Vec::new();
+        }
+
+        let mut extra = Vec::with_capacity(28);
+        let len: u16 = if include_offset { 24 } else { 16 };
+        extra.extend_from_slice(&ZIP64_EXTRA_ID.to_le_bytes());
+        extra.extend_from_slice(&len.to_le_bytes());
+        extra.extend_from_slice(&self.size.to_le_bytes());
+        extra.extend_from_slice(&self.compressed_size.to_le_bytes());
+        if include_offset {
+            extra.extend_from_slice(&self.offset.to_le_bytes());
+        }
+        extra
+    }
+
+    /// Fields shared by local and central directory headers (from "version needed" to "extra field length")
+    fn common_header(&self, extra_len: usize) -> Vec<u8> {
+        let (compressed_size, size) = if self.is_zip64() {
+            (u32::MAX, u32::MAX)
+        } else {
+            (self.compressed_size as u32, self.size as u32)
+        };
+
+        let mut header = Vec::with_capacity(26);
+        header.extend_from_slice(&self.version_needed().to_le_bytes());
+        header.extend_from_slice(&FLAG_UTF8.to_le_bytes());
+        header.extend_from_slice(&self.method.to_le_bytes());
+        header.extend_from_slice(&DOS_TIME.to_le_bytes());
+        header.extend_from_slice(&DOS_DATE.to_le_bytes());
+        header.extend_from_slice(&self.crc32.to_le_bytes());
+        header.extend_from_slice(&compressed_size.to_le_bytes());
+        header.extend_from_slice(&size.to_le_bytes());
+        header.extend_from_slice(&(self.name.len() as u16).to_le_bytes());
+        header.extend_from_slice(&(extra_len as u16).to_le_bytes());
+        header
+    }
+
+    fn local_header(&self) -> Vec<u8> {
+        let extra = self.zip64_extra(false);
+        let mut header = LOCAL_HEADER_SIG.to_le_bytes().to_vec();
+        header.extend(self.common_header(extra.len()));
+        header.extend_from_slice(self.name.as_bytes());
+        header.extend(extra);
+        header
+    }
+
+    fn central_header(&self) -> Vec<u8> {
+        let extra = self.zip64_extra(true);
+        let offset = if self.is_zip64() { u32::MAX } else { self.offset as u32 };
+
+        let mut header = CENTRAL_HEADER_SIG.to_le_bytes().to_vec();
+        // Version made by (MS-DOS, same as needed)
+        header.extend_from_slice(&self.version_needed().to_le_bytes());
+        header.extend(self.common_header(extra.len()));
+        // Comment length, disk number, internal and external attributes
+        header.extend_from_slice(&[0u8; 10]);
+        header.extend_from_slice(&offset.to_le_bytes());
+        header.extend_from_slice(self.name.as_bytes());
+        header.extend(extra);
+        header
+    }
+}
+
+fn compress(data: &[u8], compression: ZipCompression, level: Option<u32>) -> Result<(u16, Vec<u8>)> {
+    match compression {
+        ZipCompression::Deflate => {
+            let level = level.map_or(flate2::Compression::default(), flate2::Compression::new);
+            let mut encoder = DeflateEncoder::new(Vec::new(), level);
+            encoder.write_all(data)?;
+            Ok((METHOD_DEFLATE, encoder.finish()?))
+        }
+        ZipCompression::Zstd => {
+            let level = level.map_or(zstd::DEFAULT_COMPRESSION_LEVEL, |l| l as i32);
+            Ok((METHOD_ZSTD, zstd::bulk::compress(data, level)?))
+        }
+    }
+}
+
+/// Create ZIP file from a list of files (name in the archive and path).
+/// Files are compressed in parallel, entries are sorted by name and use a fixed timestamp
+/// so that the same inputs always produce the same archive.
+pub fn create_zip(
+    files: &[(String, PathBuf)],
+    output: &Path,
+    compression: ZipCompression,
+    level: Option<u32>,
+) -> Result<()> {
+    let mut files: Vec<&(String, PathBuf)> = files.iter().collect();
+    files.sort_by(|a, b| a.0.cmp(&b.0));
+
+    let mut writer = BufWriter::new(File::create(output).context(format!("Failed creating {output:?}"))?);
+    let mut entries: Vec<Entry> = Vec::with_capacity(files.len());
+    let mut offset: u64 = 0;
+
+    let task = Task::start("zip", files.len() as u64);
+    for batch in files.chunks(BATCH_SIZE) {
+        let compressed = batch
+            .par_iter()
+            .map(|&(name, path)| {
+                let data = fs::read(path).context(format!("Failed reading {path:?}"))?;
+                let (method, compressed) = compress(&data, compression, level)?;
+                task.inc();
+                Ok((name, data.len() as u64, crc32fast::hash(&data), method, compressed))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        for (name, size, crc32, method, data) in compressed {
+            let entry = Entry {
+                name: name.to_owned(),
+                method,
+                crc32,
+                compressed_size: data.len() as u64,
+                size,
+                offset,
+            };
+            let header = entry.local_header();
+            writer.write_all(&header)?;
+            writer.write_all(&data)?;
+            offset += (header.len() + data.len()) as u64;
+            entries.push(entry);
+        }
+    }
+
+    let cd_offset = offset;
+    let mut cd_size: u64 = 0;
+    for entry in &entries {
+        let header = entry.central_header();
+        writer.write_all(&header)?;
+        cd_size += header.len() as u64;
+    }
+
+    let zip64 = entries.len() >= u16::MAX as usize || cd_offset >= u32::MAX as u64 || cd_size >= u32::MAX as u64;
+    if zip64 {
+        let eocd64_offset = cd_offset + cd_size;
+        let mut record = ZIP64_END_OF_CENTRAL_DIR_SIG.to_le_bytes().to_vec();
+        // Size of the remaining record
+        record.extend_from_slice(&44u64.to_le_bytes());
+        // Version made by/needed
+        record.extend_from_slice(&45u16.to_le_bytes());
+        record.extend_from_slice(&45u16.to_le_bytes());
+        // Number of this disk, disk with central directory
+        record.extend_from_slice(&[0u8; 8]);
+        record.extend_from_slice(&(entries.len() as u64).to_le_bytes());
+        record.extend_from_slice(&(entries.len() as u64).to_le_bytes());
+        record.extend_from_slice(&cd_size.to_le_bytes());
+        record.extend_from_slice(&cd_offset.to_le_bytes());
+
+        record.extend_from_slice(&ZIP64_LOCATOR_SIG.to_le_bytes());
+        record.extend_from_slice(&0u32.to_le_bytes());
+        record.extend_from_slice(&eocd64_offset.to_le_bytes());
+        // Total number of disks
+        record.extend_from_slice(&1u32.to_le_bytes());
+        writer.write_all(&record)?;
+    }
+
+    let num_entries = if zip64 { u16::MAX } else { entries.len() as u16 };
+    let mut eocd = END_OF_CENTRAL_DIR_SIG.to_le_bytes().to_vec();
+    // Number of this disk, disk with central directory
+    eocd.extend_from_slice(&[0u8; 4]);
+    eocd.extend_from_slice(&num_entries.to_le_bytes());
+    eocd.extend_from_slice(&num_entries.to_le_bytes());
+    eocd.extend_from_slice(&(cd_size.min(u32::MAX as u64) as u32).to_le_bytes());
+    eocd.extend_from_slice(&(cd_offset.min(u32::MAX as u64) as u32).to_le_bytes());
+    // Comment length
+    eocd.extend_from_slice(&0u16.to_le_bytes());
+    writer.write_all(&eocd)?;
+    writer.flush()?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod zip_tests {
+    use super::*;
+    use crate::utils::misc::{list_dir, TestDir};
+    use flate2::read::DeflateDecoder;
+    use std::io::Read;
+
+    fn read_u16(data: &[u8], pos: usize) -> u16 {
+        u16::from_le_bytes(data[pos..pos + 2].try_into().unwrap())
+    }
+
+    fn read_u32(data: &[u8], pos: usize) -> u32 {
+        u32::from_le_bytes(data[pos..pos + 4].try_into().unwrap())
+    }
+
+    #[test]
+    fn test_zip() {
+        let dir = TestDir::new("zip");
+        let input = dir.join("input");
+        fs::create_dir_all(input.join("sub")).unwrap();
+        fs::write(input.join("b.txt"), "bouf ".repeat(100)).unwrap();
+        fs::write(input.join("sub/a.txt"), "hello").unwrap();
+
+        let zip_a = dir.join("a.zip");
+        let zip_b = dir.join("b.zip");
+        let files = list_dir(&input).unwrap();
+        create_zip(&files, &zip_a, ZipCompression::Deflate, None).unwrap();
+        create_zip(&files, &zip_b, ZipCompression::Deflate, None).unwrap();
+        let data = fs::read(&zip_a).unwrap();
+        assert_eq!(data, fs::read(&zip_b).unwrap());
+
+        // End of central directory: two entries
+        let eocd = data.len() - 22;
+        assert_eq!(read_u32(&data, eocd), END_OF_CENTRAL_DIR_SIG);
+        assert_eq!(read_u16(&data, eocd + 10), 2);
+
+        // First entry (sorted by name) can be decompressed
+        assert_eq!(read_u32(&data, 0), LOCAL_HEADER_SIG);
+        assert_eq!(read_u16(&data, 8), METHOD_DEFLATE);
+        let compressed_size = read_u32(&data, 18) as usize;
+        let name_len = read_u16(&data, 26) as usize;
+        assert_eq!(&data[30..30 + name_len], b"b.txt");
+        let start = 30 + name_len;
+        let mut content = String::new();
+        DeflateDecoder::new(&data[start..start + compressed_size])
+            .read_to_string(&mut content)
+            .unwrap();
+        assert_eq!(content, "bouf ".repeat(100));
+        assert_eq!(read_u32(&data, 14), crc32fast::hash(content.as_bytes()));
+    }
+}